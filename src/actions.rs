@@ -29,7 +29,7 @@ pub async fn login(
 )> {
     let (client, config) = api_client_async().await?;
     let (kdf, iterations, memory, parallelism) =
-        client.prelogin(email).await?;
+        with_retries(config.max_retries, || client.prelogin(email)).await?;
 
     let identity = crate::identity::Identity::new(
         email,
@@ -39,14 +39,17 @@ pub async fn login(
         memory,
         parallelism,
     )?;
-    let (access_token, refresh_token, protected_key) = client
-        .login(
-            email,
-            &crate::config::device_id(&config).await?,
-            &identity.master_password_hash,
-            two_factor_token,
-            two_factor_provider,
-        )
+    let device_id = crate::config::device_id(&config).await?;
+    let (access_token, refresh_token, protected_key) =
+        with_retries(config.max_retries, || {
+            client.login(
+                email,
+                &device_id,
+                &identity.master_password_hash,
+                two_factor_token,
+                two_factor_provider,
+            )
+        })
         .await?;
 
     Ok((
@@ -127,6 +130,7 @@ pub async fn sync(
         String,
         String,
         std::collections::HashMap<String, String>,
+        std::collections::HashMap<String, String>,
         Vec<crate::db::Entry>,
     ),
 )> {
@@ -147,10 +151,11 @@ async fn sync_once(
     String,
     String,
     std::collections::HashMap<String, String>,
+    std::collections::HashMap<String, String>,
     Vec<crate::db::Entry>,
 )> {
-    let (client, _) = api_client_async().await?;
-    client.sync(access_token).await
+    let (client, config) = api_client_async().await?;
+    with_retries(config.max_retries, || client.sync(access_token)).await
 }
 
 pub fn add(
@@ -159,10 +164,11 @@ pub fn add(
     name: &str,
     data: &crate::db::EntryData,
     notes: Option<&str>,
+    fields: &[crate::db::Field],
     folder_id: Option<&str>,
-) -> Result<(Option<String>, ())> {
+) -> Result<(Option<String>, Option<crate::db::Entry>)> {
     with_exchange_refresh_token(access_token, refresh_token, |access_token| {
-        add_once(access_token, name, data, notes, folder_id)
+        add_once(access_token, name, data, notes, fields, folder_id)
     })
 }
 
@@ -171,11 +177,11 @@ fn add_once(
     name: &str,
     data: &crate::db::EntryData,
     notes: Option<&str>,
+    fields: &[crate::db::Field],
     folder_id: Option<&str>,
-) -> Result<()> {
+) -> Result<Option<crate::db::Entry>> {
     let (client, _) = api_client()?;
-    client.add(access_token, name, data, notes, folder_id)?;
-    Ok(())
+    client.add(access_token, name, data, notes, fields, folder_id)
 }
 
 pub fn edit(
@@ -257,6 +263,26 @@ fn list_folders_once(access_token: &str) -> Result<Vec<(String, String)>> {
     client.folders(access_token)
 }
 
+pub fn download_attachment(
+    access_token: &str,
+    refresh_token: &str,
+    cipher_id: &str,
+    attachment_id: &str,
+) -> Result<(Option<String>, Vec<u8>)> {
+    with_exchange_refresh_token(access_token, refresh_token, |access_token| {
+        download_attachment_once(access_token, cipher_id, attachment_id)
+    })
+}
+
+fn download_attachment_once(
+    access_token: &str,
+    cipher_id: &str,
+    attachment_id: &str,
+) -> Result<Vec<u8>> {
+    let (client, _) = api_client()?;
+    client.download_attachment(access_token, cipher_id, attachment_id)
+}
+
 pub fn create_folder(
     access_token: &str,
     refresh_token: &str,
@@ -272,6 +298,53 @@ fn create_folder_once(access_token: &str, name: &str) -> Result<String> {
     client.create_folder(access_token, name)
 }
 
+// retries requests which failed with a (probably) transient server error,
+// with exponential backoff
+async fn with_retries<F, Fut, T>(max_retries: u32, f: F) -> Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match f().await {
+            Err(e) if e.is_transient() && attempt < max_retries => {
+                let retry_after = match e {
+                    Error::RequestRateLimited { retry_after } => retry_after,
+                    _ => None,
+                };
+                let delay = retry_delay(
+                    attempt,
+                    retry_after,
+                    rand::Rng::gen_range(&mut rand::thread_rng(), 0.0..1.0),
+                );
+                log::warn!("{e}, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+// jitter is a random number in [0, 1), used to add up to 50% to the delay so
+// that multiple clients don't all retry at the same time
+fn retry_delay(
+    attempt: u32,
+    retry_after: Option<u64>,
+    jitter: f64,
+) -> std::time::Duration {
+    const MAX_DELAY_SECS: u64 = 60;
+    let max_delay = std::time::Duration::from_secs(MAX_DELAY_SECS);
+
+    if let Some(retry_after) = retry_after {
+        return std::time::Duration::from_secs(retry_after).min(max_delay);
+    }
+    let base = std::time::Duration::from_millis(500)
+        .saturating_mul(2_u32.saturating_pow(attempt));
+    base.mul_f64(1.0 + jitter / 2.0).min(max_delay)
+}
+
 fn with_exchange_refresh_token<F, T>(
     access_token: &str,
     refresh_token: &str,
@@ -327,12 +400,50 @@ async fn exchange_refresh_token_async(refresh_token: &str) -> Result<String> {
     client.exchange_refresh_token_async(refresh_token).await
 }
 
+// the network_timeout configuration option can be overridden either for the
+// whole process (by the command line client) or for a single task (by the
+// agent, while handling a request from a client which overrode it)
+static NETWORK_TIMEOUT: std::sync::Mutex<Option<u64>> =
+    std::sync::Mutex::new(None);
+tokio::task_local! {
+    static TASK_NETWORK_TIMEOUT: Option<u64>;
+}
+
+pub fn set_network_timeout(timeout: Option<u64>) {
+    *NETWORK_TIMEOUT.lock().unwrap() = timeout;
+}
+
+#[must_use]
+pub fn network_timeout_override() -> Option<u64> {
+    TASK_NETWORK_TIMEOUT
+        .try_with(|timeout| *timeout)
+        .ok()
+        .flatten()
+        .or_else(|| *NETWORK_TIMEOUT.lock().unwrap())
+}
+
+pub async fn with_network_timeout<F: std::future::Future>(
+    timeout: Option<u64>,
+    f: F,
+) -> F::Output {
+    TASK_NETWORK_TIMEOUT.scope(timeout, f).await
+}
+
+fn network_timeout(
+    config: &crate::config::Config,
+) -> Option<std::time::Duration> {
+    network_timeout_override()
+        .or(config.network_timeout)
+        .map(std::time::Duration::from_secs)
+}
+
 fn api_client() -> Result<(crate::api::Client, crate::config::Config)> {
     let config = crate::config::Config::load()?;
     let client = crate::api::Client::new(
         &config.base_url(),
         &config.identity_url(),
         config.client_cert_path(),
+        network_timeout(&config),
     );
     Ok((client, config))
 }
@@ -344,6 +455,25 @@ async fn api_client_async(
         &config.base_url(),
         &config.identity_url(),
         config.client_cert_path(),
+        network_timeout(&config),
     );
     Ok((client, config))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let ms = std::time::Duration::from_millis;
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(retry_delay(0, None, 0.0), ms(500));
+        assert_eq!(retry_delay(1, None, 0.0), ms(1000));
+        assert_eq!(retry_delay(2, None, 0.0), ms(2000));
+        assert_eq!(retry_delay(2, None, 0.5), ms(2500));
+        assert_eq!(retry_delay(30, None, 0.0), secs(60));
+        assert_eq!(retry_delay(0, Some(10), 0.9), secs(10));
+        assert_eq!(retry_delay(0, Some(3600), 0.0), secs(60));
+    }
+}
@@ -45,6 +45,66 @@ impl std::fmt::Display for UriMatchType {
     }
 }
 
+impl std::str::FromStr for UriMatchType {
+    type Err = Error;
+
+    fn from_str(ty: &str) -> Result<Self> {
+        #[allow(clippy::enum_glob_use)]
+        use UriMatchType::*;
+        match ty {
+            "domain" => Ok(Domain),
+            "host" => Ok(Host),
+            "starts_with" | "startswith" => Ok(StartsWith),
+            "exact" => Ok(Exact),
+            "regular_expression" | "regex" => Ok(RegularExpression),
+            "never" => Ok(Never),
+            _ => Err(Error::InvalidUriMatchType { ty: ty.to_string() }),
+        }
+    }
+}
+
+#[derive(
+    serde_repr::Serialize_repr,
+    serde_repr::Deserialize_repr,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+)]
+#[repr(u8)]
+pub enum FieldType {
+    Text = 0,
+    Hidden = 1,
+    Boolean = 2,
+    Linked = 3,
+}
+
+impl FieldType {
+    #[must_use]
+    pub fn from_repr(ty: u32) -> Option<Self> {
+        match ty {
+            0 => Some(Self::Text),
+            1 => Some(Self::Hidden),
+            2 => Some(Self::Boolean),
+            3 => Some(Self::Linked),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Text => "text",
+            Self::Hidden => "hidden",
+            Self::Boolean => "boolean",
+            Self::Linked => "linked",
+        };
+        write!(f, "{s}")
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TwoFactorProviderType {
     Authenticator = 0,
@@ -347,6 +407,8 @@ struct SyncResCipher {
     folder_id: Option<String>,
     #[serde(rename = "OrganizationId", alias = "organizationId")]
     organization_id: Option<String>,
+    #[serde(rename = "Type", alias = "type")]
+    ty: u32,
     #[serde(rename = "Name", alias = "name")]
     name: String,
     #[serde(rename = "Login", alias = "login")]
@@ -357,12 +419,20 @@ struct SyncResCipher {
     identity: Option<CipherIdentity>,
     #[serde(rename = "SecureNote", alias = "secureNote")]
     secure_note: Option<CipherSecureNote>,
+    #[serde(rename = "SshKey", alias = "sshKey")]
+    ssh_key: Option<CipherSshKey>,
     #[serde(rename = "Notes", alias = "notes")]
     notes: Option<String>,
     #[serde(rename = "PasswordHistory", alias = "passwordHistory")]
     password_history: Option<Vec<SyncResPasswordHistory>>,
     #[serde(rename = "Fields", alias = "fields")]
     fields: Option<Vec<SyncResField>>,
+    #[serde(rename = "Attachments", alias = "attachments")]
+    attachments: Option<Vec<SyncResAttachment>>,
+    #[serde(rename = "CreationDate", alias = "creationDate")]
+    creation_date: Option<String>,
+    #[serde(rename = "RevisionDate", alias = "revisionDate")]
+    revision_date: Option<String>,
     #[serde(rename = "DeletedDate", alias = "deletedDate")]
     deleted_date: Option<String>,
 }
@@ -406,6 +476,7 @@ impl SyncResCipher {
                 }
                 (folder_name, Some(folder_id))
             });
+        #[allow(clippy::option_if_let_else)] // one branch per cipher type
         let data = if let Some(login) = &self.login {
             crate::db::EntryData::Login {
                 username: login.username.clone(),
@@ -454,15 +525,22 @@ impl SyncResCipher {
                 passport_number: identity.passport_number.clone(),
                 username: identity.username.clone(),
             }
-        } else if let Some(_secure_note) = &self.secure_note {
+        } else if self.secure_note.is_some() {
             crate::db::EntryData::SecureNote
+        } else if let Some(ssh_key) = &self.ssh_key {
+            crate::db::EntryData::SshKey {
+                private_key: ssh_key.private_key.clone(),
+                public_key: ssh_key.public_key.clone(),
+                fingerprint: ssh_key.key_fingerprint.clone(),
+            }
         } else {
-            return None;
+            crate::db::EntryData::Unsupported { ty: self.ty }
         };
         let fields = self.fields.as_ref().map_or_else(Vec::new, |fields| {
             fields
                 .iter()
                 .map(|field| crate::db::Field {
+                    ty: FieldType::from_repr(field.ty),
                     name: field.name.clone(),
                     value: field.value.clone(),
                 })
@@ -478,6 +556,22 @@ impl SyncResCipher {
             fields,
             notes: self.notes.clone(),
             history,
+            creation_date: self.creation_date.clone(),
+            revision_date: self.revision_date.clone(),
+            attachments: self.attachments.as_ref().map_or_else(
+                Vec::new,
+                |attachments| {
+                    attachments
+                        .iter()
+                        .map(|attachment| crate::db::Attachment {
+                            id: attachment.id.clone(),
+                            file_name: attachment.file_name.clone(),
+                            key: attachment.key.clone(),
+                            size: attachment.size_name.clone(),
+                        })
+                        .collect()
+                },
+            ),
         })
     }
 }
@@ -498,6 +592,8 @@ struct SyncResProfileOrganization {
     id: String,
     #[serde(rename = "Key", alias = "key")]
     key: String,
+    #[serde(rename = "Name", alias = "name")]
+    name: Option<String>,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -587,6 +683,16 @@ struct CipherIdentity {
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 struct CipherSecureNote {}
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct CipherSshKey {
+    #[serde(rename = "PrivateKey", alias = "privateKey")]
+    private_key: Option<String>,
+    #[serde(rename = "PublicKey", alias = "publicKey")]
+    public_key: Option<String>,
+    #[serde(rename = "KeyFingerprint", alias = "keyFingerprint")]
+    key_fingerprint: Option<String>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 struct SyncResPasswordHistory {
     #[serde(rename = "LastUsedDate", alias = "lastUsedDate")]
@@ -595,6 +701,26 @@ struct SyncResPasswordHistory {
     password: Option<String>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct SyncResAttachment {
+    #[serde(rename = "Id", alias = "id")]
+    id: String,
+    #[serde(rename = "FileName", alias = "fileName")]
+    file_name: String,
+    #[serde(rename = "Key", alias = "key")]
+    key: Option<String>,
+    #[serde(rename = "SizeName", alias = "sizeName")]
+    size_name: Option<String>,
+}
+
+// the url in the sync response can expire, so a fresh one is requested
+// before downloading
+#[derive(serde::Deserialize, Debug)]
+struct AttachmentRes {
+    #[serde(rename = "Url", alias = "url")]
+    url: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 struct SyncResField {
     #[serde(rename = "Type", alias = "type")]
@@ -605,10 +731,22 @@ struct SyncResField {
     value: Option<String>,
 }
 
+// the numeric cipher type used by the bitwarden api
+fn cipher_type(data: &crate::db::EntryData) -> u32 {
+    match data {
+        crate::db::EntryData::Login { .. } => 1,
+        crate::db::EntryData::SecureNote => 2,
+        crate::db::EntryData::Card { .. } => 3,
+        crate::db::EntryData::Identity { .. } => 4,
+        crate::db::EntryData::SshKey { .. } => 5,
+        crate::db::EntryData::Unsupported { ty } => *ty,
+    }
+}
+
 #[derive(serde::Serialize, Debug)]
 struct CiphersPostReq {
     #[serde(rename = "type")]
-    ty: u32, // XXX what are the valid types?
+    ty: u32,
     #[serde(rename = "folderId")]
     folder_id: Option<String>,
     name: String,
@@ -618,12 +756,21 @@ struct CiphersPostReq {
     identity: Option<CipherIdentity>,
     #[serde(rename = "secureNote")]
     secure_note: Option<CipherSecureNote>,
+    fields: Option<Vec<CiphersPostReqField>>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct CiphersPostReqField {
+    #[serde(rename = "type")]
+    ty: FieldType,
+    name: Option<String>,
+    value: Option<String>,
 }
 
 #[derive(serde::Serialize, Debug)]
 struct CiphersPutReq {
     #[serde(rename = "type")]
-    ty: u32, // XXX what are the valid types?
+    ty: u32,
     #[serde(rename = "folderId")]
     folder_id: Option<String>,
     #[serde(rename = "organizationId")]
@@ -677,6 +824,7 @@ pub struct Client {
     base_url: String,
     identity_url: String,
     client_cert_path: Option<std::path::PathBuf>,
+    timeout: Option<std::time::Duration>,
 }
 
 impl Client {
@@ -685,16 +833,30 @@ impl Client {
         base_url: &str,
         identity_url: &str,
         client_cert_path: Option<&std::path::Path>,
+        timeout: Option<std::time::Duration>,
     ) -> Self {
         Self {
             base_url: base_url.to_string(),
             identity_url: identity_url.to_string(),
             client_cert_path: client_cert_path
                 .map(std::path::Path::to_path_buf),
+            timeout,
         }
     }
 
+    fn blocking_reqwest_client(&self) -> Result<reqwest::blocking::Client> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        builder.build().map_err(reqwest_error)
+    }
+
     async fn reqwest_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
         if let Some(client_cert_path) = self.client_cert_path.as_ref() {
             let mut buf = Vec::new();
             let mut f = tokio::fs::File::open(client_cert_path)
@@ -715,14 +877,14 @@ impl Client {
                     file: client_cert_path.clone(),
                 }
             })?;
-            Ok(reqwest::Client::builder().identity(pem).build().map_err(
-                |e| Error::LoadClientCertReqwest {
+            Ok(builder.identity(pem).build().map_err(|e| {
+                Error::LoadClientCertReqwest {
                     source: e,
                     file: client_cert_path.clone(),
-                },
-            )?)
+                }
+            })?)
         } else {
-            Ok(reqwest::Client::new())
+            builder.build().map_err(reqwest_error)
         }
     }
 
@@ -739,7 +901,8 @@ impl Client {
             .json(&prelogin)
             .send()
             .await
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
+        check_rate_limit(&res)?;
         let prelogin_res: PreloginRes = res.json_with_path().await?;
         Ok((
             prelogin_res.kdf,
@@ -779,7 +942,7 @@ impl Client {
             .form(&connect_req)
             .send()
             .await
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         if res.status() == reqwest::StatusCode::OK {
             Ok(())
         } else {
@@ -833,7 +996,8 @@ impl Client {
             )
             .send()
             .await
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
+        check_rate_limit(&res)?;
         if res.status() == reqwest::StatusCode::OK {
             let connect_res: ConnectPasswordRes =
                 res.json_with_path().await?;
@@ -867,6 +1031,7 @@ impl Client {
         String,
         String,
         std::collections::HashMap<String, String>,
+        std::collections::HashMap<String, String>,
         Vec<crate::db::Entry>,
     )> {
         let client = self.reqwest_client().await?;
@@ -875,7 +1040,8 @@ impl Client {
             .header("Authorization", format!("Bearer {access_token}"))
             .send()
             .await
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
+        check_rate_limit(&res)?;
         match res.status() {
             reqwest::StatusCode::OK => {
                 let sync_res: SyncRes = res.json_with_path().await?;
@@ -891,10 +1057,19 @@ impl Client {
                     .iter()
                     .map(|org| (org.id.clone(), org.key.clone()))
                     .collect();
+                let org_names = sync_res
+                    .profile
+                    .organizations
+                    .iter()
+                    .filter_map(|org| {
+                        org.name.clone().map(|name| (org.id.clone(), name))
+                    })
+                    .collect();
                 Ok((
                     sync_res.profile.key,
                     sync_res.profile.private_key,
                     org_keys,
+                    org_names,
                     ciphers,
                 ))
             }
@@ -913,10 +1088,11 @@ impl Client {
         name: &str,
         data: &crate::db::EntryData,
         notes: Option<&str>,
+        fields: &[crate::db::Field],
         folder_id: Option<&str>,
-    ) -> Result<()> {
+    ) -> Result<Option<crate::db::Entry>> {
         let mut req = CiphersPostReq {
-            ty: 1,
+            ty: cipher_type(data),
             folder_id: folder_id.map(std::string::ToString::to_string),
             name: name.to_string(),
             notes: notes.map(std::string::ToString::to_string),
@@ -924,6 +1100,20 @@ impl Client {
             card: None,
             identity: None,
             secure_note: None,
+            fields: if fields.is_empty() {
+                None
+            } else {
+                Some(
+                    fields
+                        .iter()
+                        .map(|field| CiphersPostReqField {
+                            ty: field.ty.unwrap_or(FieldType::Text),
+                            name: field.name.clone(),
+                            value: field.value.clone(),
+                        })
+                        .collect(),
+                )
+            },
         };
         match data {
             crate::db::EntryData::Login {
@@ -1010,16 +1200,33 @@ impl Client {
             crate::db::EntryData::SecureNote {} => {
                 req.secure_note = Some(CipherSecureNote {});
             }
+            // ssh keys are only supported for reading so far
+            crate::db::EntryData::SshKey { .. } => {
+                return Err(Error::UnsupportedCipherType { ty: 5 });
+            }
+            crate::db::EntryData::Unsupported { ty } => {
+                return Err(Error::UnsupportedCipherType { ty: *ty });
+            }
         }
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .post(self.api_url("/ciphers"))
             .header("Authorization", format!("Bearer {access_token}"))
             .json(&req)
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         match res.status() {
-            reqwest::StatusCode::OK => Ok(()),
+            reqwest::StatusCode::OK => {
+                // the entry was already created at this point, so failing to
+                // parse the response shouldn't be a fatal error
+                match res.json_with_path::<SyncResCipher>() {
+                    Ok(cipher) => Ok(cipher.to_entry(&[])),
+                    Err(e) => {
+                        log::warn!("failed to parse created cipher: {e}");
+                        Ok(None)
+                    }
+                }
+            }
             reqwest::StatusCode::UNAUTHORIZED => {
                 Err(Error::RequestUnauthorized)
             }
@@ -1041,7 +1248,7 @@ impl Client {
         history: &[crate::db::HistoryEntry],
     ) -> Result<()> {
         let mut req = CiphersPutReq {
-            ty: 1,
+            ty: cipher_type(data),
             folder_id: folder_uuid.map(std::string::ToString::to_string),
             organization_id: org_id.map(std::string::ToString::to_string),
             name: name.to_string(),
@@ -1143,14 +1350,21 @@ impl Client {
             crate::db::EntryData::SecureNote {} => {
                 req.secure_note = Some(CipherSecureNote {});
             }
+            // ssh keys are only supported for reading so far
+            crate::db::EntryData::SshKey { .. } => {
+                return Err(Error::UnsupportedCipherType { ty: 5 });
+            }
+            crate::db::EntryData::Unsupported { ty } => {
+                return Err(Error::UnsupportedCipherType { ty: *ty });
+            }
         }
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .put(self.api_url(&format!("/ciphers/{id}")))
             .header("Authorization", format!("Bearer {access_token}"))
             .json(&req)
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         match res.status() {
             reqwest::StatusCode::OK => Ok(()),
             reqwest::StatusCode::UNAUTHORIZED => {
@@ -1163,12 +1377,12 @@ impl Client {
     }
 
     pub fn remove(&self, access_token: &str, id: &str) -> Result<()> {
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .delete(self.api_url(&format!("/ciphers/{id}")))
             .header("Authorization", format!("Bearer {access_token}"))
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         match res.status() {
             reqwest::StatusCode::OK => Ok(()),
             reqwest::StatusCode::UNAUTHORIZED => {
@@ -1184,12 +1398,12 @@ impl Client {
         &self,
         access_token: &str,
     ) -> Result<Vec<(String, String)>> {
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .get(self.api_url("/folders"))
             .header("Authorization", format!("Bearer {access_token}"))
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         match res.status() {
             reqwest::StatusCode::OK => {
                 let folders_res: FoldersRes = res.json_with_path()?;
@@ -1208,6 +1422,48 @@ impl Client {
         }
     }
 
+    pub fn download_attachment(
+        &self,
+        access_token: &str,
+        cipher_id: &str,
+        attachment_id: &str,
+    ) -> Result<Vec<u8>> {
+        let client = self.blocking_reqwest_client()?;
+        let res = client
+            .get(self.api_url(&format!(
+                "/ciphers/{cipher_id}/attachment/{attachment_id}"
+            )))
+            .header("Authorization", format!("Bearer {access_token}"))
+            .send()
+            .map_err(reqwest_error)?;
+        let url = match res.status() {
+            reqwest::StatusCode::OK => {
+                let attachment_res: AttachmentRes = res.json_with_path()?;
+                attachment_res.url
+            }
+            reqwest::StatusCode::UNAUTHORIZED => {
+                return Err(Error::RequestUnauthorized)
+            }
+            _ => {
+                return Err(Error::RequestFailed {
+                    status: res.status().as_u16(),
+                })
+            }
+        };
+
+        // the download url is either signed or contains its own token, so
+        // it doesn't need the authorization header
+        let res = client.get(url).send().map_err(reqwest_error)?;
+        match res.status() {
+            reqwest::StatusCode::OK => {
+                Ok(res.bytes().map_err(reqwest_error)?.to_vec())
+            }
+            _ => Err(Error::RequestFailed {
+                status: res.status().as_u16(),
+            }),
+        }
+    }
+
     pub fn create_folder(
         &self,
         access_token: &str,
@@ -1216,13 +1472,13 @@ impl Client {
         let req = FoldersPostReq {
             name: name.to_string(),
         };
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .post(self.api_url("/folders"))
             .header("Authorization", format!("Bearer {access_token}"))
             .json(&req)
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         match res.status() {
             reqwest::StatusCode::OK => {
                 let folders_res: FoldersResData = res.json_with_path()?;
@@ -1246,12 +1502,12 @@ impl Client {
             client_id: "desktop".to_string(),
             refresh_token: refresh_token.to_string(),
         };
-        let client = reqwest::blocking::Client::new();
+        let client = self.blocking_reqwest_client()?;
         let res = client
             .post(self.identity_url("/connect/token"))
             .form(&connect_req)
             .send()
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         let connect_res: ConnectRefreshTokenRes = res.json_with_path()?;
         Ok(connect_res.access_token)
     }
@@ -1271,7 +1527,7 @@ impl Client {
             .form(&connect_req)
             .send()
             .await
-            .map_err(|source| Error::Reqwest { source })?;
+            .map_err(reqwest_error)?;
         let connect_res: ConnectRefreshTokenRes =
             res.json_with_path().await?;
         Ok(connect_res.access_token)
@@ -1342,3 +1598,25 @@ fn classify_login_error(error_res: &ConnectErrorRes, code: u16) -> Error {
     log::warn!("unexpected error received during login: {:?}", error_res);
     Error::RequestFailed { status: code }
 }
+
+fn reqwest_error(source: reqwest::Error) -> Error {
+    if source.is_timeout() {
+        Error::RequestTimeout
+    } else {
+        Error::Reqwest { source }
+    }
+}
+
+fn check_rate_limit(res: &reqwest::Response) -> Result<()> {
+    if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        // only the delay-seconds form of retry-after is supported, since
+        // that is what bitwarden servers send
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        return Err(Error::RequestRateLimited { retry_after });
+    }
+    Ok(())
+}
@@ -31,6 +31,7 @@ pub async fn register(
             };
             let client_id = rbw::pinentry::getpin(
                 &config_pinentry().await?,
+                &config_pinentry_title().await?,
                 "API key client__id",
                 &format!("Log in to {host}"),
                 err.as_deref(),
@@ -41,6 +42,7 @@ pub async fn register(
             .context("failed to read client_id from pinentry")?;
             let client_secret = rbw::pinentry::getpin(
                 &config_pinentry().await?,
+                &config_pinentry_title().await?,
                 "API key client__secret",
                 &format!("Log in to {host}"),
                 err.as_deref(),
@@ -109,7 +111,8 @@ pub async fn login(
             };
             let password = rbw::pinentry::getpin(
                 &config_pinentry().await?,
-                "Master Password",
+                &config_pinentry_title().await?,
+                &config_pinentry_prompt().await?,
                 &format!("Log in to {host}"),
                 err.as_deref(),
                 tty,
@@ -236,6 +239,7 @@ async fn two_factor(
         };
         let code = rbw::pinentry::getpin(
             &config_pinentry().await?,
+            &config_pinentry_title().await?,
             provider.header(),
             provider.message(),
             err.as_deref(),
@@ -412,7 +416,8 @@ pub async fn unlock(
             };
             let password = rbw::pinentry::getpin(
                 &config_pinentry().await?,
-                "Master Password",
+                &config_pinentry_title().await?,
+                &config_pinentry_prompt().await?,
                 &format!(
                     "Unlock the local database for '{}'",
                     rbw::dirs::profile()
@@ -463,9 +468,67 @@ async fn unlock_success(
     keys: rbw::locked::Keys,
     org_keys: std::collections::HashMap<String, rbw::locked::Keys>,
 ) -> anyhow::Result<()> {
+    {
+        let mut state = state.lock().await;
+        state.priv_key = Some(keys);
+        state.org_keys = Some(org_keys);
+    }
+    // the cache is only an optimization, so this shouldn't fail unlocking
+    if let Err(e) = prewarm_decrypt_cache(state).await {
+        eprintln!("failed to prewarm decrypt cache: {e:#}");
+    }
+    Ok(())
+}
+
+// with the prewarm option, every entry is decrypted into the decrypt cache
+// as soon as the agent is unlocked (and again after each sync), instead of
+// as each entry is first displayed
+async fn prewarm_decrypt_cache(
+    state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
+) -> anyhow::Result<()> {
+    let config = rbw::config::Config::load_async().await?;
+    if !config.decrypt_cache || !config.prewarm {
+        return Ok(());
+    }
+    let db = load_db().await?;
+
     let mut state = state.lock().await;
-    state.priv_key = Some(keys);
-    state.org_keys = Some(org_keys);
+    if state.needs_unlock() {
+        return Ok(());
+    }
+    let mut cache = std::collections::HashMap::new();
+    for entry in &db.entries {
+        // values which fail to decrypt are left out, so that they are
+        // reported by the cli as usual when it tries to decrypt them itself
+        let mut plaintexts = std::collections::HashMap::new();
+        let folder = entry
+            .folder
+            .as_deref()
+            .map(|folder| (folder, state.key(None)));
+        let keys = state.key(entry.org_id.as_deref());
+        let cipherstrings = entry
+            .cipherstrings()
+            .into_iter()
+            .map(|cipherstring| (cipherstring, keys));
+        for (cipherstring, keys) in folder.into_iter().chain(cipherstrings) {
+            let Some(keys) = keys else {
+                continue;
+            };
+            if let Ok(plaintext) = decrypt_cipherstring(keys, cipherstring) {
+                plaintexts.insert(cipherstring.to_string(), plaintext);
+            }
+        }
+        cache.insert(
+            entry.id.clone(),
+            rbw::protocol::DecryptCacheEntry {
+                id: entry.id.clone(),
+                revision_date: entry.revision_date.clone(),
+                plaintexts,
+            },
+        );
+    }
+    state.decrypt_cache = cache;
+
     Ok(())
 }
 
@@ -512,7 +575,13 @@ pub async fn sync(
     };
     let (
         access_token,
-        (protected_key, protected_private_key, protected_org_keys, entries),
+        (
+            protected_key,
+            protected_private_key,
+            protected_org_keys,
+            org_names,
+            entries,
+        ),
     ) = rbw::actions::sync(&access_token, &refresh_token)
         .await
         .context("failed to sync database from server")?;
@@ -522,8 +591,14 @@ pub async fn sync(
     db.protected_key = Some(protected_key);
     db.protected_private_key = Some(protected_private_key);
     db.protected_org_keys = protected_org_keys;
+    db.org_names = org_names;
+    db.pending.clear();
     db.entries = entries;
     save_db(&db).await?;
+    state.lock().await.decrypt_cache.clear();
+    if let Err(e) = prewarm_decrypt_cache(state.clone()).await {
+        eprintln!("failed to prewarm decrypt cache: {e:#}");
+    }
 
     if let Err(e) = subscribe_to_notifications(state.clone()).await {
         eprintln!("failed to subscribe to notifications: {e}");
@@ -549,16 +624,80 @@ pub async fn decrypt(
             "failed to find decryption keys in in-memory state"
         ));
     };
+    let plaintext = decrypt_cipherstring(keys, cipherstring)?;
+
+    respond_decrypt(sock, plaintext).await?;
+
+    Ok(())
+}
+
+fn decrypt_cipherstring(
+    keys: &rbw::locked::Keys,
+    cipherstring: &str,
+) -> anyhow::Result<String> {
     let cipherstring = rbw::cipherstring::CipherString::new(cipherstring)
         .context("failed to parse encrypted secret")?;
-    let plaintext = String::from_utf8(
+    String::from_utf8(
         cipherstring
             .decrypt_symmetric(keys)
             .context("failed to decrypt encrypted secret")?,
     )
-    .context("failed to parse decrypted secret")?;
+    .context("failed to parse decrypted secret")
+}
 
-    respond_decrypt(sock, plaintext).await?;
+pub async fn decrypt_cache_load(
+    sock: &mut crate::sock::Sock,
+    state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
+    revisions: &std::collections::HashMap<String, Option<String>>,
+) -> anyhow::Result<()> {
+    let mut state = state.lock().await;
+    // entries which have been changed since they were cached will never be
+    // requested again, so there's no point in keeping them
+    state.decrypt_cache.retain(|id, entry| match revisions.get(id) {
+        Some(revision) => revision == &entry.revision_date,
+        None => true,
+    });
+    let entries = if state.needs_unlock() {
+        vec![]
+    } else {
+        revisions
+            .keys()
+            .filter_map(|id| state.decrypt_cache.get(id))
+            .cloned()
+            .collect()
+    };
+
+    sock.send(&rbw::protocol::Response::DecryptCache { entries })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn decrypt_cache_store(
+    sock: &mut crate::sock::Sock,
+    state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
+    entries: Vec<rbw::protocol::DecryptCacheEntry>,
+) -> anyhow::Result<()> {
+    let mut state = state.lock().await;
+    // don't let anything be cached after the agent was locked
+    if !state.needs_unlock() {
+        for entry in entries {
+            state.decrypt_cache.insert(entry.id.clone(), entry);
+        }
+    }
+
+    respond_ack(sock).await?;
+
+    Ok(())
+}
+
+pub async fn decrypt_cache_clear(
+    sock: &mut crate::sock::Sock,
+    state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
+) -> anyhow::Result<()> {
+    state.lock().await.decrypt_cache.clear();
+
+    respond_ack(sock).await?;
 
     Ok(())
 }
@@ -587,19 +726,86 @@ pub async fn encrypt(
     Ok(())
 }
 
+pub async fn decrypt_attachment(
+    sock: &mut crate::sock::Sock,
+    state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
+    data: &str,
+    key: Option<&str>,
+    org_id: Option<&str>,
+) -> anyhow::Result<()> {
+    let state = state.lock().await;
+    let Some(keys) = state.key(org_id)
+    else {
+        return Err(anyhow::anyhow!(
+            "failed to find decryption keys in in-memory state"
+        ));
+    };
+
+    // attachments with their own key have that key encrypted with the
+    // entry's key
+    let attachment_keys = key
+        .map(|key| -> anyhow::Result<_> {
+            let key = rbw::cipherstring::CipherString::new(key)
+                .context("failed to parse encrypted attachment key")?
+                .decrypt_locked_symmetric(keys)
+                .context("failed to decrypt attachment key")?;
+            if key.data().len() != 64 {
+                return Err(anyhow::anyhow!(
+                    "attachment key has the wrong length ({} bytes)",
+                    key.data().len()
+                ));
+            }
+            Ok(rbw::locked::Keys::new(key))
+        })
+        .transpose()?;
+
+    let data = rbw::base64::decode(data)
+        .context("failed to parse encrypted attachment")?;
+    let plaintext = rbw::cipherstring::CipherString::from_bytes(&data)
+        .context("failed to parse encrypted attachment")?
+        .decrypt_symmetric(attachment_keys.as_ref().unwrap_or(keys))
+        .context("failed to decrypt attachment")?;
+
+    sock.send(&rbw::protocol::Response::DecryptAttachment {
+        data: rbw::base64::encode(plaintext),
+    })
+    .await?;
+
+    Ok(())
+}
+
 pub async fn clipboard_store(
     sock: &mut crate::sock::Sock,
     state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
     text: &str,
+    clear_after: Option<u64>,
 ) -> anyhow::Result<()> {
-    state
-        .lock()
+    let selection = rbw::config::Config::load_async()
         .await
-        .clipboard
-        .set_contents(text.to_owned())
-        .map_err(|e| {
+        .map(|config| config.clipboard_selection)
+        .unwrap_or_default();
+    for clipboard in state.lock().await.clipboards(selection) {
+        clipboard.set_contents(text.to_owned()).map_err(|e| {
             anyhow::anyhow!("couldn't store value to clipboard: {e}")
         })?;
+    }
+
+    if let Some(clear_after) = clear_after {
+        let text = text.to_owned();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(clear_after))
+                .await;
+            for clipboard in state.lock().await.clipboards(selection) {
+                // don't clobber anything that was copied in the meantime
+                if clipboard.get_contents().ok().as_ref() != Some(&text) {
+                    continue;
+                }
+                if let Err(e) = clipboard.set_contents(String::new()) {
+                    log::warn!("couldn't clear clipboard: {e}");
+                }
+            }
+        });
+    }
 
     respond_ack(sock).await?;
 
@@ -615,6 +821,15 @@ pub async fn version(sock: &mut crate::sock::Sock) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn data_dir(sock: &mut crate::sock::Sock) -> anyhow::Result<()> {
+    sock.send(&rbw::protocol::Response::DataDir {
+        dir: rbw::dirs::db_dir().to_string_lossy().into_owned(),
+    })
+    .await?;
+
+    Ok(())
+}
+
 async fn respond_ack(sock: &mut crate::sock::Sock) -> anyhow::Result<()> {
     sock.send(&rbw::protocol::Response::Ack).await?;
 
@@ -681,6 +896,16 @@ async fn config_pinentry() -> anyhow::Result<String> {
     Ok(config.pinentry)
 }
 
+async fn config_pinentry_title() -> anyhow::Result<String> {
+    let config = rbw::config::Config::load_async().await?;
+    Ok(config.pinentry_title())
+}
+
+async fn config_pinentry_prompt() -> anyhow::Result<String> {
+    let config = rbw::config::Config::load_async().await?;
+    Ok(config.pinentry_prompt())
+}
+
 pub async fn subscribe_to_notifications(
     state: std::sync::Arc<tokio::sync::Mutex<crate::agent::State>>,
 ) -> anyhow::Result<()> {
@@ -13,6 +13,9 @@ pub struct State {
     pub sync_timeout_duration: std::time::Duration,
     pub notifications_handler: crate::notifications::Handler,
     pub clipboard: Box<dyn copypasta::ClipboardProvider>,
+    pub primary: Box<dyn copypasta::ClipboardProvider>,
+    pub decrypt_cache:
+        std::collections::HashMap<String, rbw::protocol::DecryptCacheEntry>,
 }
 
 impl State {
@@ -33,12 +36,79 @@ impl State {
     pub fn clear(&mut self) {
         self.priv_key = None;
         self.org_keys = None;
+        self.decrypt_cache.clear();
         self.timeout.clear();
     }
 
     pub fn set_sync_timeout(&mut self) {
         self.sync_timeout.set(self.sync_timeout_duration);
     }
+
+    pub fn clipboards(
+        &mut self,
+        selection: rbw::config::ClipboardSelection,
+    ) -> Vec<&mut Box<dyn copypasta::ClipboardProvider>> {
+        match selection {
+            rbw::config::ClipboardSelection::Clipboard => {
+                vec![&mut self.clipboard]
+            }
+            rbw::config::ClipboardSelection::Primary => {
+                vec![&mut self.primary]
+            }
+            rbw::config::ClipboardSelection::Both => {
+                vec![&mut self.clipboard, &mut self.primary]
+            }
+        }
+    }
+}
+
+fn clipboard_or_nop<C: copypasta::ClipboardProvider + 'static>(
+    name: &str,
+    clipboard: Result<C, impl std::fmt::Display>,
+) -> Box<dyn copypasta::ClipboardProvider> {
+    match clipboard {
+        Ok(clipboard) => Box::new(clipboard),
+        Err(e) => {
+            log::warn!("couldn't create {name} context: {e}");
+            // infallible
+            Box::new(
+                copypasta::nop_clipboard::NopClipboardContext::new().unwrap(),
+            )
+        }
+    }
+}
+
+// the primary selection only exists on x11 (including xwayland)
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+))]
+fn primary_selection() -> Box<dyn copypasta::ClipboardProvider> {
+    clipboard_or_nop(
+        "primary selection",
+        copypasta::x11_clipboard::X11ClipboardContext::<
+            copypasta::x11_clipboard::Primary,
+        >::new(),
+    )
+}
+
+#[cfg(not(all(
+    unix,
+    not(any(
+        target_os = "macos",
+        target_os = "android",
+        target_os = "ios",
+        target_os = "emscripten"
+    ))
+)))]
+fn primary_selection() -> Box<dyn copypasta::ClipboardProvider> {
+    // infallible
+    Box::new(copypasta::nop_clipboard::NopClipboardContext::new().unwrap())
 }
 
 pub struct Agent {
@@ -60,26 +130,9 @@ impl Agent {
             sync_timeout.set(sync_timeout_duration);
         }
         let notifications_handler = crate::notifications::Handler::new();
-        let clipboard: Box<dyn copypasta::ClipboardProvider> =
-            copypasta::ClipboardContext::new().map_or_else(
-                |e| {
-                    log::warn!("couldn't create clipboard context: {e}");
-                    let clipboard = Box::new(
-                        // infallible
-                        copypasta::nop_clipboard::NopClipboardContext::new()
-                            .unwrap(),
-                    );
-                    let clipboard: Box<dyn copypasta::ClipboardProvider> =
-                        clipboard;
-                    clipboard
-                },
-                |clipboard| {
-                    let clipboard = Box::new(clipboard);
-                    let clipboard: Box<dyn copypasta::ClipboardProvider> =
-                        clipboard;
-                    clipboard
-                },
-            );
+        let clipboard =
+            clipboard_or_nop("clipboard", copypasta::ClipboardContext::new());
+        let primary = primary_selection();
         Ok(Self {
             timer_r,
             sync_timer_r,
@@ -92,6 +145,8 @@ impl Agent {
                 sync_timeout_duration,
                 notifications_handler,
                 clipboard,
+                primary,
+                decrypt_cache: std::collections::HashMap::new(),
             })),
         })
     }
@@ -193,7 +248,25 @@ async fn handle_request(
             return Ok(());
         }
     };
-    let set_timeout = match &req.action {
+    let set_timeout = rbw::actions::with_network_timeout(
+        req.network_timeout,
+        Box::pin(handle_action(sock, state.clone(), &req)),
+    )
+    .await?;
+
+    if set_timeout {
+        state.lock().await.set_timeout();
+    }
+
+    Ok(())
+}
+
+async fn handle_action(
+    sock: &mut crate::sock::Sock,
+    state: std::sync::Arc<tokio::sync::Mutex<State>>,
+    req: &rbw::protocol::Request,
+) -> anyhow::Result<bool> {
+    Ok(match &req.action {
         rbw::protocol::Action::Register => {
             crate::actions::register(sock, req.tty.as_deref()).await?;
             true
@@ -248,9 +321,47 @@ async fn handle_request(
             .await?;
             true
         }
-        rbw::protocol::Action::ClipboardStore { text } => {
-            crate::actions::clipboard_store(sock, state.clone(), text)
-                .await?;
+        rbw::protocol::Action::DecryptAttachment { data, key, org_id } => {
+            crate::actions::decrypt_attachment(
+                sock,
+                state.clone(),
+                data,
+                key.as_deref(),
+                org_id.as_deref(),
+            )
+            .await?;
+            true
+        }
+        rbw::protocol::Action::DecryptCacheLoad { revisions } => {
+            crate::actions::decrypt_cache_load(
+                sock,
+                state.clone(),
+                revisions,
+            )
+            .await?;
+            true
+        }
+        rbw::protocol::Action::DecryptCacheStore { entries } => {
+            crate::actions::decrypt_cache_store(
+                sock,
+                state.clone(),
+                entries.clone(),
+            )
+            .await?;
+            true
+        }
+        rbw::protocol::Action::DecryptCacheClear => {
+            crate::actions::decrypt_cache_clear(sock, state.clone()).await?;
+            false
+        }
+        rbw::protocol::Action::ClipboardStore { text, clear_after } => {
+            crate::actions::clipboard_store(
+                sock,
+                state.clone(),
+                text,
+                *clear_after,
+            )
+            .await?;
             true
         }
         rbw::protocol::Action::Quit => std::process::exit(0),
@@ -258,11 +369,9 @@ async fn handle_request(
             crate::actions::version(sock).await?;
             true
         }
-    };
-
-    if set_timeout {
-        state.lock().await.set_timeout();
-    }
-
-    Ok(())
+        rbw::protocol::Action::DataDir => {
+            crate::actions::data_dir(sock).await?;
+            true
+        }
+    })
 }
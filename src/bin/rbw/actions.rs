@@ -32,12 +32,7 @@ pub fn quit() -> anyhow::Result<()> {
             let mut pid = String::new();
             std::fs::File::open(pidfile)?.read_to_string(&mut pid)?;
             let pid = nix::unistd::Pid::from_raw(pid.trim_end().parse()?);
-            sock.send(&rbw::protocol::Request {
-                tty: nix::unistd::ttyname(0).ok().and_then(|p| {
-                    p.to_str().map(std::string::ToString::to_string)
-                }),
-                action: rbw::protocol::Action::Quit,
-            })?;
+            sock.send(&request(rbw::protocol::Action::Quit))?;
             wait_for_exit(pid);
             Ok(())
         }
@@ -51,70 +46,168 @@ pub fn quit() -> anyhow::Result<()> {
     }
 }
 
+// decrypts a cipherstring with the given org key (or the user's key)
+pub type DecryptFn<'a> =
+    dyn Fn(&str, Option<&str>) -> anyhow::Result<String> + 'a;
+
+// when the decrypt_cache configuration option is enabled, the agent keeps the
+// decrypted fields of each entry (until it is locked), keyed by the entry's
+// id and revision, so that later commands don't need to ask it to decrypt
+// every field again one at a time. this holds what was loaded from the agent
+// for the current command, along with anything newly decrypted which should
+// be sent back to it by store.
+pub struct DecryptCache {
+    entries: std::sync::Mutex<
+        std::collections::HashMap<String, rbw::protocol::DecryptCacheEntry>,
+    >,
+    updated: std::sync::Mutex<Vec<rbw::protocol::DecryptCacheEntry>>,
+}
+
+impl DecryptCache {
+    pub fn load<'a>(
+        entries: impl IntoIterator<Item = &'a rbw::db::Entry>,
+    ) -> anyhow::Result<Self> {
+        let mut sock = connect()?;
+        sock.send(&request(rbw::protocol::Action::DecryptCacheLoad {
+            revisions: entries
+                .into_iter()
+                .map(|entry| (entry.id.clone(), entry.revision_date.clone()))
+                .collect(),
+        }))?;
+
+        let res = sock.recv()?;
+        match res {
+            rbw::protocol::Response::DecryptCache { entries } => Ok(Self {
+                entries: std::sync::Mutex::new(
+                    entries
+                        .into_iter()
+                        .map(|entry| (entry.id.clone(), entry))
+                        .collect(),
+                ),
+                updated: std::sync::Mutex::new(vec![]),
+            }),
+            rbw::protocol::Response::Error { error } => Err(anyhow::anyhow!(
+                "failed to load decrypt cache: {}",
+                error
+            )),
+            _ => Err(anyhow::anyhow!("unexpected message: {:?}", res)),
+        }
+    }
+
+    // calls to the decrypt function passed to f are answered from (and
+    // recorded in) the cache for the given entry
+    pub fn with_entry<T>(
+        &self,
+        entry: &rbw::db::Entry,
+        f: impl FnOnce(&DecryptFn<'_>) -> T,
+    ) -> T {
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&entry.id)
+            .filter(|cached| cached.revision_date == entry.revision_date)
+            .cloned()
+            .unwrap_or_else(|| rbw::protocol::DecryptCacheEntry {
+                id: entry.id.clone(),
+                revision_date: entry.revision_date.clone(),
+                plaintexts: std::collections::HashMap::new(),
+            });
+        let known = cached.plaintexts.len();
+        let cached = std::cell::RefCell::new(cached);
+
+        let res = f(&|cipherstring, org_id| {
+            if let Some(plaintext) =
+                cached.borrow().plaintexts.get(cipherstring)
+            {
+                return Ok(plaintext.clone());
+            }
+            let plaintext = decrypt(cipherstring, org_id)?;
+            cached
+                .borrow_mut()
+                .plaintexts
+                .insert(cipherstring.to_string(), plaintext.clone());
+            Ok(plaintext)
+        });
+
+        let cached = cached.into_inner();
+        if cached.plaintexts.len() > known {
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(cached.id.clone(), cached.clone());
+            self.updated.lock().unwrap().push(cached);
+        }
+        res
+    }
+
+    pub fn store(self) -> anyhow::Result<()> {
+        let entries = self.updated.into_inner().unwrap();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        simple_action(rbw::protocol::Action::DecryptCacheStore { entries })
+    }
+}
+
+pub fn clear_decrypt_cache() -> anyhow::Result<()> {
+    simple_action(rbw::protocol::Action::DecryptCacheClear)
+}
+
+// these go through the library's agent client, so that rbw::vault::Vault
+// decrypts and encrypts entries exactly the same way
 pub fn decrypt(
     cipherstring: &str,
     org_id: Option<&str>,
 ) -> anyhow::Result<String> {
-    let mut sock = connect()?;
-    sock.send(&rbw::protocol::Request {
-        tty: nix::unistd::ttyname(0)
-            .ok()
-            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
-        action: rbw::protocol::Action::Decrypt {
-            cipherstring: cipherstring.to_string(),
-            org_id: org_id.map(std::string::ToString::to_string),
-        },
-    })?;
-
-    let res = sock.recv()?;
-    match res {
-        rbw::protocol::Response::Decrypt { plaintext } => Ok(plaintext),
-        rbw::protocol::Response::Error { error } => {
-            Err(anyhow::anyhow!("failed to decrypt: {}", error))
-        }
-        _ => Err(anyhow::anyhow!("unexpected message: {:?}", res)),
-    }
+    rbw::vault::decrypt(cipherstring, org_id).context("failed to decrypt")
 }
 
 pub fn encrypt(
     plaintext: &str,
     org_id: Option<&str>,
 ) -> anyhow::Result<String> {
+    rbw::vault::encrypt(plaintext, org_id).context("failed to encrypt")
+}
+
+pub fn decrypt_attachment(
+    data: &[u8],
+    key: Option<&str>,
+    org_id: Option<&str>,
+) -> anyhow::Result<Vec<u8>> {
     let mut sock = connect()?;
-    sock.send(&rbw::protocol::Request {
-        tty: nix::unistd::ttyname(0)
-            .ok()
-            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
-        action: rbw::protocol::Action::Encrypt {
-            plaintext: plaintext.to_string(),
-            org_id: org_id.map(std::string::ToString::to_string),
-        },
-    })?;
+    sock.send(&request(rbw::protocol::Action::DecryptAttachment {
+        data: rbw::base64::encode(data),
+        key: key.map(std::string::ToString::to_string),
+        org_id: org_id.map(std::string::ToString::to_string),
+    }))?;
 
     let res = sock.recv()?;
     match res {
-        rbw::protocol::Response::Encrypt { cipherstring } => Ok(cipherstring),
+        rbw::protocol::Response::DecryptAttachment { data } => {
+            rbw::base64::decode(data)
+                .context("failed to parse decrypted attachment")
+        }
         rbw::protocol::Response::Error { error } => {
-            Err(anyhow::anyhow!("failed to encrypt: {}", error))
+            Err(anyhow::anyhow!("failed to decrypt: {}", error))
         }
         _ => Err(anyhow::anyhow!("unexpected message: {:?}", res)),
     }
 }
 
-pub fn clipboard_store(text: &str) -> anyhow::Result<()> {
+pub fn clipboard_store(
+    text: &str,
+    clear_after: Option<u64>,
+) -> anyhow::Result<()> {
     simple_action(rbw::protocol::Action::ClipboardStore {
         text: text.to_string(),
+        clear_after,
     })
 }
 
 pub fn version() -> anyhow::Result<u32> {
     let mut sock = connect()?;
-    sock.send(&rbw::protocol::Request {
-        tty: nix::unistd::ttyname(0)
-            .ok()
-            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
-        action: rbw::protocol::Action::Version,
-    })?;
+    sock.send(&request(rbw::protocol::Action::Version))?;
 
     let res = sock.recv()?;
     match res {
@@ -129,12 +222,7 @@ pub fn version() -> anyhow::Result<u32> {
 fn simple_action(action: rbw::protocol::Action) -> anyhow::Result<()> {
     let mut sock = connect()?;
 
-    sock.send(&rbw::protocol::Request {
-        tty: nix::unistd::ttyname(0)
-            .ok()
-            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
-        action,
-    })?;
+    sock.send(&request(action))?;
 
     let res = sock.recv()?;
     match res {
@@ -146,6 +234,30 @@ fn simple_action(action: rbw::protocol::Action) -> anyhow::Result<()> {
     }
 }
 
+pub fn data_dir() -> anyhow::Result<String> {
+    let mut sock = connect()?;
+    sock.send(&request(rbw::protocol::Action::DataDir))?;
+
+    let res = sock.recv()?;
+    match res {
+        rbw::protocol::Response::DataDir { dir } => Ok(dir),
+        rbw::protocol::Response::Error { error } => {
+            Err(anyhow::anyhow!("failed to get data directory: {}", error))
+        }
+        _ => Err(anyhow::anyhow!("unexpected message: {:?}", res)),
+    }
+}
+
+fn request(action: rbw::protocol::Action) -> rbw::protocol::Request {
+    rbw::protocol::Request {
+        tty: nix::unistd::ttyname(0)
+            .ok()
+            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
+        action,
+        network_timeout: rbw::actions::network_timeout_override(),
+    }
+}
+
 fn connect() -> anyhow::Result<crate::sock::Sock> {
     crate::sock::Sock::connect().with_context(|| {
         let log = rbw::dirs::agent_stderr_file();
@@ -1,7 +1,12 @@
 use anyhow::Context as _;
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
 use serde::Serialize;
 use std::io;
-use std::io::prelude::Write;
+use std::io::prelude::{Read as _, Write};
+use std::io::IsTerminal as _;
+use unicode_normalization::UnicodeNormalization as _;
+
+const DECRYPT_FAILED: &str = "<decryption failed>";
 
 const MISSING_CONFIG_HELP: &str =
     "Before using rbw, you must configure the email address you would like to \
@@ -23,29 +28,43 @@ struct DecryptedCipher {
     fields: Vec<DecryptedField>,
     notes: Option<String>,
     history: Vec<DecryptedHistoryEntry>,
+    creation_date: Option<String>,
+    revision_date: Option<String>,
+    attachments: Vec<DecryptedAttachment>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed_fields: Vec<String>,
 }
 
 impl DecryptedCipher {
-    fn display_short(&self, desc: &str, clipboard: bool) -> bool {
-        match &self.data {
-            DecryptedData::Login { password, .. } => {
-                password.as_ref().map_or_else(
-                    || {
-                        eprintln!("entry for '{desc}' had no password");
-                        false
-                    },
-                    |password| val_display_or_store(clipboard, password),
-                )
+    fn display_short(&self, desc: &str, clipboard: Clipboard) -> bool {
+        match self.short_value(desc) {
+            Ok(value) => val_display_or_store(clipboard, &value),
+            Err(e) => {
+                // the other fields are still displayed, so this is only
+                // informational (and can be silenced with --quiet)
+                log::warn!("{e}");
+                false
             }
-            DecryptedData::Card { number, .. } => {
-                number.as_ref().map_or_else(
-                    || {
-                        eprintln!("entry for '{desc}' had no card number");
-                        false
-                    },
-                    |number| val_display_or_store(clipboard, number),
-                )
+        }
+    }
+
+    // the password, or whatever the equivalent is for other entry types
+    fn short_value(&self, desc: &str) -> anyhow::Result<String> {
+        let missing = |field, what| {
+            if self.decrypt_failed(field) {
+                anyhow::anyhow!("failed to decrypt {what} for '{desc}'")
+            } else {
+                MissingValue(format!("entry for '{desc}' had no {what}"))
+                    .into()
             }
+        };
+        match &self.data {
+            DecryptedData::Login { password, .. } => password
+                .clone()
+                .ok_or_else(|| missing("password", "password")),
+            DecryptedData::Card { number, .. } => number
+                .clone()
+                .ok_or_else(|| missing("number", "card number")),
             DecryptedData::Identity {
                 title,
                 first_name,
@@ -61,79 +80,145 @@ impl DecryptedCipher {
                         .cloned()
                         .collect();
                 if names.is_empty() {
-                    eprintln!("entry for '{desc}' had no name");
-                    false
+                    Err(MissingValue(format!(
+                        "entry for '{desc}' had no name"
+                    ))
+                    .into())
                 } else {
-                    val_display_or_store(clipboard, &names.join(" "))
+                    Ok(names.join(" "))
                 }
             }
-            DecryptedData::SecureNote {} => self.notes.as_ref().map_or_else(
-                || {
-                    eprintln!("entry for '{desc}' had no notes");
-                    false
-                },
-                |notes| val_display_or_store(clipboard, notes),
-            ),
+            DecryptedData::SecureNote {} => {
+                self.notes.clone().ok_or_else(|| missing("notes", "notes"))
+            }
+            DecryptedData::SshKey { private_key, .. } => private_key
+                .clone()
+                .ok_or_else(|| missing("private_key", "private key")),
         }
     }
 
-    fn display_field(&self, desc: &str, field: &str, clipboard: bool) {
+    // like short_value, but for listing several entries at once, where a
+    // missing value shouldn't stop the others from being shown
+    fn short_value_or_failed(&self) -> String {
+        let fields: &[&str] = match &self.data {
+            DecryptedData::Login { .. } => &["password"],
+            DecryptedData::Card { .. } => &["number"],
+            DecryptedData::Identity { .. } => {
+                &["title", "first_name", "middle_name", "last_name"]
+            }
+            DecryptedData::SecureNote => &["notes"],
+            DecryptedData::SshKey { .. } => &["private_key"],
+        };
+        self.short_value("").unwrap_or_else(|_| {
+            if fields.iter().any(|field| self.decrypt_failed(field)) {
+                DECRYPT_FAILED.to_string()
+            } else {
+                String::new()
+            }
+        })
+    }
+
+    fn display_field(
+        &self,
+        desc: &str,
+        field: &str,
+        clipboard: Clipboard,
+        totp_windows: &std::ops::RangeInclusive<u64>,
+    ) -> anyhow::Result<()> {
+        let value = self.field_value(desc, field, totp_windows)?.ok_or_else(
+            || anyhow::anyhow!("entry '{desc}' has no field '{field}'"),
+        )?;
+        val_display_or_store(clipboard, &value);
+        Ok(())
+    }
+
+    fn field_value(
+        &self,
+        desc: &str,
+        field: &str,
+        totp_windows: &std::ops::RangeInclusive<u64>,
+    ) -> anyhow::Result<Option<String>> {
         let field = field.to_lowercase();
         let field = field.as_str();
+        if field == "password" {
+            if let Some(ty) = match &self.data {
+                DecryptedData::Login { .. } => None,
+                DecryptedData::Card { .. } => Some("card"),
+                DecryptedData::Identity { .. } => Some("identity"),
+                DecryptedData::SecureNote => Some("secure note"),
+                DecryptedData::SshKey { .. } => Some("ssh key"),
+            } {
+                // only an exactly named custom field is used here, since
+                // whatever the partial match below finds is unlikely to be
+                // what was meant
+                let f = self
+                    .fields
+                    .iter()
+                    .find(|f| {
+                        f.name.as_deref().is_some_and(|name| {
+                            name.to_lowercase() == "password"
+                        })
+                    })
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("{ty} entries have no password")
+                    })?;
+                return Ok(Some(f.value.clone().unwrap_or_default()));
+            }
+        }
+        // every type of entry can have notes, so they're handled the same
+        // way for all of them
+        if matches!(field, "note" | "notes") {
+            return Ok(self.notes.clone());
+        }
         match &self.data {
-            DecryptedData::Login {
-                username,
-                totp,
-                uris,
-                ..
-            } => match field {
-                "notes" => {
-                    if let Some(notes) = &self.notes {
-                        val_display_or_store(clipboard, notes);
-                    }
-                }
+            DecryptedData::Login { username, uris, .. } => match field {
                 "username" | "user" => {
                     if let Some(username) = &username {
-                        val_display_or_store(clipboard, username);
+                        return Ok(Some(username.clone()));
                     }
                 }
                 "totp" | "code" => {
-                    if let Some(totp) = totp {
-                        match generate_totp(totp) {
-                            Ok(code) => {
-                                val_display_or_store(clipboard, &code);
-                            }
-                            Err(e) => {
-                                eprintln!("{e}");
-                            }
+                    if let Some(totp) = self.totp() {
+                        return generate_totp(
+                            totp,
+                            &TotpOverrides::default(),
+                            totp_windows,
+                        )
+                        .map(Some);
+                    }
+                }
+                "totp_issuer" | "totp_account" => {
+                    if let Some(totp) = self.totp() {
+                        let params = parse_totp_secret(totp)?;
+                        let value = if field == "totp_issuer" {
+                            params.issuer
+                        } else {
+                            params.account
+                        };
+                        if let Some(value) = value {
+                            return Ok(Some(value));
                         }
                     }
                 }
+                "totp_secret" | "totp-secret" => {
+                    if let Some(totp) = self.totp() {
+                        let params = parse_totp_secret(totp)?;
+                        return Ok(Some(
+                            params.to_uri(&self.name, username.as_deref()),
+                        ));
+                    }
+                }
                 "uris" | "urls" | "sites" => {
                     if let Some(uris) = uris {
                         let uri_strs: Vec<_> = uris
                             .iter()
                             .map(|uri| uri.uri.to_string())
                             .collect();
-                        val_display_or_store(clipboard, &uri_strs.join("\n"));
-                    }
-                }
-                "password" => {
-                    self.display_short(desc, clipboard);
-                }
-                _ => {
-                    for f in &self.fields {
-                        if let Some(name) = &f.name {
-                            if name.to_lowercase().as_str().contains(field) {
-                                val_display_or_store(
-                                    clipboard,
-                                    f.value.as_deref().unwrap_or(""),
-                                );
-                                break;
-                            }
-                        }
+                        return Ok(Some(uri_strs.join("\n")));
                     }
                 }
+                "password" => return self.short_value(desc).map(Some),
+                _ => return Ok(self.custom_field_value(field)),
             },
             DecryptedData::Card {
                 cardholder_name,
@@ -143,62 +228,42 @@ impl DecryptedCipher {
                 code,
                 ..
             } => match field {
-                "number" | "card" => {
-                    self.display_short(desc, clipboard);
-                }
+                "number" | "card" => return self.short_value(desc).map(Some),
                 "exp" => {
                     if let (Some(month), Some(year)) = (exp_month, exp_year) {
-                        val_display_or_store(
-                            clipboard,
-                            &format!("{month}/{year}"),
-                        );
+                        return Ok(Some(format!("{month}/{year}")));
                     }
                 }
                 "exp_month" | "month" => {
                     if let Some(exp_month) = exp_month {
-                        val_display_or_store(clipboard, exp_month);
+                        return Ok(Some(exp_month.clone()));
                     }
                 }
                 "exp_year" | "year" => {
                     if let Some(exp_year) = exp_year {
-                        val_display_or_store(clipboard, exp_year);
+                        return Ok(Some(exp_year.clone()));
                     }
                 }
                 "cvv" => {
                     if let Some(code) = code {
-                        val_display_or_store(clipboard, code);
+                        return Ok(Some(code.clone()));
                     }
                 }
                 "name" | "cardholder" => {
                     if let Some(cardholder_name) = cardholder_name {
-                        val_display_or_store(clipboard, cardholder_name);
+                        return Ok(Some(cardholder_name.clone()));
                     }
                 }
                 "brand" | "type" => {
                     if let Some(brand) = brand {
-                        val_display_or_store(clipboard, brand);
-                    }
-                }
-                "notes" => {
-                    if let Some(notes) = &self.notes {
-                        val_display_or_store(clipboard, notes);
-                    }
-                }
-                _ => {
-                    for f in &self.fields {
-                        if let Some(name) = &f.name {
-                            if name.to_lowercase().as_str().contains(field) {
-                                val_display_or_store(
-                                    clipboard,
-                                    f.value.as_deref().unwrap_or(""),
-                                );
-                                break;
-                            }
-                        }
+                        return Ok(Some(brand.clone()));
                     }
                 }
+                _ => return Ok(self.custom_field_value(field)),
             },
             DecryptedData::Identity {
+                first_name,
+                last_name,
                 address1,
                 address2,
                 address3,
@@ -214,12 +279,23 @@ impl DecryptedCipher {
                 username,
                 ..
             } => match field {
-                "name" => {
-                    self.display_short(desc, clipboard);
+                "name" | "fullname" => {
+                    return self.short_value(desc).map(Some)
+                }
+                "firstlast" => {
+                    let names: Vec<_> = [first_name, last_name]
+                        .iter()
+                        .copied()
+                        .flatten()
+                        .cloned()
+                        .collect();
+                    if !names.is_empty() {
+                        return Ok(Some(names.join(" ")));
+                    }
                 }
                 "email" => {
                     if let Some(email) = email {
-                        val_display_or_store(clipboard, email);
+                        return Ok(Some(email.clone()));
                     }
                 }
                 "address" => {
@@ -234,96 +310,433 @@ impl DecryptedCipher {
                         strs.push(address3.clone());
                     }
                     if !strs.is_empty() {
-                        val_display_or_store(clipboard, &strs.join("\n"));
+                        return Ok(Some(strs.join("\n")));
                     }
                 }
                 "city" => {
                     if let Some(city) = city {
-                        val_display_or_store(clipboard, city);
+                        return Ok(Some(city.clone()));
                     }
                 }
                 "state" => {
                     if let Some(state) = state {
-                        val_display_or_store(clipboard, state);
+                        return Ok(Some(state.clone()));
                     }
                 }
                 "postcode" | "zipcode" | "zip" => {
                     if let Some(postal_code) = postal_code {
-                        val_display_or_store(clipboard, postal_code);
+                        return Ok(Some(postal_code.clone()));
                     }
                 }
                 "country" => {
                     if let Some(country) = country {
-                        val_display_or_store(clipboard, country);
+                        return Ok(Some(country.clone()));
                     }
                 }
                 "phone" => {
                     if let Some(phone) = phone {
-                        val_display_or_store(clipboard, phone);
+                        return Ok(Some(phone.clone()));
                     }
                 }
                 "ssn" => {
                     if let Some(ssn) = ssn {
-                        val_display_or_store(clipboard, ssn);
+                        return Ok(Some(ssn.clone()));
                     }
                 }
                 "license" => {
                     if let Some(license_number) = license_number {
-                        val_display_or_store(clipboard, license_number);
+                        return Ok(Some(license_number.clone()));
                     }
                 }
                 "passport" => {
                     if let Some(passport_number) = passport_number {
-                        val_display_or_store(clipboard, passport_number);
+                        return Ok(Some(passport_number.clone()));
                     }
                 }
                 "username" => {
                     if let Some(username) = username {
-                        val_display_or_store(clipboard, username);
+                        return Ok(Some(username.clone()));
                     }
                 }
-                "notes" => {
-                    if let Some(notes) = &self.notes {
-                        val_display_or_store(clipboard, notes);
-                    }
+                _ => return Ok(self.custom_field_value(field)),
+            },
+            DecryptedData::SecureNote {} => {
+                return Ok(self.custom_field_value(field));
+            }
+            DecryptedData::SshKey {
+                public_key,
+                fingerprint,
+                ..
+            } => match field {
+                "private_key" | "private" | "key" => {
+                    return self.short_value(desc).map(Some)
                 }
-                _ => {
-                    for f in &self.fields {
-                        if let Some(name) = &f.name {
-                            if name.to_lowercase().as_str().contains(field) {
-                                val_display_or_store(
-                                    clipboard,
-                                    f.value.as_deref().unwrap_or(""),
-                                );
-                                break;
-                            }
-                        }
+                "public_key" | "public" => {
+                    if let Some(public_key) = public_key {
+                        return Ok(Some(public_key.clone()));
                     }
                 }
-            },
-            DecryptedData::SecureNote {} => match field {
-                "note" | "notes" => {
-                    self.display_short(desc, clipboard);
-                }
-                _ => {
-                    for f in &self.fields {
-                        if let Some(name) = &f.name {
-                            if name.to_lowercase().as_str().contains(field) {
-                                val_display_or_store(
-                                    clipboard,
-                                    f.value.as_deref().unwrap_or(""),
-                                );
-                                break;
-                            }
-                        }
+                "fingerprint" => {
+                    if let Some(fingerprint) = fingerprint {
+                        return Ok(Some(fingerprint.clone()));
                     }
                 }
+                _ => return Ok(self.custom_field_value(field)),
             },
         }
+
+        Ok(None)
+    }
+
+    // when several fields are requested at once, an empty value is reported
+    // in place rather than hiding all of the others
+    fn optional_field_value(
+        &self,
+        desc: &str,
+        field: &str,
+        totp_windows: &std::ops::RangeInclusive<u64>,
+    ) -> anyhow::Result<Option<String>> {
+        match self.field_value(desc, field, totp_windows) {
+            Err(e) if e.is::<MissingValue>() => Ok(None),
+            res => res,
+        }
+    }
+
+    fn fields_text(
+        &self,
+        desc: &str,
+        fields: &[String],
+        labels: bool,
+        totp_windows: &std::ops::RangeInclusive<u64>,
+    ) -> anyhow::Result<String> {
+        // missing fields still get a (blank) line, so that the values stay
+        // in the requested positions when parsing the unlabeled output
+        let mut lines = vec![];
+        for field in fields {
+            let value = self
+                .optional_field_value(desc, field, totp_windows)?
+                .unwrap_or_default();
+            if labels {
+                lines.push(format!("{field}: {value}"));
+            } else {
+                lines.push(value);
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn display_fields_json(
+        &self,
+        desc: &str,
+        fields: &[String],
+        totp_windows: &std::ops::RangeInclusive<u64>,
+    ) -> anyhow::Result<()> {
+        let mut map = serde_json::Map::new();
+        for field in fields {
+            map.insert(
+                field.clone(),
+                self.optional_field_value(desc, field, totp_windows)?
+                    .map_or(
+                        serde_json::Value::Null,
+                        serde_json::Value::String,
+                    ),
+            );
+        }
+        serde_json::to_writer_pretty(std::io::stdout(), &map)
+            .context(format!("failed to write entry '{desc}' to stdout"))?;
+        println!();
+
+        Ok(())
+    }
+
+    fn custom_field_value(&self, field: &str) -> Option<String> {
+        self.fields
+            .iter()
+            .find(|f| {
+                f.name.as_ref().is_some_and(|name| {
+                    name.to_lowercase().as_str().contains(field)
+                })
+            })
+            .map(|f| f.value.clone().unwrap_or_default())
+    }
+
+    // unlike custom_field_value, the name has to match exactly (ignoring
+    // case), and it's an error for it to be ambiguous
+    fn exact_custom_field_value(
+        &self,
+        desc: &str,
+        field: &str,
+    ) -> anyhow::Result<String> {
+        let field = field.to_lowercase();
+        let matches: Vec<_> = self
+            .fields
+            .iter()
+            .filter(|f| {
+                f.name
+                    .as_ref()
+                    .is_some_and(|name| name.to_lowercase() == field)
+            })
+            .collect();
+        match matches.as_slice() {
+            [] => Err(anyhow::anyhow!(
+                "entry '{desc}' has no custom field named '{field}'"
+            )),
+            [f] => Ok(f.value.clone().unwrap_or_default()),
+            _ => Err(anyhow::anyhow!(
+                "entry '{desc}' has {} custom fields named '{field}'",
+                matches.len()
+            )),
+        }
+    }
+
+    // the names of the custom fields, for `rbw fields`. values are left out
+    // so that hidden fields aren't displayed.
+    fn field_list_lines(&self, porcelain: bool) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter_map(|field| {
+                let name = field.name.as_deref()?;
+                let ty = field.ty.map(|ty| ty.to_string());
+                Some(if porcelain {
+                    porcelain_line(&[name, ty.as_deref().unwrap_or("")])
+                } else if let Some(ty) = ty {
+                    format!("{name} ({ty})")
+                } else {
+                    name.to_string()
+                })
+            })
+            .collect()
+    }
+
+    fn header(&self) -> String {
+        format!("=== {} ({}) ===", self.path(), self.id)
+    }
+
+    fn path(&self) -> String {
+        let folder = self
+            .folder
+            .as_deref()
+            .map_or_else(String::new, |folder| format!("{folder}/"));
+        let user = match &self.data {
+            DecryptedData::Login {
+                username: Some(username),
+                ..
+            } => format!("{username}@"),
+            _ => String::new(),
+        };
+        format!("{folder}{user}{}", self.name)
     }
 
-    fn display_long(&self, desc: &str, clipboard: bool) {
+    // the --porcelain output of `rbw get`. this is a stable interface, so
+    // keys may be added but existing keys must not change.
+    fn porcelain_lines(&self) -> Vec<String> {
+        let mut pairs = vec![
+            ("id", Some(self.id.as_str())),
+            ("name", Some(self.name.as_str())),
+            ("folder", self.folder.as_deref()),
+        ];
         match &self.data {
+            DecryptedData::Login {
+                username,
+                password,
+                totp,
+                uris,
+            } => {
+                pairs.extend([
+                    ("type", Some(self.data.type_name())),
+                    ("username", username.as_deref()),
+                    ("password", password.as_deref()),
+                    ("totp", totp.as_deref()),
+                ]);
+                pairs.extend(
+                    uris.iter()
+                        .flatten()
+                        .map(|uri| ("uri", Some(uri.uri.as_str()))),
+                );
+            }
+            DecryptedData::Card {
+                cardholder_name,
+                number,
+                brand,
+                exp_month,
+                exp_year,
+                code,
+            } => {
+                pairs.extend([
+                    ("type", Some(self.data.type_name())),
+                    ("cardholder_name", cardholder_name.as_deref()),
+                    ("number", number.as_deref()),
+                    ("brand", brand.as_deref()),
+                    ("exp_month", exp_month.as_deref()),
+                    ("exp_year", exp_year.as_deref()),
+                    ("code", code.as_deref()),
+                ]);
+            }
+            DecryptedData::Identity {
+                title,
+                first_name,
+                middle_name,
+                last_name,
+                address1,
+                address2,
+                address3,
+                city,
+                state,
+                postal_code,
+                country,
+                phone,
+                email,
+                ssn,
+                license_number,
+                passport_number,
+                username,
+            } => {
+                pairs.extend([
+                    ("type", Some(self.data.type_name())),
+                    ("title", title.as_deref()),
+                    ("first_name", first_name.as_deref()),
+                    ("middle_name", middle_name.as_deref()),
+                    ("last_name", last_name.as_deref()),
+                    ("address1", address1.as_deref()),
+                    ("address2", address2.as_deref()),
+                    ("address3", address3.as_deref()),
+                    ("city", city.as_deref()),
+                    ("state", state.as_deref()),
+                    ("postal_code", postal_code.as_deref()),
+                    ("country", country.as_deref()),
+                    ("phone", phone.as_deref()),
+                    ("email", email.as_deref()),
+                    ("ssn", ssn.as_deref()),
+                    ("license_number", license_number.as_deref()),
+                    ("passport_number", passport_number.as_deref()),
+                    ("username", username.as_deref()),
+                ]);
+            }
+            DecryptedData::SecureNote => {
+                pairs.push(("type", Some(self.data.type_name())));
+            }
+            DecryptedData::SshKey {
+                private_key,
+                public_key,
+                fingerprint,
+            } => {
+                pairs.extend([
+                    ("type", Some(self.data.type_name())),
+                    ("private_key", private_key.as_deref()),
+                    ("public_key", public_key.as_deref()),
+                    ("fingerprint", fingerprint.as_deref()),
+                ]);
+            }
+        }
+        pairs.push(("notes", self.notes.as_deref()));
+
+        let mut lines: Vec<String> = pairs
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value.map(|value| porcelain_line(&[key, value]))
+            })
+            .collect();
+        lines.extend(self.fields.iter().map(|field| {
+            porcelain_line(&[
+                "field",
+                field.name.as_deref().unwrap_or(""),
+                field.value.as_deref().unwrap_or(""),
+            ])
+        }));
+        lines
+    }
+
+    fn list_match(&self, folder: Option<&str>, ty: Option<&str>) -> bool {
+        if let Some(folder) = folder {
+            if self.folder.as_deref() != Some(folder) {
+                return false;
+            }
+        }
+        if let Some(ty) = ty {
+            if self.data.type_name() != ty {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn search_match(
+        &self,
+        term: &str,
+        folder: Option<&str>,
+        area: Option<SearchField>,
+    ) -> bool {
+        if let Some(folder) = folder {
+            if self.folder.as_deref() != Some(folder) {
+                return false;
+            }
+        }
+
+        let term = fold_case_and_accents(term);
+        self.search_fields(area)
+            .any(|field| fold_case_and_accents(field).contains(&term))
+    }
+
+    // higher is better: an exact name match, then a name prefix, then a
+    // name substring, and then a match in any of the other fields
+    fn search_score(&self, term: &str) -> u8 {
+        let term = fold_case_and_accents(term);
+        let name = fold_case_and_accents(&self.name);
+        if name == term {
+            3
+        } else if name.starts_with(&term) {
+            2
+        } else {
+            u8::from(name.contains(&term))
+        }
+    }
+
+    // with no area given, every area is searched
+    fn search_fields(
+        &self,
+        area: Option<SearchField>,
+    ) -> impl Iterator<Item = &str> {
+        let want = move |field| area.is_none() || area == Some(field);
+        let uris = match &self.data {
+            DecryptedData::Login {
+                uris: Some(uris), ..
+            } if want(SearchField::Uri) => uris.as_slice(),
+            _ => &[],
+        };
+        Some(self.name.as_str())
+            .filter(|_| want(SearchField::Name))
+            .into_iter()
+            .chain(self.username().filter(|_| want(SearchField::Username)))
+            .chain(self.notes.as_deref().filter(|_| want(SearchField::Notes)))
+            .chain(uris.iter().map(|uri| uri.uri.as_str()))
+            .chain(
+                self.fields
+                    .iter()
+                    .filter(move |_| want(SearchField::Field))
+                    .flat_map(|field| {
+                        field
+                            .name
+                            .as_deref()
+                            .into_iter()
+                            .chain(field.value.as_deref())
+                    }),
+            )
+    }
+
+    // printed after display_long, so it's separated from it by a blank line
+    fn display_history(&self) {
+        if self.history.is_empty() {
+            return;
+        }
+        println!();
+        println!("{}:", style_label("History"));
+        for history in &self.history {
+            println!("{}: {}", history.last_used_date, history.password);
+        }
+    }
+
+    fn display_long(&self, desc: &str, clipboard: Clipboard) {
+        let mut displayed = match &self.data {
             DecryptedData::Login {
                 username,
                 totp,
@@ -331,10 +744,16 @@ impl DecryptedCipher {
                 ..
             } => {
                 let mut displayed = self.display_short(desc, clipboard);
-                displayed |=
-                    display_field("Username", username.as_deref(), clipboard);
-                displayed |=
-                    display_field("TOTP Secret", totp.as_deref(), clipboard);
+                displayed |= display_field(
+                    "Username",
+                    self.field_or_failed("username", username.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "TOTP Secret",
+                    self.field_or_failed("totp", totp.as_deref()),
+                    clipboard,
+                );
 
                 if let Some(uris) = uris {
                     for uri in uris {
@@ -357,13 +776,7 @@ impl DecryptedCipher {
                         clipboard,
                     );
                 }
-
-                if let Some(notes) = &self.notes {
-                    if displayed {
-                        println!();
-                    }
-                    println!("{notes}");
-                }
+                displayed
             }
             DecryptedData::Card {
                 cardholder_name,
@@ -378,24 +791,31 @@ impl DecryptedCipher {
                 if let (Some(exp_month), Some(exp_year)) =
                     (exp_month, exp_year)
                 {
-                    println!("Expiration: {exp_month}/{exp_year}");
+                    println!(
+                        "{}: {exp_month}/{exp_year}",
+                        style_label("Expiration")
+                    );
                     displayed = true;
                 }
-                displayed |= display_field("CVV", code.as_deref(), clipboard);
+                displayed |= display_field(
+                    "CVV",
+                    self.field_or_failed("code", code.as_deref()),
+                    clipboard,
+                );
                 displayed |= display_field(
                     "Name",
-                    cardholder_name.as_deref(),
+                    self.field_or_failed(
+                        "cardholder_name",
+                        cardholder_name.as_deref(),
+                    ),
                     clipboard,
                 );
-                displayed |=
-                    display_field("Brand", brand.as_deref(), clipboard);
-
-                if let Some(notes) = &self.notes {
-                    if displayed {
-                        println!();
-                    }
-                    println!("{notes}");
-                }
+                displayed |= display_field(
+                    "Brand",
+                    self.field_or_failed("brand", brand.as_deref()),
+                    clipboard,
+                );
+                displayed
             }
             DecryptedData::Identity {
                 address1,
@@ -415,51 +835,169 @@ impl DecryptedCipher {
             } => {
                 let mut displayed = self.display_short(desc, clipboard);
 
-                displayed |=
-                    display_field("Address", address1.as_deref(), clipboard);
-                displayed |=
-                    display_field("Address", address2.as_deref(), clipboard);
-                displayed |=
-                    display_field("Address", address3.as_deref(), clipboard);
-                displayed |=
-                    display_field("City", city.as_deref(), clipboard);
-                displayed |=
-                    display_field("State", state.as_deref(), clipboard);
+                displayed |= display_field(
+                    "Address",
+                    self.field_or_failed("address1", address1.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Address",
+                    self.field_or_failed("address2", address2.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Address",
+                    self.field_or_failed("address3", address3.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "City",
+                    self.field_or_failed("city", city.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "State",
+                    self.field_or_failed("state", state.as_deref()),
+                    clipboard,
+                );
                 displayed |= display_field(
                     "Postcode",
-                    postal_code.as_deref(),
+                    self.field_or_failed(
+                        "postal_code",
+                        postal_code.as_deref(),
+                    ),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Country",
+                    self.field_or_failed("country", country.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Phone",
+                    self.field_or_failed("phone", phone.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Email",
+                    self.field_or_failed("email", email.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "SSN",
+                    self.field_or_failed("ssn", ssn.as_deref()),
                     clipboard,
                 );
-                displayed |=
-                    display_field("Country", country.as_deref(), clipboard);
-                displayed |=
-                    display_field("Phone", phone.as_deref(), clipboard);
-                displayed |=
-                    display_field("Email", email.as_deref(), clipboard);
-                displayed |= display_field("SSN", ssn.as_deref(), clipboard);
                 displayed |= display_field(
                     "License",
-                    license_number.as_deref(),
+                    self.field_or_failed(
+                        "license_number",
+                        license_number.as_deref(),
+                    ),
                     clipboard,
                 );
                 displayed |= display_field(
                     "Passport",
-                    passport_number.as_deref(),
+                    self.field_or_failed(
+                        "passport_number",
+                        passport_number.as_deref(),
+                    ),
                     clipboard,
                 );
-                displayed |=
-                    display_field("Username", username.as_deref(), clipboard);
-
-                if let Some(notes) = &self.notes {
-                    if displayed {
-                        println!();
-                    }
-                    println!("{notes}");
-                }
+                displayed |= display_field(
+                    "Username",
+                    self.field_or_failed("username", username.as_deref()),
+                    clipboard,
+                );
+                displayed
             }
             DecryptedData::SecureNote {} => {
-                self.display_short(desc, clipboard);
+                self.display_short(desc, clipboard)
+            }
+            DecryptedData::SshKey {
+                public_key,
+                fingerprint,
+                ..
+            } => {
+                let mut displayed = self.display_short(desc, clipboard);
+                displayed |= display_field(
+                    "Public key",
+                    self.field_or_failed("public_key", public_key.as_deref()),
+                    clipboard,
+                );
+                displayed |= display_field(
+                    "Fingerprint",
+                    self.field_or_failed(
+                        "fingerprint",
+                        fingerprint.as_deref(),
+                    ),
+                    clipboard,
+                );
+                displayed
+            }
+        };
+        displayed |= display_field(
+            "Modified",
+            self.revision_date.as_deref(),
+            clipboard,
+        );
+
+        // the notes are what display_short already displayed for a secure
+        // note
+        if matches!(self.data, DecryptedData::SecureNote) {
+            return;
+        }
+        if let Some(notes) = &self.notes {
+            if displayed {
+                println!();
             }
+            println!("{notes}");
+        }
+    }
+
+    // the dedicated totp field always takes precedence, but some importers
+    // store the secret in a custom field instead, so fall back to a custom
+    // field named "totp", and then to any custom field containing an
+    // otpauth:// url
+    fn totp(&self) -> Option<&str> {
+        if let DecryptedData::Login {
+            totp: Some(totp), ..
+        } = &self.data
+        {
+            return Some(totp);
+        }
+
+        let named_totp = self.fields.iter().filter(|field| {
+            field
+                .name
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case("totp"))
+        });
+        let otpauth_url = self.fields.iter().filter(|field| {
+            field
+                .value
+                .as_deref()
+                .is_some_and(|value| value.starts_with("otpauth://"))
+        });
+        named_totp
+            .chain(otpauth_url)
+            .filter_map(|field| field.value.as_deref())
+            .find(|value| parse_totp_secret(value).is_ok())
+    }
+
+    fn decrypt_failed(&self, field: &str) -> bool {
+        self.failed_fields.iter().any(|failed| failed == field)
+    }
+
+    fn field_or_failed<'a>(
+        &self,
+        field: &str,
+        value: Option<&'a str>,
+    ) -> Option<&'a str> {
+        if value.is_none() && self.decrypt_failed(field) {
+            Some(DECRYPT_FAILED)
+        } else {
+            value
         }
     }
 
@@ -475,14 +1013,106 @@ impl DecryptedCipher {
         }
     }
 
-    fn display_json(&self, desc: &str) -> anyhow::Result<()> {
-        serde_json::to_writer_pretty(std::io::stdout(), &self)
-            .context(format!("failed to write entry '{desc}' to stdout"))?;
+    fn username(&self) -> Option<&str> {
+        match &self.data {
+            DecryptedData::Login { username, .. } => username.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn display_json(
+        &self,
+        desc: &str,
+        only: &[String],
+    ) -> anyhow::Result<()> {
+        if only.is_empty() {
+            serde_json::to_writer_pretty(std::io::stdout(), &self)
+        } else {
+            let mut value = serde_json::to_value(self)
+                .context(format!("failed to serialize entry '{desc}'"))?;
+            if let serde_json::Value::Object(map) = &mut value {
+                map.retain(|key, _| only.contains(key));
+            }
+            serde_json::to_writer_pretty(std::io::stdout(), &value)
+        }
+        .context(format!("failed to write entry '{desc}' to stdout"))?;
         println!();
 
         Ok(())
     }
 
+    fn matches_url(
+        &self,
+        url: &url::Url,
+        username: Option<&str>,
+        uri_match: Option<rbw::api::UriMatchType>,
+        equivalent_domains: &[Vec<String>],
+    ) -> bool {
+        let DecryptedData::Login {
+            username: entry_username,
+            uris: Some(uris),
+            ..
+        } = &self.data
+        else {
+            return false;
+        };
+        if let Some(given_username) = username {
+            if entry_username.as_deref() != Some(given_username) {
+                return false;
+            }
+        }
+        uris.iter()
+            .any(|uri| uri.matches_url_as(url, uri_match, equivalent_domains))
+    }
+
+    // whether the entry would have matched the url if its uri matching
+    // hadn't been disabled, so that not finding it can be explained
+    fn never_matches_url(
+        &self,
+        url: &url::Url,
+        equivalent_domains: &[Vec<String>],
+    ) -> bool {
+        let DecryptedData::Login {
+            uris: Some(uris), ..
+        } = &self.data
+        else {
+            return false;
+        };
+        uris.iter()
+            .filter(|uri| {
+                uri.match_type == Some(rbw::api::UriMatchType::Never)
+            })
+            .any(|uri| {
+                DecryptedUri {
+                    uri: uri.uri.clone(),
+                    match_type: Some(rbw::api::UriMatchType::Domain),
+                }
+                .matches_url(url, equivalent_domains)
+            })
+    }
+
+    // for entries which mention a url in their notes or custom fields rather
+    // than storing it as a uri (which only login entries can have)
+    fn text_matches_url(
+        &self,
+        url: &url::Url,
+        equivalent_domains: &[Vec<String>],
+    ) -> bool {
+        self.notes
+            .iter()
+            .map(String::as_str)
+            .chain(self.fields.iter().filter_map(|f| f.value.as_deref()))
+            .flat_map(str::split_whitespace)
+            .filter(|word| word.contains("://"))
+            .any(|word| {
+                DecryptedUri {
+                    uri: word.to_string(),
+                    match_type: None,
+                }
+                .matches_url(url, equivalent_domains)
+            })
+    }
+
     fn exact_match(
         &self,
         name: &str,
@@ -534,8 +1164,18 @@ impl DecryptedCipher {
         username: Option<&str>,
         folder: Option<&str>,
         try_match_folder: bool,
+        fold: bool,
     ) -> bool {
-        if !self.name.contains(name) {
+        let contains = |haystack: &str, needle: &str| {
+            if fold {
+                fold_case_and_accents(haystack)
+                    .contains(&fold_case_and_accents(needle))
+            } else {
+                haystack.contains(needle)
+            }
+        };
+
+        if !contains(&self.name, name) {
             return false;
         }
 
@@ -545,7 +1185,7 @@ impl DecryptedCipher {
                     username: Some(found_username),
                     ..
                 } => {
-                    if !found_username.contains(given_username) {
+                    if !contains(found_username, given_username) {
                         return false;
                     }
                 }
@@ -559,7 +1199,7 @@ impl DecryptedCipher {
         if try_match_folder {
             if let Some(given_folder) = folder {
                 if let Some(folder) = &self.folder {
-                    if !folder.contains(given_folder) {
+                    if !contains(folder, given_folder) {
                         return false;
                     }
                 } else {
@@ -574,21 +1214,67 @@ impl DecryptedCipher {
     }
 }
 
-fn val_display_or_store(clipboard: bool, password: &str) -> bool {
-    if clipboard {
-        match clipboard_store(password) {
-            Ok(()) => true,
-            Err(e) => {
-                eprintln!("{e}");
-                false
-            }
+// clear_after is the number of seconds after which the agent should clear
+// the clipboard again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clipboard {
+    No,
+    Copy { clear_after: Option<u64> },
+    CopyAndPrint { clear_after: Option<u64> },
+}
+
+fn val_display_or_store(clipboard: Clipboard, password: &str) -> bool {
+    styled_display_or_store(clipboard, password, password)
+}
+
+// the clipboard always gets the plain value, since escape codes would only
+// get in the way when pasting
+fn styled_display_or_store(
+    clipboard: Clipboard,
+    plain: &str,
+    styled: &str,
+) -> bool {
+    if let Clipboard::Copy { clear_after }
+    | Clipboard::CopyAndPrint { clear_after } = clipboard
+    {
+        if let Err(e) = clipboard_store(plain, clear_after) {
+            eprintln!("{e}");
+            return false;
         }
+    }
+    if !matches!(clipboard, Clipboard::Copy { .. }) {
+        println!("{styled}");
+    }
+    true
+}
+
+// see https://no-color.org/
+fn color_enabled(
+    no_color: Option<&std::ffi::OsStr>,
+    is_terminal: bool,
+) -> bool {
+    is_terminal && no_color.unwrap_or_default().is_empty()
+}
+
+fn style(text: &str, code: &str) -> String {
+    if color_enabled(
+        std::env::var_os("NO_COLOR").as_deref(),
+        io::stdout().is_terminal(),
+    ) {
+        format!("\x1b[{code}m{text}\x1b[0m")
     } else {
-        println!("{password}");
-        true
+        text.to_string()
     }
 }
 
+fn style_label(label: &str) -> String {
+    style(label, "36")
+}
+
+fn style_name(name: &str) -> String {
+    style(name, "1")
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
@@ -627,15 +1313,70 @@ enum DecryptedData {
         username: Option<String>,
     },
     SecureNote,
+    SshKey {
+        private_key: Option<String>,
+        public_key: Option<String>,
+        fingerprint: Option<String>,
+    },
 }
 
-#[derive(Debug, Clone, Serialize)]
+// the names accepted by `rbw list --type`, as returned by
+// DecryptedData::type_name
+const ENTRY_TYPE_NAMES: &[&str] =
+    &["login", "card", "identity", "note", "ssh_key"];
+
+impl DecryptedData {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Login { .. } => "login",
+            Self::Card { .. } => "card",
+            Self::Identity { .. } => "identity",
+            Self::SecureNote => "note",
+            Self::SshKey { .. } => "ssh_key",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
 struct DecryptedField {
+    ty: Option<rbw::api::FieldType>,
     name: Option<String>,
     value: Option<String>,
 }
 
+impl Serialize for DecryptedField {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+
+        let mut state = serializer.serialize_struct("DecryptedField", 2)?;
+        state.serialize_field("name", &self.name)?;
+        // boolean fields are stored as strings, but are more useful as
+        // actual booleans in json output
+        match (self.ty, self.value.as_deref()) {
+            (Some(rbw::api::FieldType::Boolean), Some("true")) => {
+                state.serialize_field("value", &true)?;
+            }
+            (Some(rbw::api::FieldType::Boolean), Some("false")) => {
+                state.serialize_field("value", &false)?;
+            }
+            _ => state.serialize_field("value", &self.value)?,
+        }
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+struct DecryptedAttachment {
+    id: String,
+    file_name: String,
+    size: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[cfg_attr(test, derive(Eq, PartialEq))]
 struct DecryptedHistoryEntry {
@@ -650,11 +1391,159 @@ struct DecryptedUri {
     match_type: Option<rbw::api::UriMatchType>,
 }
 
+impl DecryptedUri {
+    fn matches_url(
+        &self,
+        url: &url::Url,
+        equivalent_domains: &[Vec<String>],
+    ) -> bool {
+        self.matches_url_as(url, None, equivalent_domains)
+    }
+
+    // the stored match type can be overridden, except for uris which were
+    // explicitly excluded from matching
+    fn matches_url_as(
+        &self,
+        url: &url::Url,
+        uri_match: Option<rbw::api::UriMatchType>,
+        equivalent_domains: &[Vec<String>],
+    ) -> bool {
+        let match_type = match uri_match {
+            Some(match_type)
+                if self.match_type != Some(rbw::api::UriMatchType::Never) =>
+            {
+                match_type
+            }
+            _ => self.match_type.unwrap_or(rbw::api::UriMatchType::Domain),
+        };
+        if match_type == rbw::api::UriMatchType::RegularExpression {
+            return regex::Regex::new(&self.uri)
+                .is_ok_and(|re| re.is_match(url.as_str()));
+        }
+
+        // bitwarden allows saving uris without a scheme
+        let Some(stored) = url::Url::parse(&self.uri)
+            .ok()
+            .filter(url::Url::has_host)
+            .or_else(|| {
+                url::Url::parse(&format!("https://{}", self.uri)).ok()
+            })
+        else {
+            return false;
+        };
+
+        match match_type {
+            rbw::api::UriMatchType::Domain => {
+                match (stored.host_str(), url.host_str()) {
+                    (Some(stored_host), Some(host)) => {
+                        domain_matches(stored_host, host, equivalent_domains)
+                    }
+                    _ => false,
+                }
+            }
+            rbw::api::UriMatchType::Host => {
+                stored.host_str().is_some()
+                    && stored.host_str() == url.host_str()
+                    && stored.port_or_known_default()
+                        == url.port_or_known_default()
+            }
+            rbw::api::UriMatchType::StartsWith => {
+                url_for_comparison(url, &stored)
+                    .as_str()
+                    .starts_with(stored.as_str())
+            }
+            rbw::api::UriMatchType::Exact => {
+                url_for_comparison(url, &stored)
+                    .as_str()
+                    .trim_end_matches('/')
+                    == stored.as_str().trim_end_matches('/')
+            }
+            rbw::api::UriMatchType::RegularExpression
+            | rbw::api::UriMatchType::Never => false,
+        }
+    }
+}
+
+fn domain_matches(
+    stored_host: &str,
+    host: &str,
+    equivalent_domains: &[Vec<String>],
+) -> bool {
+    let stored_domain = registrable_domain(stored_host);
+    let domain = registrable_domain(host);
+    stored_domain == domain
+        || equivalent_domains.iter().any(|group| {
+            let in_group = |domain: &str| {
+                group.iter().any(|equivalent| {
+                    registrable_domain(&equivalent.to_lowercase()) == domain
+                })
+            };
+            in_group(&stored_domain) && in_group(&domain)
+        })
+}
+
+// like the official clients, hosts are compared by the domain that was
+// registered under a public suffix, so www.example.co.uk and
+// accounts.example.co.uk are the same site but foo.github.io and
+// bar.github.io aren't. ip addresses and hosts without a registrable domain
+// (like localhost) are compared as they are.
+fn registrable_domain(host: &str) -> String {
+    if host.starts_with('[') || host.parse::<std::net::IpAddr>().is_ok() {
+        return host.to_string();
+    }
+    psl::domain_str(host).unwrap_or(host).to_string()
+}
+
+// login pages are frequently visited with extra query parameters (like
+// redirect targets) or fragments, so those are ignored unless the stored uri
+// specifies them itself
+fn url_for_comparison(url: &url::Url, stored: &url::Url) -> url::Url {
+    let mut url = url.clone();
+    if stored.query().is_none() {
+        url.set_query(None);
+    }
+    if stored.fragment().is_none() {
+        url.set_fragment(None);
+    }
+    url
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug, Eq, PartialEq))]
 enum ListField {
     Name,
     Id,
     User,
     Folder,
+    Uri,
+    Totp,
+    LastUsed,
+}
+
+impl ListField {
+    // the fields displayed by `--fields all`, in order
+    const ALL: &'static [Self] = &[
+        Self::Id,
+        Self::Name,
+        Self::User,
+        Self::Folder,
+        Self::Uri,
+        Self::Totp,
+        Self::LastUsed,
+    ];
+    const DEFAULT: &'static [Self] = &[Self::Name];
+
+    fn parse_list(fields: &[String]) -> anyhow::Result<Vec<Self>> {
+        let mut parsed = vec![];
+        for field in fields {
+            match field.as_str() {
+                "all" => parsed.extend_from_slice(Self::ALL),
+                "default" => parsed.extend_from_slice(Self::DEFAULT),
+                _ => parsed.push(field.try_into()?),
+            }
+        }
+        Ok(parsed)
+    }
 }
 
 impl std::convert::TryFrom<&String> for ListField {
@@ -666,17 +1555,83 @@ impl std::convert::TryFrom<&String> for ListField {
             "id" => Self::Id,
             "user" => Self::User,
             "folder" => Self::Folder,
+            "uri" => Self::Uri,
+            "totp" => Self::Totp,
+            "last_used" => Self::LastUsed,
             _ => return Err(anyhow::anyhow!("unknown field {}", s)),
         })
     }
 }
 
+// top level keys of the serialized DecryptedCipher
+const RAW_KEYS: &[&str] = &[
+    "id",
+    "folder",
+    "name",
+    "data",
+    "fields",
+    "notes",
+    "history",
+    "creation_date",
+    "revision_date",
+    "attachments",
+    "failed_fields",
+];
+
+fn validate_raw_keys(keys: &[String]) -> anyhow::Result<()> {
+    for key in keys {
+        if !RAW_KEYS.contains(&key.as_str()) {
+            return Err(anyhow::anyhow!(
+                "unknown key {}, valid keys are: {}",
+                key,
+                RAW_KEYS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
 const HELP: &str = r#"
 # The first line of this file will be the password, and the remainder of the
 # file (after any blank lines after the password) will be stored as a note.
 # Lines with leading # will be ignored.
 "#;
 
+const FIELDS_HELP: &str = r#"
+# Fill in the values after the colons; fields left empty will not be set.
+# Everything after "notes:" (including the following lines) will be stored as
+# a note. Lines with leading # will be ignored.
+"#;
+
+const CARD_FIELDS: &[&str] = &[
+    "cardholder_name",
+    "number",
+    "brand",
+    "exp_month",
+    "exp_year",
+    "code",
+];
+
+const IDENTITY_FIELDS: &[&str] = &[
+    "title",
+    "first_name",
+    "middle_name",
+    "last_name",
+    "address1",
+    "address2",
+    "address3",
+    "city",
+    "state",
+    "postal_code",
+    "country",
+    "phone",
+    "email",
+    "ssn",
+    "license_number",
+    "passport_number",
+    "username",
+];
+
 pub fn config_show() -> anyhow::Result<()> {
     let config = rbw::config::Config::load()?;
     serde_json::to_writer_pretty(std::io::stdout(), &config)
@@ -716,8 +1671,101 @@ pub fn config_set(key: &str, value: &str) -> anyhow::Result<()> {
                 .context("failed to parse value for sync_interval")?;
             config.sync_interval = interval;
         }
-        "pinentry" => config.pinentry = value.to_string(),
-        _ => return Err(anyhow::anyhow!("invalid config key: {}", key)),
+        "pinentry" => {
+            // the program may be installed later, so this isn't an error
+            if !is_executable(value) {
+                log::warn!(
+                    "pinentry program '{value}' was not found{}",
+                    if value.contains('/') { "" } else { " in $PATH" }
+                );
+            }
+            config.pinentry = value.to_string();
+        }
+        "pinentry_title" => config.pinentry_title = Some(value.to_string()),
+        "pinentry_prompt" => {
+            config.pinentry_prompt = Some(value.to_string());
+        }
+        "sync_after_write" => {
+            config.sync_after_write = value
+                .parse()
+                .context("failed to parse value for sync_after_write")?;
+        }
+        "network_timeout" => {
+            let timeout = value
+                .parse()
+                .context("failed to parse value for network_timeout")?;
+            if timeout == 0 {
+                return Err(anyhow::anyhow!(
+                    "network_timeout must be greater than 0"
+                ));
+            }
+            config.network_timeout = Some(timeout);
+        }
+        "max_retries" => {
+            config.max_retries = value
+                .parse()
+                .context("failed to parse value for max_retries")?;
+        }
+        "generate_policy" => {
+            // catch mistakes now rather than the next time a password is
+            // generated
+            rbw::pwgen::Policy::load(std::path::Path::new(value))?;
+            config.generate_policy = Some(value.into());
+        }
+        "track_usage" => {
+            config.track_usage = value
+                .parse()
+                .context("failed to parse value for track_usage")?;
+        }
+        "decrypt_cache" => {
+            config.decrypt_cache = value
+                .parse()
+                .context("failed to parse value for decrypt_cache")?;
+        }
+        "prewarm" => {
+            config.prewarm =
+                value.parse().context("failed to parse value for prewarm")?;
+        }
+        "equivalent_domains" => {
+            config.equivalent_domains = parse_equivalent_domains(value)?;
+        }
+        "on_multiple_matches" => {
+            config.on_multiple_matches = match value {
+                "error" => rbw::config::MultipleMatches::Error,
+                "first" => rbw::config::MultipleMatches::First,
+                "newest" => rbw::config::MultipleMatches::Newest,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "invalid value for on_multiple_matches: {value}, \
+                        expected one of error, first, newest"
+                    ))
+                }
+            };
+        }
+        "clipboard_selection" => {
+            config.clipboard_selection = match value {
+                "clipboard" => rbw::config::ClipboardSelection::Clipboard,
+                "primary" => rbw::config::ClipboardSelection::Primary,
+                "both" => rbw::config::ClipboardSelection::Both,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "invalid value for clipboard_selection: {value}, \
+                        expected one of clipboard, primary, both"
+                    ))
+                }
+            };
+        }
+        _ => {
+            let alias = key
+                .strip_prefix("field_aliases.")
+                .filter(|alias| !alias.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid config key: {}", key)
+                })?;
+            config
+                .field_aliases
+                .insert(alias.to_string(), value.to_string());
+        }
     }
     config.save()?;
 
@@ -731,6 +1779,30 @@ pub fn config_set(key: &str, value: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+// groups are separated by semicolons, and the domains within a group by
+// commas, like "google.com,youtube.com;amazon.com,amazon.co.uk"
+fn parse_equivalent_domains(value: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    value
+        .split(';')
+        .filter(|group| !group.trim().is_empty())
+        .map(|group| {
+            let domains: Vec<String> = group
+                .split(',')
+                .map(|domain| domain.trim().to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect();
+            if domains.len() < 2 {
+                return Err(anyhow::anyhow!(
+                    "equivalent domain group '{}' must contain at least two \
+                    domains",
+                    group.trim()
+                ));
+            }
+            Ok(domains)
+        })
+        .collect()
+}
+
 pub fn config_unset(key: &str) -> anyhow::Result<()> {
     let mut config = rbw::config::Config::load()
         .unwrap_or_else(|_| rbw::config::Config::new());
@@ -744,7 +1816,38 @@ pub fn config_unset(key: &str) -> anyhow::Result<()> {
             config.lock_timeout = rbw::config::default_lock_timeout();
         }
         "pinentry" => config.pinentry = rbw::config::default_pinentry(),
-        _ => return Err(anyhow::anyhow!("invalid config key: {}", key)),
+        "pinentry_title" => config.pinentry_title = None,
+        "pinentry_prompt" => config.pinentry_prompt = None,
+        "sync_after_write" => {
+            config.sync_after_write = rbw::config::default_sync_after_write();
+        }
+        "network_timeout" => config.network_timeout = None,
+        "max_retries" => {
+            config.max_retries = rbw::config::default_max_retries();
+        }
+        "generate_policy" => config.generate_policy = None,
+        "track_usage" => config.track_usage = false,
+        "decrypt_cache" => config.decrypt_cache = false,
+        "prewarm" => config.prewarm = false,
+        "equivalent_domains" => config.equivalent_domains.clear(),
+        "on_multiple_matches" => {
+            config.on_multiple_matches =
+                rbw::config::MultipleMatches::default();
+        }
+        "clipboard_selection" => {
+            config.clipboard_selection =
+                rbw::config::ClipboardSelection::default();
+        }
+        "field_aliases" => config.field_aliases.clear(),
+        _ => {
+            let alias = key
+                .strip_prefix("field_aliases.")
+                .filter(|alias| !alias.is_empty())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid config key: {}", key)
+                })?;
+            config.field_aliases.remove(alias);
+        }
     }
     config.save()?;
 
@@ -758,9 +1861,47 @@ pub fn config_unset(key: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn clipboard_store(val: &str) -> anyhow::Result<()> {
+// --porcelain output consists of lines of tab separated values. backslashes,
+// tabs, and newlines in values are escaped as \\, \t, and \n, so that every
+// value is on a single line and can't contain the separator.
+fn porcelain_line(values: &[&str]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+fn is_executable(program: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let is_executable_file = |path: &std::path::Path| {
+        std::fs::metadata(path).is_ok_and(|metadata| {
+            metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+        })
+    };
+
+    if program.contains('/') {
+        is_executable_file(std::path::Path::new(program))
+    } else {
+        std::env::var_os("PATH").is_some_and(|path| {
+            std::env::split_paths(&path)
+                .any(|dir| is_executable_file(&dir.join(program)))
+        })
+    }
+}
+
+fn clipboard_store(
+    val: &str,
+    clear_after: Option<u64>,
+) -> anyhow::Result<()> {
     ensure_agent()?;
-    crate::actions::clipboard_store(val)?;
+    crate::actions::clipboard_store(val, clear_after)?;
 
     Ok(())
 }
@@ -794,6 +1935,42 @@ pub fn unlocked() -> anyhow::Result<()> {
     Ok(())
 }
 
+pub fn whoami() -> anyhow::Result<()> {
+    let config = rbw::config::Config::load()?;
+    let email = config.email.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("failed to find email address in config")
+    })?;
+    let server = config.server_name();
+    let db = rbw::db::Db::load(&server, email).unwrap_or_default();
+
+    // organization names are only known after a sync, so fall back to ids
+    let mut orgs: Vec<_> = db
+        .protected_org_keys
+        .keys()
+        .map(|id| db.org_names.get(id).unwrap_or(id).clone())
+        .collect();
+    orgs.sort_unstable();
+    let logged_in = db.access_token.is_some() && db.refresh_token.is_some();
+    // don't start the agent just to find out that it isn't unlocked
+    let unlocked = logged_in && crate::actions::unlocked().is_ok();
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    println!("email: {email}");
+    println!("server: {server}");
+    println!(
+        "organizations: {}",
+        if orgs.is_empty() {
+            "none".to_string()
+        } else {
+            orgs.join(", ")
+        }
+    );
+    println!("logged in: {}", yes_no(logged_in));
+    println!("unlocked: {}", yes_no(unlocked));
+
+    Ok(())
+}
+
 pub fn sync() -> anyhow::Result<()> {
     ensure_agent()?;
     crate::actions::login()?;
@@ -802,22 +1979,54 @@ pub fn sync() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn list(fields: &[String]) -> anyhow::Result<()> {
-    let fields: Vec<ListField> = fields
-        .iter()
-        .map(std::convert::TryFrom::try_from)
-        .collect::<anyhow::Result<_>>()?;
+pub fn list(
+    fields: &[String],
+    folder: Option<&str>,
+    ty: Option<&str>,
+    org: Option<&str>,
+    sort: ListSort,
+    reverse: bool,
+    porcelain: bool,
+) -> anyhow::Result<()> {
+    let fields = ListField::parse_list(fields)?;
+    if let Some(ty) = ty {
+        if !ENTRY_TYPE_NAMES.contains(&ty) {
+            return Err(anyhow::anyhow!(
+                "unknown entry type {ty}, expected one of {}",
+                ENTRY_TYPE_NAMES.join(", ")
+            ));
+        }
+    }
 
     unlock()?;
 
     let db = load_db()?;
-    let mut ciphers: Vec<DecryptedCipher> = db
-        .entries
-        .iter()
-        .cloned()
-        .map(|entry| decrypt_cipher(&entry))
+    let org_id = org.map(|org| resolve_org(&db, org)).transpose()?;
+    let entries: Vec<_> = supported_entries(&db)
+        .filter(|entry| in_org(entry, org_id.as_deref()))
+        .collect();
+    // every decryption is a separate round trip to the agent (over its own
+    // connection), so for large vaults it's much faster to have several of
+    // them in flight at once
+    let cache = load_decrypt_cache(entries.iter().copied())?;
+    let mut ciphers: Vec<DecryptedCipher> = entries
+        .into_par_iter()
+        .map(|entry| decrypt_cipher(entry, cache.as_ref()))
+        .filter(|cipher| {
+            cipher
+                .as_ref()
+                .map_or(true, |cipher| cipher.list_match(folder, ty))
+        })
         .collect::<anyhow::Result<_>>()?;
-    ciphers.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    store_decrypt_cache(cache);
+    let usage = if matches!(sort, ListSort::LastUsed)
+        || fields.iter().any(|f| matches!(f, ListField::LastUsed))
+    {
+        load_usage()?
+    } else {
+        std::collections::HashMap::new()
+    };
+    sort_list_results(&mut ciphers, sort, reverse, &usage);
 
     for cipher in ciphers {
         let values: Vec<String> = fields
@@ -826,24 +2035,46 @@ pub fn list(fields: &[String]) -> anyhow::Result<()> {
                 ListField::Name => cipher.name.clone(),
                 ListField::Id => cipher.id.clone(),
                 ListField::User => match &cipher.data {
-                    DecryptedData::Login { username, .. } => {
-                        username.as_ref().map_or_else(
+                    DecryptedData::Login { username, .. } => cipher
+                        .field_or_failed("username", username.as_deref())
+                        .map_or_else(
                             String::new,
                             std::string::ToString::to_string,
-                        )
-                    }
+                        ),
                     _ => String::new(),
                 },
-                ListField::Folder => cipher.folder.as_ref().map_or_else(
-                    String::new,
-                    std::string::ToString::to_string,
-                ),
+                ListField::Folder => cipher
+                    .field_or_failed("folder", cipher.folder.as_deref())
+                    .map_or_else(
+                        String::new,
+                        std::string::ToString::to_string,
+                    ),
+                ListField::Uri => match &cipher.data {
+                    DecryptedData::Login {
+                        uris: Some(uris), ..
+                    } => uris
+                        .first()
+                        .map(|uri| uri.uri.clone())
+                        .unwrap_or_default(),
+                    _ => String::new(),
+                },
+                ListField::Totp => cipher.totp().is_some().to_string(),
+                ListField::LastUsed => {
+                    usage.get(&cipher.id).cloned().unwrap_or_default()
+                }
             })
             .collect();
+        let line = if porcelain {
+            porcelain_line(
+                &values.iter().map(String::as_str).collect::<Vec<_>>(),
+            )
+        } else {
+            values.join("\t")
+        };
 
         // write to stdout but don't panic when pipe get's closed
         // this happens when piping stdout in a shell
-        match writeln!(&mut io::stdout(), "{}", values.join("\t")) {
+        match writeln!(&mut io::stdout(), "{line}") {
             Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
             res => res,
         }?;
@@ -852,378 +2083,784 @@ pub fn list(fields: &[String]) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn get(
-    name: &str,
-    user: Option<&str>,
+pub fn search(
+    term: &str,
     folder: Option<&str>,
-    field: Option<&str>,
-    full: bool,
+    first: bool,
+    sort: SearchSort,
+    area: Option<SearchField>,
+    org: Option<&str>,
     raw: bool,
-    clipboard: bool,
+    porcelain: bool,
 ) -> anyhow::Result<()> {
     unlock()?;
 
     let db = load_db()?;
+    let org_id = org.map(|org| resolve_org(&db, org)).transpose()?;
+    let entries: Vec<_> = supported_entries(&db)
+        .filter(|entry| in_org(entry, org_id.as_deref()))
+        .collect();
+    let cache = load_decrypt_cache(entries.iter().copied())?;
+    let mut found: Vec<DecryptedCipher> = entries
+        .into_iter()
+        .map(|entry| decrypt_cipher(entry, cache.as_ref()))
+        .filter(|cipher| {
+            cipher.as_ref().map_or(true, |cipher| {
+                cipher.search_match(term, folder, area)
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    store_decrypt_cache(cache);
 
-    let desc = format!(
-        "{}{}",
-        user.map_or_else(String::new, |s| format!("{s}@")),
-        name
-    );
+    if first {
+        let best = best_search_match(found, term)
+            .ok_or(FindEntryError::NotFound)
+            .with_context(|| format!("couldn't find entry for '{term}'"))?;
+        found = vec![best];
+    }
+    sort_search_results(&mut found, sort, term);
 
-    let (_, decrypted) = find_entry(&db, name, user, folder)
-        .with_context(|| format!("couldn't find entry for '{desc}'"))?;
     if raw {
-        decrypted.display_json(&desc)?;
-    } else if full {
-        decrypted.display_long(&desc, clipboard);
-    } else if let Some(field) = field {
-        decrypted.display_field(&desc, field, clipboard);
-    } else {
-        decrypted.display_short(&desc, clipboard);
+        let results: Vec<_> = found.iter().map(SearchResult::new).collect();
+        serde_json::to_writer_pretty(std::io::stdout(), &results)
+            .context("failed to write search results to stdout")?;
+        println!();
+        return Ok(());
+    }
+
+    for cipher in found {
+        let line = if porcelain {
+            let username = cipher.username();
+            porcelain_line(&[
+                &cipher.id,
+                cipher.folder.as_deref().unwrap_or(""),
+                username.unwrap_or(""),
+                &cipher.name,
+            ])
+        } else {
+            cipher.path()
+        };
+
+        // write to stdout but don't panic when pipe get's closed
+        // this happens when piping stdout in a shell
+        match writeln!(&mut io::stdout(), "{line}") {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            res => res,
+        }?;
     }
 
     Ok(())
 }
 
-pub fn code(
-    name: &str,
-    user: Option<&str>,
-    folder: Option<&str>,
-) -> anyhow::Result<()> {
+pub fn export(format: ExportFormat) -> anyhow::Result<()> {
     unlock()?;
 
     let db = load_db()?;
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let res = match format {
+        ExportFormat::Csv => export_csv(&db, &mut stdout),
+    }
+    .and_then(|skipped| {
+        stdout.flush()?;
+        Ok(skipped)
+    });
+    match res {
+        Ok(skipped) => {
+            if skipped > 0 {
+                eprintln!("skipped {skipped} non-login entries");
+            }
+            Ok(())
+        }
+        // don't fail when the output is piped into something like head
+        Err(e)
+            if e.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                e.kind() == std::io::ErrorKind::BrokenPipe
+            }) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
 
-    let desc = format!(
-        "{}{}",
-        user.map_or_else(String::new, |s| format!("{s}@")),
-        name
-    );
-
-    let (_, decrypted) = find_entry(&db, name, user, folder)
-        .with_context(|| format!("couldn't find entry for '{desc}'"))?;
-
-    if let DecryptedData::Login { totp, .. } = decrypted.data {
-        if let Some(totp) = totp {
-            println!("{}", generate_totp(&totp)?);
-        } else {
-            return Err(anyhow::anyhow!(
-                "entry does not contain a totp secret"
-            ));
+// rows are written as each entry is decrypted rather than collecting the
+// whole database first. returns the number of skipped non-login entries.
+fn export_csv(
+    db: &rbw::db::Db,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    write_csv_row(
+        out,
+        &["name", "folder", "username", "password", "totp", "uris"],
+    )?;
+
+    let entries: Vec<_> = supported_entries(db).collect();
+    let cache = load_decrypt_cache(entries.iter().copied())?;
+    let mut skipped = 0;
+    for entry in entries {
+        if !matches!(entry.data, rbw::db::EntryData::Login { .. }) {
+            skipped += 1;
+            continue;
         }
-    } else {
-        return Err(anyhow::anyhow!("not a login entry"));
+        let cipher = decrypt_cipher(entry, cache.as_ref())?;
+        let DecryptedData::Login {
+            username,
+            password,
+            totp,
+            uris,
+        } = &cipher.data
+        else {
+            unreachable!();
+        };
+        let uris = uris
+            .iter()
+            .flatten()
+            .map(|uri| uri.uri.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        write_csv_row(
+            out,
+            &[
+                &cipher.name,
+                cipher.folder.as_deref().unwrap_or(""),
+                username.as_deref().unwrap_or(""),
+                password.as_deref().unwrap_or(""),
+                totp.as_deref().unwrap_or(""),
+                &uris,
+            ],
+        )?;
     }
+    store_decrypt_cache(cache);
 
-    Ok(())
+    Ok(skipped)
 }
 
-pub fn add(
-    name: &str,
-    username: Option<&str>,
-    uris: &[(String, Option<rbw::api::UriMatchType>)],
-    folder: Option<&str>,
-) -> anyhow::Result<()> {
-    unlock()?;
-
-    let mut db = load_db()?;
-    // unwrap is safe here because the call to unlock above is guaranteed to
-    // populate these or error
-    let mut access_token = db.access_token.as_ref().unwrap().clone();
-    let refresh_token = db.refresh_token.as_ref().unwrap();
+fn write_csv_row(out: &mut impl Write, fields: &[&str]) -> io::Result<()> {
+    let fields: Vec<_> =
+        fields.iter().map(|field| csv_field(field)).collect();
+    writeln!(out, "{}", fields.join(","))
+}
 
-    let name = crate::actions::encrypt(name, None)?;
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
 
-    let username = username
-        .map(|username| crate::actions::encrypt(username, None))
-        .transpose()?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+}
 
-    let contents = rbw::edit::edit("", HELP)?;
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
 
-    let (password, notes) = parse_editor(&contents);
-    let password = password
-        .map(|password| crate::actions::encrypt(&password, None))
-        .transpose()?;
-    let notes = notes
-        .map(|notes| crate::actions::encrypt(&notes, None))
-        .transpose()?;
-    let uris: Vec<_> = uris
-        .iter()
-        .map(|uri| {
-            Ok(rbw::db::Uri {
-                uri: crate::actions::encrypt(&uri.0, None)?,
-                match_type: uri.1,
-            })
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "csv" => Self::Csv,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown export format {s}, expected csv"
+                ))
+            }
         })
-        .collect::<anyhow::Result<_>>()?;
+    }
+}
 
-    let mut folder_id = None;
-    if let Some(folder_name) = folder {
-        let (new_access_token, folders) =
-            rbw::actions::list_folders(&access_token, refresh_token)?;
-        if let Some(new_access_token) = new_access_token {
-            access_token = new_access_token.clone();
-            db.access_token = Some(new_access_token);
-            save_db(&db)?;
+// --field takes a comma separated list, but custom field names can contain
+// commas themselves, so those can be escaped as \, (and backslashes as \\)
+fn split_field_list(list: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = list.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some(',' | '\\')) => {
+                field.push(chars.next().unwrap());
+            }
+            ',' => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
         }
+    }
+    fields.push(field);
+    fields
+}
 
-        let folders: Vec<(String, String)> = folders
-            .iter()
-            .cloned()
-            .map(|(id, name)| Ok((id, crate::actions::decrypt(&name, None)?)))
-            .collect::<anyhow::Result<_>>()?;
+// aliases from the field_aliases config option are checked before the
+// built in field names, and are case insensitive like the field names are
+fn resolve_field_alias<'a>(
+    aliases: &'a std::collections::BTreeMap<String, String>,
+    field: &'a str,
+) -> &'a str {
+    aliases
+        .iter()
+        .find(|(alias, _)| alias.to_lowercase() == field.to_lowercase())
+        .map_or(field, |(_, name)| name.as_str())
+}
 
-        for (id, name) in folders {
-            if name == folder_name {
-                folder_id = Some(id);
-            }
-        }
-        if folder_id.is_none() {
-            let (new_access_token, id) = rbw::actions::create_folder(
-                &access_token,
-                refresh_token,
-                &crate::actions::encrypt(folder_name, None)?,
-            )?;
-            if let Some(new_access_token) = new_access_token {
-                access_token = new_access_token.clone();
-                db.access_token = Some(new_access_token);
-                save_db(&db)?;
-            }
-            folder_id = Some(id);
+#[derive(Debug, Serialize)]
+struct SearchResult<'a> {
+    id: &'a str,
+    name: &'a str,
+    folder: Option<&'a str>,
+    username: Option<&'a str>,
+}
+
+impl<'a> SearchResult<'a> {
+    fn new(cipher: &'a DecryptedCipher) -> Self {
+        Self {
+            id: &cipher.id,
+            name: &cipher.name,
+            folder: cipher.folder.as_deref(),
+            username: cipher.username(),
         }
     }
+}
 
-    if let (Some(access_token), ()) = rbw::actions::add(
-        &access_token,
-        refresh_token,
-        &name,
-        &rbw::db::EntryData::Login {
-            username,
-            password,
-            uris,
-            totp: None,
-        },
-        notes.as_deref(),
-        folder_id.as_deref(),
-    )? {
-        db.access_token = Some(access_token);
-        save_db(&db)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Name,
+    Username,
+    Notes,
+    Uri,
+    Field,
+}
+
+impl std::str::FromStr for SearchField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "name" => Self::Name,
+            "username" => Self::Username,
+            "notes" => Self::Notes,
+            "uri" => Self::Uri,
+            "field" => Self::Field,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown search field {s}, expected one of name, \
+                    username, notes, uri, field"
+                ))
+            }
+        })
     }
+}
 
-    crate::actions::sync()?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSort {
+    Folder,
+    Name,
+    Relevance,
+}
 
-    Ok(())
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListSort {
+    Name,
+    Folder,
+    User,
+    Id,
+    // most recently modified first
+    Modified,
+    // most recently used first, with entries which were never used last
+    LastUsed,
 }
 
-pub fn generate(
-    name: Option<&str>,
-    username: Option<&str>,
-    uris: &[(String, Option<rbw::api::UriMatchType>)],
-    folder: Option<&str>,
-    len: usize,
-    ty: rbw::pwgen::Type,
-) -> anyhow::Result<()> {
-    let password = rbw::pwgen::pwgen(ty, len);
-    println!("{password}");
+impl std::str::FromStr for ListSort {
+    type Err = anyhow::Error;
 
-    if let Some(name) = name {
-        unlock()?;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "name" => Self::Name,
+            "folder" => Self::Folder,
+            "user" => Self::User,
+            "id" => Self::Id,
+            "modified" => Self::Modified,
+            "last_used" => Self::LastUsed,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown sort order {s}, expected one of name, folder, \
+                    user, id, modified, last_used"
+                ))
+            }
+        })
+    }
+}
 
-        let mut db = load_db()?;
-        // unwrap is safe here because the call to unlock above is guaranteed
-        // to populate these or error
-        let mut access_token = db.access_token.as_ref().unwrap().clone();
-        let refresh_token = db.refresh_token.as_ref().unwrap();
+// like sort_search_results, every order falls back to the other fields so
+// that the output is deterministic
+fn sort_list_results(
+    ciphers: &mut [DecryptedCipher],
+    sort: ListSort,
+    reverse: bool,
+    usage: &std::collections::HashMap<String, String>,
+) {
+    let by_name = |a: &DecryptedCipher, b: &DecryptedCipher| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| a.folder.cmp(&b.folder))
+            .then_with(|| a.id.cmp(&b.id))
+    };
+    let cmp = |a: &DecryptedCipher, b: &DecryptedCipher| match sort {
+        ListSort::Name => by_name(a, b),
+        ListSort::Folder => {
+            a.folder.cmp(&b.folder).then_with(|| by_name(a, b))
+        }
+        ListSort::User => {
+            a.username().cmp(&b.username()).then_with(|| by_name(a, b))
+        }
+        ListSort::Id => a.id.cmp(&b.id),
+        ListSort::Modified => {
+            let modified = |cipher: &DecryptedCipher| {
+                cipher
+                    .revision_date
+                    .as_deref()
+                    .and_then(parse_revision_date)
+            };
+            modified(b).cmp(&modified(a)).then_with(|| by_name(a, b))
+        }
+        ListSort::LastUsed => {
+            let last_used = |cipher: &DecryptedCipher| {
+                usage
+                    .get(&cipher.id)
+                    .and_then(|date| parse_revision_date(date))
+            };
+            last_used(b).cmp(&last_used(a)).then_with(|| by_name(a, b))
+        }
+    };
+    if reverse {
+        ciphers.sort_by(|a, b| cmp(b, a));
+    } else {
+        ciphers.sort_by(cmp);
+    }
+}
 
-        let name = crate::actions::encrypt(name, None)?;
-        let username = username
-            .map(|username| crate::actions::encrypt(username, None))
-            .transpose()?;
-        let password = crate::actions::encrypt(&password, None)?;
-        let uris: Vec<_> = uris
-            .iter()
-            .map(|uri| {
-                Ok(rbw::db::Uri {
-                    uri: crate::actions::encrypt(&uri.0, None)?,
-                    match_type: uri.1,
-                })
-            })
-            .collect::<anyhow::Result<_>>()?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    Login,
+    Card,
+    Identity,
+}
+
+impl std::str::FromStr for EntryType {
+    type Err = anyhow::Error;
 
-        let mut folder_id = None;
-        if let Some(folder_name) = folder {
-            let (new_access_token, folders) =
-                rbw::actions::list_folders(&access_token, refresh_token)?;
-            if let Some(new_access_token) = new_access_token {
-                access_token = new_access_token.clone();
-                db.access_token = Some(new_access_token);
-                save_db(&db)?;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "login" => Self::Login,
+            "card" => Self::Card,
+            "identity" => Self::Identity,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown entry type {s}, expected one of login, card, \
+                    identity"
+                ))
             }
+        })
+    }
+}
 
-            let folders: Vec<(String, String)> = folders
-                .iter()
-                .cloned()
-                .map(|(id, name)| {
-                    Ok((id, crate::actions::decrypt(&name, None)?))
-                })
-                .collect::<anyhow::Result<_>>()?;
+impl std::str::FromStr for SearchSort {
+    type Err = anyhow::Error;
 
-            for (id, name) in folders {
-                if name == folder_name {
-                    folder_id = Some(id);
-                }
-            }
-            if folder_id.is_none() {
-                let (new_access_token, id) = rbw::actions::create_folder(
-                    &access_token,
-                    refresh_token,
-                    &crate::actions::encrypt(folder_name, None)?,
-                )?;
-                if let Some(new_access_token) = new_access_token {
-                    access_token = new_access_token.clone();
-                    db.access_token = Some(new_access_token);
-                    save_db(&db)?;
-                }
-                folder_id = Some(id);
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "folder" => Self::Folder,
+            "name" => Self::Name,
+            "relevance" => Self::Relevance,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown sort order {s}, expected one of folder, name, \
+                    relevance"
+                ))
             }
-        }
-
-        if let (Some(access_token), ()) = rbw::actions::add(
-            &access_token,
-            refresh_token,
-            &name,
-            &rbw::db::EntryData::Login {
-                username,
-                password: Some(password),
-                uris,
-                totp: None,
-            },
-            None,
-            folder_id.as_deref(),
-        )? {
-            db.access_token = Some(access_token);
-            save_db(&db)?;
-        }
+        })
+    }
+}
 
-        crate::actions::sync()?;
+// every order falls back to comparing the folder, name, and id, so that the
+// output doesn't depend on the order of the entries in the database
+fn sort_search_results(
+    found: &mut [DecryptedCipher],
+    sort: SearchSort,
+    term: &str,
+) {
+    let by_folder = |a: &DecryptedCipher, b: &DecryptedCipher| {
+        a.folder
+            .cmp(&b.folder)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.id.cmp(&b.id))
+    };
+    match sort {
+        SearchSort::Folder => found.sort_by(by_folder),
+        SearchSort::Name => found.sort_by(|a, b| {
+            a.name.cmp(&b.name).then_with(|| by_folder(a, b))
+        }),
+        SearchSort::Relevance => found.sort_by(|a, b| {
+            b.search_score(term)
+                .cmp(&a.search_score(term))
+                .then_with(|| by_folder(a, b))
+        }),
     }
+}
 
-    Ok(())
+// ties are broken by name, so that the result doesn't depend on the order
+// of the entries in the database
+fn best_search_match(
+    found: Vec<DecryptedCipher>,
+    term: &str,
+) -> Option<DecryptedCipher> {
+    found.into_iter().max_by(|a, b| {
+        a.search_score(term)
+            .cmp(&b.search_score(term))
+            .then_with(|| b.name.cmp(&a.name))
+    })
 }
 
-pub fn edit(
+pub fn get(
     name: &str,
-    username: Option<&str>,
+    user: Option<&str>,
     folder: Option<&str>,
+    strict_folder: bool,
+    org: Option<&str>,
+    fields: &[String],
+    field_exact: Option<&str>,
+    labels: bool,
+    full: bool,
+    raw: bool,
+    clipboard: Clipboard,
+    header: Option<bool>,
+    only: &[String],
+    totp_windows: &std::ops::RangeInclusive<u64>,
+    search_notes: bool,
+    uri_match: Option<rbw::api::UriMatchType>,
+    print_history: bool,
+    qr: bool,
+    all: bool,
+    porcelain: bool,
 ) -> anyhow::Result<()> {
+    validate_raw_keys(only)?;
+    let totp_field = |field: &String| {
+        matches!(
+            field.to_lowercase().as_str(),
+            "totp" | "code" | "totp_secret" | "totp-secret"
+        )
+    };
+    if qr && (fields.len() > 1 || !fields.iter().all(totp_field)) {
+        return Err(anyhow::anyhow!(
+            "--qr can only be used with --field totp or --field totp_secret"
+        ));
+    }
     unlock()?;
 
     let mut db = load_db()?;
-    let access_token = db.access_token.as_ref().unwrap();
-    let refresh_token = db.refresh_token.as_ref().unwrap();
+
+    let field_aliases = rbw::config::Config::load()
+        .map(|config| config.field_aliases)
+        .unwrap_or_default();
+    let fields: Vec<String> = fields
+        .iter()
+        .flat_map(|field| split_field_list(field))
+        .map(|field| resolve_field_alias(&field_aliases, &field).to_string())
+        .collect();
+    let fields = fields.as_slice();
+    let attachment = match fields {
+        [field] => field.strip_prefix("attachment:"),
+        _ => None,
+    };
+    // attachments are written to stdout as is, since they are arbitrary
+    // binary data
+    if attachment.is_some() && (raw || !matches!(clipboard, Clipboard::No)) {
+        return Err(anyhow::anyhow!(
+            "--field attachment: can't be used with --raw or --clipboard"
+        ));
+    }
 
     let desc = format!(
         "{}{}",
-        username.map_or_else(String::new, |s| format!("{s}@")),
+        user.map_or_else(String::new, |s| format!("{s}@")),
         name
     );
 
-    let (entry, decrypted) = find_entry(&db, name, username, folder)
+    let org_id = org.map(|org| resolve_org(&db, org)).transpose()?;
+    if all {
+        let matches = find_entries(
+            &db,
+            name,
+            user,
+            folder,
+            org_id.as_deref(),
+            search_notes,
+            strict_folder,
+            uri_match,
+        )
         .with_context(|| format!("couldn't find entry for '{desc}'"))?;
-
-    let (data, notes, history) = match &decrypted.data {
-        DecryptedData::Login { password, .. } => {
-            let mut contents =
-                format!("{}\n", password.as_deref().unwrap_or(""));
-            if let Some(notes) = decrypted.notes {
-                contents.push_str(&format!("\n{notes}\n"));
+        if matches.is_empty() {
+            return Err(FindEntryError::NotFound).with_context(|| {
+                format!("couldn't find entry for '{desc}'")
+            });
+        }
+        if full {
+            for (i, (_, decrypted)) in matches.iter().enumerate() {
+                record_usage(&decrypted.id);
+                if i > 0 {
+                    println!();
+                }
+                // the header is what separates the entries here, so it
+                // defaults to on
+                if header.unwrap_or(true) {
+                    println!("{}", style_name(&decrypted.header()));
+                }
+                decrypted.display_long(&decrypted.display_name(), clipboard);
+                if print_history {
+                    decrypted.display_history();
+                }
             }
+            return Ok(());
+        }
+        for (_, decrypted) in matches {
+            // entries without a password are still listed, so that it's
+            // clear that they matched
+            let value = decrypted.short_value_or_failed();
+            record_usage(&decrypted.id);
+            println!("{}\t{value}", decrypted.display_name());
+        }
+        return Ok(());
+    }
+    let (entry, decrypted) = find_entries(
+        &db,
+        name,
+        user,
+        folder,
+        org_id.as_deref(),
+        search_notes,
+        strict_folder,
+        uri_match,
+    )
+    .and_then(single_entry)
+    .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    record_usage(&decrypted.id);
+    if let Some(file_name) = attachment {
+        let contents =
+            download_attachment(&mut db, &entry, &decrypted, file_name)?;
+        io::stdout()
+            .write_all(&contents)
+            .context("failed to write attachment to stdout")?;
+    } else if qr {
+        let uri = decrypted
+            .field_value(&desc, "totp_secret", totp_windows)?
+            .ok_or_else(|| {
+                anyhow::anyhow!("entry '{desc}' has no totp secret")
+            })?;
+        print!("{}", qr_code(&uri)?);
+    } else if let Some(field) = field_exact {
+        val_display_or_store(
+            clipboard,
+            &decrypted.exact_custom_field_value(&desc, field)?,
+        );
+    } else if raw && !fields.is_empty() {
+        decrypted.display_fields_json(&desc, fields, totp_windows)?;
+    } else if raw {
+        decrypted.display_json(&desc, only)?;
+    } else if porcelain && fields.is_empty() {
+        for line in decrypted.porcelain_lines() {
+            println!("{line}");
+        }
+    } else if full {
+        // headers are only needed to separate multiple entries, so they
+        // default to off when displaying a single entry
+        if header.unwrap_or(false) {
+            println!("{}", style_name(&decrypted.header()));
+        }
+        decrypted.display_long(&desc, clipboard);
+        // the history is never copied, so it's only shown when the entry
+        // itself is being displayed
+        if print_history && matches!(clipboard, Clipboard::No) {
+            decrypted.display_history();
+        }
+    } else if let [field] = fields {
+        decrypted.display_field(&desc, field, clipboard, totp_windows)?;
+    } else if !fields.is_empty() {
+        let text =
+            decrypted.fields_text(&desc, fields, labels, totp_windows)?;
+        val_display_or_store(clipboard, &text);
+    } else {
+        val_display_or_store(clipboard, &decrypted.short_value(&desc)?);
+    }
 
-            let contents = rbw::edit::edit(&contents, HELP)?;
+    Ok(())
+}
 
-            let (password, notes) = parse_editor(&contents);
-            let password = password
-                .map(|password| {
-                    crate::actions::encrypt(
-                        &password,
-                        entry.org_id.as_deref(),
-                    )
-                })
-                .transpose()?;
-            let notes = notes
-                .map(|notes| {
-                    crate::actions::encrypt(&notes, entry.org_id.as_deref())
-                })
-                .transpose()?;
-            let mut history = entry.history.clone();
-            let rbw::db::EntryData::Login {
-                username: entry_username,
-                password: entry_password,
-                uris: entry_uris,
-                totp: entry_totp,
-            } = &entry.data
-            else {
-                unreachable!();
-            };
+pub fn code(
+    name: Option<&str>,
+    user: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    secret: Option<&str>,
+    overrides: &TotpOverrides,
+    windows: &std::ops::RangeInclusive<u64>,
+    search_notes: bool,
+    uri_match: Option<rbw::api::UriMatchType>,
+    watch: bool,
+    remaining: bool,
+    all: bool,
+    raw: bool,
+) -> anyhow::Result<()> {
+    if watch && !io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!("--watch requires a terminal"));
+    }
 
-            if let Some(prev_password) = entry_password.clone() {
-                let new_history_entry = rbw::db::HistoryEntry {
-                    last_used_date: format!(
-                        "{}",
-                        humantime::format_rfc3339(
-                            std::time::SystemTime::now()
-                        )
-                    ),
-                    password: prev_password,
-                };
-                history.insert(0, new_history_entry);
-            }
+    if let Some(secret) = secret {
+        if watch {
+            return watch_totp(secret, overrides);
+        }
+        if raw {
+            println!("{}", totp_raw(secret, overrides, windows)?);
+            return Ok(());
+        }
+        println!("{}", generate_totp(secret, overrides, windows)?);
+        if remaining {
+            println!("{}", totp_remaining(secret, overrides)?);
+        }
+        return Ok(());
+    }
+    let name = name.ok_or_else(|| {
+        anyhow::anyhow!("either an entry name or --secret is required")
+    })?;
 
-            let data = rbw::db::EntryData::Login {
-                username: entry_username.clone(),
-                password,
-                uris: entry_uris.clone(),
-                totp: entry_totp.clone(),
+    unlock()?;
+
+    let db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        user.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    if all {
+        let matches = find_entries(
+            &db,
+            name,
+            user,
+            folder,
+            None,
+            search_notes,
+            strict_folder,
+            uri_match,
+        )
+        .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+        if matches.is_empty() {
+            return Err(FindEntryError::NotFound).with_context(|| {
+                format!("couldn't find entry for '{desc}'")
+            });
+        }
+        let mut found = false;
+        for (_, decrypted) in matches {
+            let Some(totp) = decrypted.totp() else {
+                continue;
             };
-            (data, notes, history)
+            found = true;
+            // one bad secret shouldn't hide the codes for every other entry
+            let codes = match generate_totp(totp, overrides, windows) {
+                Ok(codes) => codes,
+                Err(e) => {
+                    log::warn!(
+                        "failed to generate code for '{}': {e:#}",
+                        decrypted.display_name()
+                    );
+                    continue;
+                }
+            };
+            record_usage(&decrypted.id);
+            for code in codes.lines() {
+                println!("{}\t{code}", decrypted.display_name());
+            }
         }
-        _ => {
+        if !found {
             return Err(anyhow::anyhow!(
-                "modifications are only supported for login entries"
+                "no entries matching '{desc}' contain a totp secret"
             ));
         }
-    };
+        return Ok(());
+    }
 
-    if let (Some(access_token), ()) = rbw::actions::edit(
-        access_token,
-        refresh_token,
-        &entry.id,
-        entry.org_id.as_deref(),
-        &entry.name,
-        &data,
-        notes.as_deref(),
-        entry.folder_id.as_deref(),
-        &history,
-    )? {
-        db.access_token = Some(access_token);
-        save_db(&db)?;
+    let (_, decrypted) = find_entries(
+        &db,
+        name,
+        user,
+        folder,
+        None,
+        search_notes,
+        strict_folder,
+        uri_match,
+    )
+    .and_then(single_entry)
+    .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    record_usage(&decrypted.id);
+
+    if let Some(totp) = decrypted.totp() {
+        if watch {
+            return watch_totp(totp, overrides);
+        }
+        if raw {
+            println!("{}", totp_raw(totp, overrides, windows)?);
+            return Ok(());
+        }
+        println!("{}", generate_totp(totp, overrides, windows)?);
+        if remaining {
+            println!("{}", totp_remaining(totp, overrides)?);
+        }
+    } else if let DecryptedData::Login { .. } = decrypted.data {
+        return Err(anyhow::anyhow!("entry does not contain a totp secret"));
+    } else {
+        return Err(anyhow::anyhow!("not a login entry"));
     }
 
-    crate::actions::sync()?;
     Ok(())
 }
 
-pub fn remove(
+pub fn add(
     name: &str,
     username: Option<&str>,
+    uris: &[(String, Option<rbw::api::UriMatchType>)],
     folder: Option<&str>,
+    ty: EntryType,
+    totp: Option<&str>,
+    allow_empty: bool,
+    stdin: bool,
+    password_file: Option<&std::path::Path>,
+    note: Option<&str>,
+    no_sync: bool,
+    dry_run: bool,
 ) -> anyhow::Result<()> {
+    if ty != EntryType::Login
+        && (username.is_some()
+            || !uris.is_empty()
+            || totp.is_some()
+            || password_file.is_some()
+            || note.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "usernames, uris, totp secrets, passwords, and notes can only be \
+            given for login entries"
+        ));
+    }
+    let file_password = password_file.map(read_password_file).transpose()?;
+    // check these before opening the editor, so that a typo doesn't throw
+    // away whatever was entered there. the secret itself is stored as given,
+    // so that the parameters of otpauth urls are kept.
+    if let Some(totp) = totp {
+        parse_totp_secret(totp).context("invalid totp secret")?;
+    }
+    for (uri, match_type) in uris {
+        validate_uri(uri, *match_type)?;
+    }
+
     unlock()?;
 
     let mut db = load_db()?;
-    let access_token = db.access_token.as_ref().unwrap();
-    let refresh_token = db.refresh_token.as_ref().unwrap();
 
     let desc = format!(
         "{}{}",
@@ -1231,717 +2868,4898 @@ pub fn remove(
         name
     );
 
-    let (entry, _) = find_entry(&db, name, username, folder)
-        .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    let name = crate::actions::encrypt(name, None)?;
 
-    if let (Some(access_token), ()) =
-        rbw::actions::remove(access_token, refresh_token, &entry.id)?
-    {
+    let (data, notes) = match ty {
+        EntryType::Login => {
+            let (password, notes) =
+                if password_file.is_some() || note.is_some() {
+                    (file_password, note.map(str::to_string))
+                } else {
+                    parse_editor(&edit_contents("", HELP, stdin)?)
+                };
+            check_empty_password(
+                password.as_deref(),
+                password_file.is_none() && note.is_some(),
+                allow_empty,
+            )?;
+            let username = username
+                .map(|username| crate::actions::encrypt(username, None))
+                .transpose()?;
+            let password = password
+                .map(|password| crate::actions::encrypt(&password, None))
+                .transpose()?;
+            let totp = totp
+                .map(|totp| crate::actions::encrypt(totp, None))
+                .transpose()?;
+            let uris: Vec<_> = uris
+                .iter()
+                .map(|uri| {
+                    Ok(rbw::db::Uri {
+                        uri: crate::actions::encrypt(&uri.0, None)?,
+                        match_type: uri.1,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?;
+            let data = rbw::db::EntryData::Login {
+                username,
+                password,
+                uris,
+                totp,
+            };
+            (data, notes)
+        }
+        EntryType::Card => {
+            let (mut values, notes) = edit_fields(
+                &CARD_FIELDS.iter().map(|f| (*f, None)).collect::<Vec<_>>(),
+                None,
+                stdin,
+            )?;
+            validate_card(&values)?;
+            let mut field = |key| take_encrypted(&mut values, key, None);
+            let data = rbw::db::EntryData::Card {
+                cardholder_name: field("cardholder_name")?,
+                number: field("number")?,
+                brand: field("brand")?,
+                exp_month: field("exp_month")?,
+                exp_year: field("exp_year")?,
+                code: field("code")?,
+            };
+            (data, notes)
+        }
+        EntryType::Identity => {
+            let (mut values, notes) = edit_fields(
+                &IDENTITY_FIELDS
+                    .iter()
+                    .map(|f| (*f, None))
+                    .collect::<Vec<_>>(),
+                None,
+                stdin,
+            )?;
+            let mut field = |key| take_encrypted(&mut values, key, None);
+            let data = rbw::db::EntryData::Identity {
+                title: field("title")?,
+                first_name: field("first_name")?,
+                middle_name: field("middle_name")?,
+                last_name: field("last_name")?,
+                address1: field("address1")?,
+                address2: field("address2")?,
+                address3: field("address3")?,
+                city: field("city")?,
+                state: field("state")?,
+                postal_code: field("postal_code")?,
+                country: field("country")?,
+                phone: field("phone")?,
+                email: field("email")?,
+                ssn: field("ssn")?,
+                license_number: field("license_number")?,
+                passport_number: field("passport_number")?,
+                username: field("username")?,
+            };
+            (data, notes)
+        }
+    };
+    if dry_run {
+        println!(
+            "dry run: would add '{desc}'{}",
+            dry_run_folder_desc(&db, folder)?
+        );
+        return Ok(());
+    }
+    let notes = notes
+        .map(|notes| crate::actions::encrypt(&notes, None))
+        .transpose()?;
+
+    let folder_id = folder
+        .map(|folder_name| resolve_or_create_folder(&mut db, folder_name))
+        .transpose()?;
+
+    let (new_access_token, entry) = rbw::actions::add(
+        // unwrap is safe here because the call to unlock above is guaranteed
+        // to populate these or error
+        db.access_token.as_ref().unwrap(),
+        db.refresh_token.as_ref().unwrap(),
+        &name,
+        &data,
+        notes.as_deref(),
+        &[],
+        folder_id.as_deref(),
+    )?;
+    if let Some(access_token) = new_access_token {
         db.access_token = Some(access_token);
         save_db(&db)?;
     }
 
-    crate::actions::sync()?;
+    sync_or_update_db(&mut db, no_sync, |db| {
+        if let Some(entry) = entry {
+            db.upsert_entry(entry);
+        } else {
+            log::warn!("couldn't update the local database, run `rbw sync`");
+        }
+    })?;
 
     Ok(())
 }
 
-pub fn history(
-    name: &str,
+pub fn generate(
+    name: Option<&str>,
     username: Option<&str>,
+    uris: &[(String, Option<rbw::api::UriMatchType>)],
     folder: Option<&str>,
+    len: usize,
+    ty: rbw::pwgen::Type,
+    exclude: &str,
+    minimums: rbw::pwgen::Minimums,
+    copy: bool,
+    no_sync: bool,
+    dry_run: bool,
 ) -> anyhow::Result<()> {
-    unlock()?;
+    for (uri, match_type) in uris {
+        validate_uri(uri, *match_type)?;
+    }
 
-    let db = load_db()?;
+    // generating a password without saving it doesn't need any other
+    // configuration, so a missing config file isn't an error here
+    let policy = match rbw::config::Config::load() {
+        Ok(config) => config.generate_policy,
+        Err(rbw::error::Error::LoadConfig { source, .. })
+            if source.kind() == std::io::ErrorKind::NotFound =>
+        {
+            None
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let password = if let Some(file) = policy {
+        let policy = rbw::pwgen::Policy::load(&file)?;
+        rbw::pwgen::pwgen_with_policy(ty, len, exclude, minimums, &policy)?
+    } else {
+        rbw::pwgen::pwgen_with_minimums(ty, len, exclude, minimums)?
+    };
+    // if copying fails, the password would otherwise be lost (unless it's
+    // also being stored), so display it instead
+    if !copy
+        || !val_display_or_store(
+            Clipboard::Copy { clear_after: None },
+            &password,
+        )
+    {
+        println!("{password}");
+    }
 
-    let desc = format!(
-        "{}{}",
-        username.map_or_else(String::new, |s| format!("{s}@")),
-        name
-    );
+    if let Some(name) = name {
+        unlock()?;
 
-    let (_, decrypted) = find_entry(&db, name, username, folder)
-        .with_context(|| format!("couldn't find entry for '{desc}'"))?;
-    for history in decrypted.history {
-        println!("{}: {}", history.last_used_date, history.password);
+        let mut db = load_db()?;
+        if dry_run {
+            println!(
+                "dry run: would add '{}{name}'{}",
+                username.map_or_else(String::new, |s| format!("{s}@")),
+                dry_run_folder_desc(&db, folder)?
+            );
+            return Ok(());
+        }
+
+        // stdout only gets the password, so that it can still be piped
+        // somewhere, and the confirmation goes to stderr
+        let stored = format!(
+            "stored as '{name}'{}",
+            folder.map_or_else(String::new, |folder| format!(
+                " in folder '{folder}'"
+            ))
+        );
+        let name = crate::actions::encrypt(name, None)?;
+        let username = username
+            .map(|username| crate::actions::encrypt(username, None))
+            .transpose()?;
+        let password = crate::actions::encrypt(&password, None)?;
+        let uris: Vec<_> = uris
+            .iter()
+            .map(|uri| {
+                Ok(rbw::db::Uri {
+                    uri: crate::actions::encrypt(&uri.0, None)?,
+                    match_type: uri.1,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let folder_id = folder
+            .map(|folder_name| resolve_or_create_folder(&mut db, folder_name))
+            .transpose()?;
+
+        let (new_access_token, entry) = rbw::actions::add(
+            // unwrap is safe here because the call to unlock above is
+            // guaranteed to populate these or error
+            db.access_token.as_ref().unwrap(),
+            db.refresh_token.as_ref().unwrap(),
+            &name,
+            &rbw::db::EntryData::Login {
+                username,
+                password: Some(password),
+                uris,
+                totp: None,
+            },
+            None,
+            &[],
+            folder_id.as_deref(),
+        )?;
+        if let Some(access_token) = new_access_token {
+            db.access_token = Some(access_token);
+            save_db(&db)?;
+        }
+
+        if let Some(entry) = &entry {
+            eprintln!("{stored} with id {}", entry.id);
+        } else {
+            eprintln!("{stored}");
+        }
+        sync_or_update_db(&mut db, no_sync, |db| {
+            if let Some(entry) = entry {
+                db.upsert_entry(entry);
+            } else {
+                log::warn!(
+                    "couldn't update the local database, run `rbw sync`"
+                );
+            }
+        })?;
     }
 
     Ok(())
 }
 
-pub fn lock() -> anyhow::Result<()> {
-    ensure_agent()?;
-    crate::actions::lock()?;
+pub fn gen_username(
+    catchall: Option<&str>,
+    plus: Option<&str>,
+    into: Option<&str>,
+    strict_folder: bool,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let ty = if let Some(domain) = catchall {
+        rbw::pwgen::UsernameType::Catchall { domain }
+    } else if let Some(email) = plus {
+        let (local, domain) = email.rsplit_once('@').ok_or_else(|| {
+            anyhow::anyhow!("invalid email address '{email}'")
+        })?;
+        rbw::pwgen::UsernameType::Plus { local, domain }
+    } else {
+        rbw::pwgen::UsernameType::RandomWords
+    };
+    let username = rbw::pwgen::username(ty);
+    println!("{username}");
 
-    Ok(())
-}
+    if let Some(name) = into {
+        unlock()?;
 
-pub fn purge() -> anyhow::Result<()> {
-    stop_agent()?;
+        let mut db = load_db()?;
+        let access_token = db.access_token.as_ref().unwrap();
+        let refresh_token = db.refresh_token.as_ref().unwrap();
 
-    remove_db()?;
+        let (entry, decrypted) =
+            find_entry(&db, name, None, None, false, strict_folder)
+                .with_context(|| {
+                    format!("couldn't find entry for '{name}'")
+                })?;
 
-    Ok(())
-}
+        let rbw::db::EntryData::Login {
+            password,
+            totp,
+            uris,
+            ..
+        } = &entry.data
+        else {
+            return Err(anyhow::anyhow!(
+                "usernames can only be saved to login entries"
+            ));
+        };
+        let data = rbw::db::EntryData::Login {
+            username: Some(crate::actions::encrypt(
+                &username,
+                entry.org_id.as_deref(),
+            )?),
+            password: password.clone(),
+            totp: totp.clone(),
+            uris: uris.clone(),
+        };
+
+        if dry_run {
+            println!(
+                "dry run: would set the username of '{}' ({})",
+                decrypted.path(),
+                entry.id
+            );
+            return Ok(());
+        }
 
-pub fn stop_agent() -> anyhow::Result<()> {
-    crate::actions::quit()?;
+        if let (Some(access_token), ()) = rbw::actions::edit(
+            access_token,
+            refresh_token,
+            &entry.id,
+            entry.org_id.as_deref(),
+            &entry.name,
+            &data,
+            entry.notes.as_deref(),
+            entry.folder_id.as_deref(),
+            &entry.history,
+        )? {
+            db.access_token = Some(access_token);
+            save_db(&db)?;
+        }
+
+        sync_or_update_db(&mut db, no_sync, |db| {
+            db.upsert_entry(rbw::db::Entry { data, ..entry });
+        })?;
+    }
 
     Ok(())
 }
 
-fn ensure_agent() -> anyhow::Result<()> {
-    check_config()?;
-
-    ensure_agent_once()?;
-    let client_version = rbw::protocol::version();
-    let agent_version = version_or_quit()?;
-    if agent_version != client_version {
-        log::debug!(
-            "client protocol version is {} but agent protocol version is {}",
-            client_version,
-            agent_version
-        );
-        crate::actions::quit()?;
-        ensure_agent_once()?;
-        let agent_version = version_or_quit()?;
-        if agent_version != client_version {
-            crate::actions::quit()?;
-            return Err(anyhow::anyhow!(
-                "incompatible protocol versions: client ({}), agent ({})",
-                client_version,
-                agent_version
-            ));
-        }
+pub fn edit(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    new_username: Option<&str>,
+    new_totp: Option<&str>,
+    stdin: bool,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if let Some(totp) = new_totp.filter(|totp| !totp.is_empty()) {
+        parse_totp_secret(totp).context("invalid totp secret")?;
     }
-    Ok(())
-}
 
-fn ensure_agent_once() -> anyhow::Result<()> {
-    let agent_path = std::env::var("RBW_AGENT");
-    let agent_path = agent_path
-        .as_ref()
-        .map(std::string::String::as_str)
-        .unwrap_or("rbw-agent");
-    let status = std::process::Command::new(agent_path)
-        .status()
-        .context("failed to run rbw-agent")?;
-    if !status.success() {
-        if let Some(code) = status.code() {
-            if code != 23 {
-                return Err(anyhow::anyhow!(
-                    "failed to run rbw-agent: {}",
-                    status
-                ));
-            }
-        }
-    }
+    unlock()?;
 
-    Ok(())
-}
+    let mut db = load_db()?;
+    let access_token = db.access_token.as_ref().unwrap();
+    let refresh_token = db.refresh_token.as_ref().unwrap();
 
-fn check_config() -> anyhow::Result<()> {
-    rbw::config::Config::validate().map_err(|e| {
-        log::error!("{}", MISSING_CONFIG_HELP);
-        anyhow::Error::new(e)
-    })
-}
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
 
-fn version_or_quit() -> anyhow::Result<u32> {
-    crate::actions::version().map_err(|e| {
-        let _ = crate::actions::quit();
-        e
-    })
-}
+    let (entry, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
 
-fn find_entry(
-    db: &rbw::db::Db,
-    name: &str,
-    username: Option<&str>,
-    folder: Option<&str>,
-) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
-    if uuid::Uuid::parse_str(name).is_ok() {
-        for cipher in &db.entries {
-            if name == cipher.id {
-                return Ok((cipher.clone(), decrypt_cipher(cipher)?));
+    let path = decrypted.path();
+    // uris can't be changed here, so existing invalid ones are only pointed
+    // out rather than refusing to save the other changes
+    if let DecryptedData::Login {
+        uris: Some(uris), ..
+    } = &decrypted.data
+    {
+        for uri in uris {
+            if let Err(e) = validate_uri(&uri.uri, uri.match_type) {
+                log::warn!("'{path}' has a uri which will never match: {e}");
             }
         }
-        Err(anyhow::anyhow!("no entry found"))
-    } else {
-        let ciphers: Vec<(rbw::db::Entry, DecryptedCipher)> = db
-            .entries
-            .iter()
-            .cloned()
-            .map(|entry| {
-                decrypt_cipher(&entry).map(|decrypted| (entry, decrypted))
-            })
-            .collect::<anyhow::Result<_>>()?;
-        find_entry_raw(&ciphers, name, username, folder)
     }
-}
+    let (data, notes, history) = match &decrypted.data {
+        DecryptedData::Login { .. }
+            if new_username.is_some() || new_totp.is_some() =>
+        {
+            let rbw::db::EntryData::Login {
+                username,
+                password,
+                uris,
+                totp,
+            } = &entry.data
+            else {
+                unreachable!();
+            };
+            // an empty value clears the field, and a missing one keeps it
+            let replace = |new: Option<&str>, old: &Option<String>| {
+                new.map_or_else(
+                    || Ok(old.clone()),
+                    |new| {
+                        if new.is_empty() {
+                            return Ok(None);
+                        }
+                        crate::actions::encrypt(new, entry.org_id.as_deref())
+                            .map(Some)
+                    },
+                )
+            };
+            let data = rbw::db::EntryData::Login {
+                username: replace(new_username, username)?,
+                password: password.clone(),
+                uris: uris.clone(),
+                totp: replace(new_totp, totp)?,
+            };
+            (data, entry.notes.clone(), entry.history.clone())
+        }
+        _ if new_username.is_some() || new_totp.is_some() => {
+            return Err(anyhow::anyhow!(
+                "usernames and totp secrets can only be changed for login \
+                entries"
+            ));
+        }
+        DecryptedData::Login { password, .. } => {
+            let mut contents =
+                format!("{}\n", password.as_deref().unwrap_or(""));
+            if let Some(notes) = decrypted.notes {
+                contents.push_str(&format!("\n{notes}\n"));
+            }
 
-fn find_entry_raw(
-    entries: &[(rbw::db::Entry, DecryptedCipher)],
-    name: &str,
-    username: Option<&str>,
-    folder: Option<&str>,
-) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
-    let mut matches: Vec<(rbw::db::Entry, DecryptedCipher)> = entries
-        .iter()
-        .cloned()
-        .filter(|(_, decrypted_cipher)| {
-            decrypted_cipher.exact_match(name, username, folder, true)
-        })
-        .collect();
+            let contents = edit_contents(&contents, HELP, stdin)?;
 
-    if matches.len() == 1 {
-        return Ok(matches[0].clone());
-    }
+            let (password, notes) = parse_editor(&contents);
+            let password = password
+                .map(|password| {
+                    crate::actions::encrypt(
+                        &password,
+                        entry.org_id.as_deref(),
+                    )
+                })
+                .transpose()?;
+            let notes = notes
+                .map(|notes| {
+                    crate::actions::encrypt(&notes, entry.org_id.as_deref())
+                })
+                .transpose()?;
+            let mut history = entry.history.clone();
+            let rbw::db::EntryData::Login {
+                username: entry_username,
+                password: entry_password,
+                uris: entry_uris,
+                totp: entry_totp,
+            } = &entry.data
+            else {
+                unreachable!();
+            };
 
-    if folder.is_none() {
-        matches = entries
-            .iter()
-            .cloned()
-            .filter(|(_, decrypted_cipher)| {
-                decrypted_cipher.exact_match(name, username, folder, false)
-            })
-            .collect();
+            if let Some(prev_password) = entry_password.clone() {
+                let new_history_entry = rbw::db::HistoryEntry {
+                    last_used_date: format!(
+                        "{}",
+                        humantime::format_rfc3339(
+                            std::time::SystemTime::now()
+                        )
+                    ),
+                    password: prev_password,
+                };
+                history.insert(0, new_history_entry);
+            }
 
-        if matches.len() == 1 {
-            return Ok(matches[0].clone());
+            let data = rbw::db::EntryData::Login {
+                username: entry_username.clone(),
+                password,
+                uris: entry_uris.clone(),
+                totp: entry_totp.clone(),
+            };
+            (data, notes, history)
         }
-    }
-
-    matches = entries
-        .iter()
-        .cloned()
-        .filter(|(_, decrypted_cipher)| {
-            decrypted_cipher.partial_match(name, username, folder, true)
-        })
-        .collect();
+        DecryptedData::Card {
+            cardholder_name,
+            number,
+            brand,
+            exp_month,
+            exp_year,
+            code,
+        } => {
+            let rbw::db::EntryData::Card {
+                cardholder_name: entry_cardholder_name,
+                number: entry_number,
+                brand: entry_brand,
+                exp_month: entry_exp_month,
+                exp_year: entry_exp_year,
+                code: entry_code,
+            } = &entry.data
+            else {
+                unreachable!();
+            };
 
-    if matches.len() == 1 {
-        return Ok(matches[0].clone());
-    }
+            let (mut values, notes) = edit_fields(
+                &[
+                    ("cardholder_name", cardholder_name.as_deref()),
+                    ("number", number.as_deref()),
+                    ("brand", brand.as_deref()),
+                    ("exp_month", exp_month.as_deref()),
+                    ("exp_year", exp_year.as_deref()),
+                    ("code", code.as_deref()),
+                ],
+                decrypted.notes.as_deref(),
+                stdin,
+            )?;
+            validate_card(&values)?;
+            let org_id = entry.org_id.as_deref();
+            let mut field =
+                |key, old: &Option<String>, encrypted: &Option<String>| {
+                    keep_or_encrypt(
+                        values.remove(key),
+                        old.as_deref(),
+                        encrypted.as_ref(),
+                        org_id,
+                    )
+                };
+            let data = rbw::db::EntryData::Card {
+                cardholder_name: field(
+                    "cardholder_name",
+                    cardholder_name,
+                    entry_cardholder_name,
+                )?,
+                number: field("number", number, entry_number)?,
+                brand: field("brand", brand, entry_brand)?,
+                exp_month: field("exp_month", exp_month, entry_exp_month)?,
+                exp_year: field("exp_year", exp_year, entry_exp_year)?,
+                code: field("code", code, entry_code)?,
+            };
+            let notes = keep_or_encrypt(
+                notes,
+                decrypted.notes.as_deref(),
+                entry.notes.as_ref(),
+                org_id,
+            )?;
+            // cards don't have a password, so there is no history to add to
+            (data, notes, entry.history.clone())
+        }
+        DecryptedData::Identity {
+            title,
+            first_name,
+            middle_name,
+            last_name,
+            address1,
+            address2,
+            address3,
+            city,
+            state,
+            postal_code,
+            country,
+            phone,
+            email,
+            ssn,
+            license_number,
+            passport_number,
+            username,
+        } => {
+            let rbw::db::EntryData::Identity {
+                title: entry_title,
+                first_name: entry_first_name,
+                middle_name: entry_middle_name,
+                last_name: entry_last_name,
+                address1: entry_address1,
+                address2: entry_address2,
+                address3: entry_address3,
+                city: entry_city,
+                state: entry_state,
+                postal_code: entry_postal_code,
+                country: entry_country,
+                phone: entry_phone,
+                email: entry_email,
+                ssn: entry_ssn,
+                license_number: entry_license_number,
+                passport_number: entry_passport_number,
+                username: entry_username,
+            } = &entry.data
+            else {
+                unreachable!();
+            };
 
-    if folder.is_none() {
-        matches = entries
-            .iter()
-            .cloned()
-            .filter(|(_, decrypted_cipher)| {
-                decrypted_cipher.partial_match(name, username, folder, false)
-            })
-            .collect();
-        if matches.len() == 1 {
-            return Ok(matches[0].clone());
+            let (mut values, notes) = edit_fields(
+                &[
+                    ("title", title.as_deref()),
+                    ("first_name", first_name.as_deref()),
+                    ("middle_name", middle_name.as_deref()),
+                    ("last_name", last_name.as_deref()),
+                    ("address1", address1.as_deref()),
+                    ("address2", address2.as_deref()),
+                    ("address3", address3.as_deref()),
+                    ("city", city.as_deref()),
+                    ("state", state.as_deref()),
+                    ("postal_code", postal_code.as_deref()),
+                    ("country", country.as_deref()),
+                    ("phone", phone.as_deref()),
+                    ("email", email.as_deref()),
+                    ("ssn", ssn.as_deref()),
+                    ("license_number", license_number.as_deref()),
+                    ("passport_number", passport_number.as_deref()),
+                    ("username", username.as_deref()),
+                ],
+                decrypted.notes.as_deref(),
+                stdin,
+            )?;
+            let org_id = entry.org_id.as_deref();
+            let mut field =
+                |key, old: &Option<String>, encrypted: &Option<String>| {
+                    keep_or_encrypt(
+                        values.remove(key),
+                        old.as_deref(),
+                        encrypted.as_ref(),
+                        org_id,
+                    )
+                };
+            let data = rbw::db::EntryData::Identity {
+                title: field("title", title, entry_title)?,
+                first_name: field(
+                    "first_name",
+                    first_name,
+                    entry_first_name,
+                )?,
+                middle_name: field(
+                    "middle_name",
+                    middle_name,
+                    entry_middle_name,
+                )?,
+                last_name: field("last_name", last_name, entry_last_name)?,
+                address1: field("address1", address1, entry_address1)?,
+                address2: field("address2", address2, entry_address2)?,
+                address3: field("address3", address3, entry_address3)?,
+                city: field("city", city, entry_city)?,
+                state: field("state", state, entry_state)?,
+                postal_code: field(
+                    "postal_code",
+                    postal_code,
+                    entry_postal_code,
+                )?,
+                country: field("country", country, entry_country)?,
+                phone: field("phone", phone, entry_phone)?,
+                email: field("email", email, entry_email)?,
+                ssn: field("ssn", ssn, entry_ssn)?,
+                license_number: field(
+                    "license_number",
+                    license_number,
+                    entry_license_number,
+                )?,
+                passport_number: field(
+                    "passport_number",
+                    passport_number,
+                    entry_passport_number,
+                )?,
+                username: field("username", username, entry_username)?,
+            };
+            let notes = keep_or_encrypt(
+                notes,
+                decrypted.notes.as_deref(),
+                entry.notes.as_ref(),
+                org_id,
+            )?;
+            // identities don't have a password, so there is no history to
+            // add to
+            (data, notes, entry.history.clone())
         }
-    }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "modifications are only supported for login, card, and \
+                identity entries"
+            ));
+        }
+    };
 
-    if matches.is_empty() {
-        Err(anyhow::anyhow!("no entry found"))
-    } else {
-        let entries: Vec<String> = matches
-            .iter()
-            .map(|(_, decrypted)| decrypted.display_name())
-            .collect();
-        let entries = entries.join(", ");
-        Err(anyhow::anyhow!("multiple entries found: {}", entries))
+    if dry_run {
+        println!("dry run: would update '{path}' ({})", entry.id);
+        return Ok(());
     }
-}
 
-fn decrypt_field(
-    name: &str,
-    field: Option<&str>,
-    org_id: Option<&str>,
-) -> Option<String> {
-    let field = field
-        .as_ref()
-        .map(|field| crate::actions::decrypt(field, org_id))
-        .transpose();
-    match field {
-        Ok(field) => field,
-        Err(e) => {
-            log::warn!("failed to decrypt {}: {}", name, e);
-            None
+    if let (Some(access_token), ()) = rbw::actions::edit(
+        access_token,
+        refresh_token,
+        &entry.id,
+        entry.org_id.as_deref(),
+        &entry.name,
+        &data,
+        notes.as_deref(),
+        entry.folder_id.as_deref(),
+        &history,
+    )? {
+        db.access_token = Some(access_token);
+        save_db(&db)?;
+    }
+
+    sync_or_update_db(&mut db, no_sync, |db| {
+        db.upsert_entry(rbw::db::Entry {
+            data,
+            notes,
+            history,
+            ..entry
+        });
+    })?;
+
+    Ok(())
+}
+
+pub fn move_entry(
+    name: &str,
+    username: Option<&str>,
+    strict_folder: bool,
+    folder_name: &str,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let mut db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (entry, decrypted) =
+        find_entry(&db, name, username, None, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+
+    // an empty folder name moves the entry out of its folder
+    let folder_name = Some(folder_name).filter(|name| !name.is_empty());
+    if decrypted.folder.as_deref() == folder_name {
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "dry run: would move '{}' ({}){}",
+            decrypted.path(),
+            entry.id,
+            if folder_name.is_some() {
+                dry_run_folder_desc(&db, folder_name)?
+            } else {
+                " out of its folder".to_string()
+            }
+        );
+        return Ok(());
+    }
+
+    let folder_id = folder_name
+        .map(|folder_name| resolve_or_create_folder(&mut db, folder_name))
+        .transpose()?;
+    let folder = folder_name
+        .map(|folder_name| crate::actions::encrypt(folder_name, None))
+        .transpose()?;
+
+    let access_token = db.access_token.as_ref().unwrap();
+    let refresh_token = db.refresh_token.as_ref().unwrap();
+    if let (Some(access_token), ()) = rbw::actions::edit(
+        access_token,
+        refresh_token,
+        &entry.id,
+        entry.org_id.as_deref(),
+        &entry.name,
+        &entry.data,
+        entry.notes.as_deref(),
+        folder_id.as_deref(),
+        &entry.history,
+    )? {
+        db.access_token = Some(access_token);
+        save_db(&db)?;
+    }
+
+    sync_or_update_db(&mut db, no_sync, |db| {
+        db.upsert_entry(rbw::db::Entry {
+            folder,
+            folder_id,
+            ..entry
+        });
+    })?;
+
+    Ok(())
+}
+
+pub fn rename(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    new_name: &str,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if new_name.is_empty() {
+        return Err(anyhow::anyhow!("the new name can't be empty"));
+    }
+
+    unlock()?;
+
+    let mut db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (entry, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+
+    if dry_run {
+        println!(
+            "dry run: would rename '{}' ({}) to '{new_name}'",
+            decrypted.path(),
+            entry.id
+        );
+        return Ok(());
+    }
+
+    let new_name =
+        crate::actions::encrypt(new_name, entry.org_id.as_deref())?;
+
+    let access_token = db.access_token.as_ref().unwrap();
+    let refresh_token = db.refresh_token.as_ref().unwrap();
+    if let (Some(access_token), ()) = rbw::actions::edit(
+        access_token,
+        refresh_token,
+        &entry.id,
+        entry.org_id.as_deref(),
+        &new_name,
+        &entry.data,
+        entry.notes.as_deref(),
+        entry.folder_id.as_deref(),
+        &entry.history,
+    )? {
+        db.access_token = Some(access_token);
+        save_db(&db)?;
+    }
+
+    sync_or_update_db(&mut db, no_sync, |db| {
+        db.upsert_entry(rbw::db::Entry {
+            name: new_name,
+            ..entry
+        });
+    })?;
+
+    Ok(())
+}
+
+pub fn clone_entry(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    new_name: &str,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if new_name.is_empty() {
+        return Err(anyhow::anyhow!("the new name can't be empty"));
+    }
+
+    unlock()?;
+
+    let mut db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (entry, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+
+    // new entries are always created in the personal vault, and everything
+    // in an organization entry is encrypted with the organization's key
+    if entry.org_id.is_some() {
+        return Err(anyhow::anyhow!(
+            "'{}' belongs to an organization, and rbw can only create \
+            entries in the personal vault",
+            decrypted.path()
+        ));
+    }
+
+    if dry_run {
+        println!(
+            "dry run: would clone '{}' ({}) to '{new_name}'",
+            decrypted.path(),
+            entry.id
+        );
+        return Ok(());
+    }
+
+    let new_name = crate::actions::encrypt(new_name, None)?;
+
+    let (new_access_token, new_entry) = rbw::actions::add(
+        db.access_token.as_ref().unwrap(),
+        db.refresh_token.as_ref().unwrap(),
+        &new_name,
+        &entry.data,
+        entry.notes.as_deref(),
+        &entry.fields,
+        entry.folder_id.as_deref(),
+    )?;
+    if let Some(access_token) = new_access_token {
+        db.access_token = Some(access_token);
+        save_db(&db)?;
+    }
+
+    sync_or_update_db(&mut db, no_sync, |db| {
+        if let Some(new_entry) = new_entry {
+            // the response only includes the folder id
+            db.upsert_entry(rbw::db::Entry {
+                folder: entry.folder,
+                ..new_entry
+            });
+        } else {
+            log::warn!("couldn't update the local database, run `rbw sync`");
+        }
+    })?;
+
+    Ok(())
+}
+
+// finds the id of the folder with the given name, creating the folder if it
+// doesn't exist yet
+fn resolve_or_create_folder(
+    db: &mut rbw::db::Db,
+    folder_name: &str,
+) -> anyhow::Result<String> {
+    // unwrap is safe here because the callers have already called unlock,
+    // which is guaranteed to populate these or error
+    let refresh_token = db.refresh_token.clone().unwrap();
+
+    let (new_access_token, folders) = rbw::actions::list_folders(
+        db.access_token.as_ref().unwrap(),
+        &refresh_token,
+    )?;
+    if let Some(new_access_token) = new_access_token {
+        db.access_token = Some(new_access_token);
+        save_db(db)?;
+    }
+
+    for (id, name) in folders {
+        if crate::actions::decrypt(&name, None)? == folder_name {
+            return Ok(id);
         }
     }
+
+    let (new_access_token, id) = rbw::actions::create_folder(
+        db.access_token.as_ref().unwrap(),
+        &refresh_token,
+        &crate::actions::encrypt(folder_name, None)?,
+    )?;
+    if let Some(new_access_token) = new_access_token {
+        db.access_token = Some(new_access_token);
+        save_db(db)?;
+    }
+    Ok(id)
 }
 
-fn decrypt_cipher(entry: &rbw::db::Entry) -> anyhow::Result<DecryptedCipher> {
-    // folder name should always be decrypted with the local key because
-    // folders are local to a specific user's vault, not the organization
-    let folder = entry
-        .folder
-        .as_ref()
-        .map(|folder| crate::actions::decrypt(folder, None))
-        .transpose();
-    let folder = match folder {
-        Ok(folder) => folder,
-        Err(e) => {
-            log::warn!("failed to decrypt folder name: {}", e);
-            None
+pub fn remove(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    no_sync: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let mut db = load_db()?;
+    let access_token = db.access_token.as_ref().unwrap();
+    let refresh_token = db.refresh_token.as_ref().unwrap();
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (entry, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+
+    if dry_run {
+        println!(
+            "dry run: would remove '{}' ({})",
+            decrypted.path(),
+            entry.id
+        );
+        return Ok(());
+    }
+
+    if let (Some(access_token), ()) =
+        rbw::actions::remove(access_token, refresh_token, &entry.id)?
+    {
+        db.access_token = Some(access_token);
+        save_db(&db)?;
+    }
+
+    sync_or_update_db(&mut db, no_sync, |db| db.remove_entry(&entry.id))?;
+
+    Ok(())
+}
+
+pub fn attachments_list(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    porcelain: bool,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (_, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    for attachment in decrypted.attachments {
+        let size = attachment.size.as_deref().unwrap_or("");
+        if porcelain {
+            println!("{}", porcelain_line(&[&attachment.file_name, size]));
+        } else {
+            println!("{}\t{size}", attachment.file_name);
         }
-    };
-    let fields = entry
-        .fields
-        .iter()
-        .map(|field| {
-            Ok(DecryptedField {
-                name: field
-                    .name
-                    .as_ref()
-                    .map(|name| {
-                        crate::actions::decrypt(name, entry.org_id.as_deref())
-                    })
-                    .transpose()?,
-                value: field
-                    .value
-                    .as_ref()
-                    .map(|value| {
-                        crate::actions::decrypt(
-                            value,
-                            entry.org_id.as_deref(),
-                        )
-                    })
-                    .transpose()?,
+    }
+
+    Ok(())
+}
+
+pub fn attachments_get(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    file_name: &str,
+    output: Option<&std::path::Path>,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let mut db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (entry, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    let contents =
+        download_attachment(&mut db, &entry, &decrypted, file_name)?;
+
+    match output {
+        Some(path) if path == std::path::Path::new("-") => io::stdout()
+            .write_all(&contents)
+            .context("failed to write attachment to stdout"),
+        Some(path) => std::fs::write(path, &contents).with_context(|| {
+            format!("failed to write attachment to {}", path.display())
+        }),
+        None => {
+            // the file name comes from the server, so don't let it point
+            // outside of the current directory
+            let path = std::path::Path::new(file_name)
+                .file_name()
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "attachment name '{file_name}' isn't a valid file \
+                        name, use --output"
+                    )
+                })?;
+            std::fs::write(path, &contents).with_context(|| {
+                format!(
+                    "failed to write attachment to {}",
+                    std::path::Path::new(path).display()
+                )
             })
-        })
-        .collect::<anyhow::Result<_>>()?;
-    let notes = entry
-        .notes
-        .as_ref()
-        .map(|notes| crate::actions::decrypt(notes, entry.org_id.as_deref()))
-        .transpose();
-    let notes = match notes {
-        Ok(notes) => notes,
-        Err(e) => {
-            log::warn!("failed to decrypt notes: {}", e);
-            None
         }
-    };
-    let history = entry
-        .history
+    }
+}
+
+fn download_attachment(
+    db: &mut rbw::db::Db,
+    entry: &rbw::db::Entry,
+    decrypted: &DecryptedCipher,
+    file_name: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let attachment = decrypted
+        .attachments
         .iter()
-        .map(|history_entry| {
-            Ok(DecryptedHistoryEntry {
-                last_used_date: history_entry.last_used_date.clone(),
-                password: crate::actions::decrypt(
-                    &history_entry.password,
-                    entry.org_id.as_deref(),
-                )?,
-            })
+        .find(|attachment| attachment.file_name == file_name)
+        .and_then(|attachment| {
+            entry.attachments.iter().find(|a| a.id == attachment.id)
         })
-        .collect::<anyhow::Result<_>>()?;
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "entry '{}' has no attachment named '{file_name}'",
+                decrypted.display_name()
+            )
+        })?;
+
+    // unwrap is safe here because the call to unlock is guaranteed to
+    // populate these or error
+    let access_token = db.access_token.as_ref().unwrap();
+    let refresh_token = db.refresh_token.as_ref().unwrap();
+    let (new_access_token, data) = rbw::actions::download_attachment(
+        access_token,
+        refresh_token,
+        &entry.id,
+        &attachment.id,
+    )
+    .with_context(|| format!("failed to download '{file_name}'"))?;
+    if let Some(new_access_token) = new_access_token {
+        db.access_token = Some(new_access_token);
+        save_db(db)?;
+    }
 
-    let data = match &entry.data {
-        rbw::db::EntryData::Login {
-            username,
-            password,
-            totp,
-            uris,
-        } => DecryptedData::Login {
-            username: decrypt_field(
-                "username",
-                username.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            password: decrypt_field(
-                "password",
-                password.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            totp: decrypt_field(
-                "totp",
-                totp.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            uris: uris
-                .iter()
-                .map(|s| {
-                    decrypt_field(
-                        "uri",
-                        Some(&s.uri),
-                        entry.org_id.as_deref(),
-                    )
-                    .map(|uri| DecryptedUri {
-                        uri,
-                        match_type: s.match_type,
-                    })
-                })
-                .collect(),
-        },
-        rbw::db::EntryData::Card {
-            cardholder_name,
-            number,
-            brand,
-            exp_month,
-            exp_year,
-            code,
-        } => DecryptedData::Card {
-            cardholder_name: decrypt_field(
-                "cardholder_name",
-                cardholder_name.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            number: decrypt_field(
-                "number",
-                number.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            brand: decrypt_field(
-                "brand",
-                brand.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            exp_month: decrypt_field(
-                "exp_month",
-                exp_month.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            exp_year: decrypt_field(
-                "exp_year",
-                exp_year.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            code: decrypt_field(
-                "code",
-                code.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-        },
-        rbw::db::EntryData::Identity {
-            title,
-            first_name,
-            middle_name,
-            last_name,
-            address1,
-            address2,
-            address3,
-            city,
-            state,
-            postal_code,
-            country,
-            phone,
-            email,
-            ssn,
-            license_number,
-            passport_number,
-            username,
-        } => DecryptedData::Identity {
-            title: decrypt_field(
-                "title",
-                title.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            first_name: decrypt_field(
-                "first_name",
-                first_name.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            middle_name: decrypt_field(
-                "middle_name",
-                middle_name.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            last_name: decrypt_field(
-                "last_name",
-                last_name.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            address1: decrypt_field(
-                "address1",
-                address1.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            address2: decrypt_field(
-                "address2",
-                address2.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            address3: decrypt_field(
-                "address3",
-                address3.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            city: decrypt_field(
-                "city",
-                city.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            state: decrypt_field(
-                "state",
-                state.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            postal_code: decrypt_field(
-                "postal_code",
-                postal_code.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            country: decrypt_field(
-                "country",
-                country.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            phone: decrypt_field(
-                "phone",
-                phone.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            email: decrypt_field(
-                "email",
-                email.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            ssn: decrypt_field(
-                "ssn",
-                ssn.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            license_number: decrypt_field(
-                "license_number",
-                license_number.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            passport_number: decrypt_field(
-                "passport_number",
-                passport_number.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-            username: decrypt_field(
-                "username",
-                username.as_deref(),
-                entry.org_id.as_deref(),
-            ),
-        },
-        rbw::db::EntryData::SecureNote {} => DecryptedData::SecureNote {},
-    };
+    crate::actions::decrypt_attachment(
+        &data,
+        attachment.key.as_deref(),
+        entry.org_id.as_deref(),
+    )
+    .with_context(|| format!("failed to decrypt '{file_name}'"))
+}
 
-    Ok(DecryptedCipher {
-        id: entry.id.clone(),
-        folder,
-        name: crate::actions::decrypt(&entry.name, entry.org_id.as_deref())?,
-        data,
-        fields,
-        notes,
-        history,
-    })
+pub fn pending(porcelain: bool) -> anyhow::Result<()> {
+    unlock()?;
+
+    let db = load_db()?;
+
+    for (id, pending) in &db.pending {
+        let name = crate::actions::decrypt(
+            &pending.name,
+            pending.org_id.as_deref(),
+        )?;
+        let state = if pending.removed {
+            "removed"
+        } else {
+            "modified"
+        };
+        if porcelain {
+            println!("{}", porcelain_line(&[id, &name, state]));
+        } else if pending.removed {
+            println!("{name} (removed)");
+        } else {
+            println!("{name}");
+        }
+    }
+
+    Ok(())
+}
+
+pub fn fields(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    porcelain: bool,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (_, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    for line in decrypted.field_list_lines(porcelain) {
+        println!("{line}");
+    }
+
+    Ok(())
 }
 
-fn parse_editor(contents: &str) -> (Option<String>, Option<String>) {
-    let mut lines = contents.lines();
+pub fn history(
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    strict_folder: bool,
+    raw: bool,
+    porcelain: bool,
+) -> anyhow::Result<()> {
+    unlock()?;
+
+    let db = load_db()?;
+
+    let desc = format!(
+        "{}{}",
+        username.map_or_else(String::new, |s| format!("{s}@")),
+        name
+    );
+
+    let (_, decrypted) =
+        find_entry(&db, name, username, folder, false, strict_folder)
+            .with_context(|| format!("couldn't find entry for '{desc}'"))?;
+    if raw {
+        serde_json::to_writer_pretty(std::io::stdout(), &decrypted.history)
+            .context("failed to write history to stdout")?;
+        println!();
+        return Ok(());
+    }
+    for history in decrypted.history {
+        if porcelain {
+            println!(
+                "{}",
+                porcelain_line(&[&history.last_used_date, &history.password])
+            );
+        } else {
+            println!("{}: {}", history.last_used_date, history.password);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn lock() -> anyhow::Result<()> {
+    ensure_agent()?;
+    crate::actions::lock()?;
+
+    Ok(())
+}
+
+pub fn purge(force: bool) -> anyhow::Result<()> {
+    let config = rbw::config::Config::load()?;
+    let email = config.email.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("failed to find email address in config")
+    })?;
+    eprintln!(
+        "removing the local database for {email} on {}",
+        config.server_name()
+    );
+
+    if !force && !confirm("are you sure?")? {
+        return Err(anyhow::anyhow!("aborted"));
+    }
+
+    stop_agent()?;
+
+    remove_db()?;
+    remove_usage()?;
+
+    Ok(())
+}
+
+pub fn stop_agent() -> anyhow::Result<()> {
+    crate::actions::quit()?;
+
+    Ok(())
+}
+
+fn ensure_agent() -> anyhow::Result<()> {
+    check_config()?;
+
+    ensure_agent_once()?;
+    let client_version = rbw::protocol::version();
+    let agent_version = version_or_quit()?;
+    if agent_version != client_version {
+        log::debug!(
+            "client protocol version is {} but agent protocol version is {}",
+            client_version,
+            agent_version
+        );
+        crate::actions::quit()?;
+        ensure_agent_once()?;
+        let agent_version = version_or_quit()?;
+        if agent_version != client_version {
+            crate::actions::quit()?;
+            return Err(anyhow::anyhow!(
+                "incompatible protocol versions: client ({}), agent ({})",
+                client_version,
+                agent_version
+            ));
+        }
+    }
+
+    // the agent reads and writes the local copy of the database itself (when
+    // syncing, including in the background), so it has to be using the same
+    // directory as this process, which may have been changed with
+    // --data-dir or $RBW_DATA_DIR since the agent was started. a restarted
+    // agent inherits this process's environment.
+    let client_dir = rbw::dirs::db_dir().to_string_lossy().into_owned();
+    let agent_dir = crate::actions::data_dir().inspect_err(|_| {
+        let _ = crate::actions::quit();
+    })?;
+    if agent_dir != client_dir {
+        log::debug!(
+            "client data directory is {} but agent data directory is {}",
+            client_dir,
+            agent_dir
+        );
+        crate::actions::quit()?;
+        ensure_agent_once()?;
+    }
+    Ok(())
+}
+
+fn ensure_agent_once() -> anyhow::Result<()> {
+    let agent_path = std::env::var("RBW_AGENT");
+    let agent_path = agent_path
+        .as_ref()
+        .map(std::string::String::as_str)
+        .unwrap_or("rbw-agent");
+    let status = match std::process::Command::new(agent_path).status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AgentNotFound {
+                path: agent_path.to_string(),
+                from_env: std::env::var_os("RBW_AGENT").is_some(),
+            }
+            .into());
+        }
+        Err(e) => return Err(e).context("failed to run rbw-agent"),
+    };
+    if !status.success() {
+        if let Some(code) = status.code() {
+            if code != 23 {
+                return Err(anyhow::anyhow!(
+                    "failed to run rbw-agent: {}",
+                    status
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// an entry which doesn't have a value for its main field at all (as opposed
+// to one which couldn't be decrypted)
+#[derive(Debug)]
+struct MissingValue(String);
+
+impl std::fmt::Display for MissingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for MissingValue {}
+
+// reported separately (with its own exit status), since it usually means
+// that only the rbw binary was installed
+#[derive(Debug)]
+pub struct AgentNotFound {
+    path: String,
+    from_env: bool,
+}
+
+impl std::fmt::Display for AgentNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.from_env {
+            write!(
+                f,
+                "rbw-agent not found at {} (from RBW_AGENT); fix or unset \
+                RBW_AGENT",
+                self.path
+            )
+        } else {
+            write!(
+                f,
+                "rbw-agent not found on PATH; install it or set RBW_AGENT \
+                to its location"
+            )
+        }
+    }
+}
+
+impl std::error::Error for AgentNotFound {}
+
+fn check_config() -> anyhow::Result<()> {
+    rbw::config::Config::validate().map_err(|e| {
+        log::error!("{}", MISSING_CONFIG_HELP);
+        anyhow::Error::new(e)
+    })
+}
+
+fn version_or_quit() -> anyhow::Result<u32> {
+    crate::actions::version().map_err(|e| {
+        let _ = crate::actions::quit();
+        e
+    })
+}
+
+fn find_entry(
+    db: &rbw::db::Db,
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    search_notes: bool,
+    strict_folder: bool,
+) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
+    single_entry(find_entries(
+        db,
+        name,
+        username,
+        folder,
+        None,
+        search_notes,
+        strict_folder,
+        None,
+    )?)
+}
+
+fn find_entries(
+    db: &rbw::db::Db,
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    org_id: Option<&str>,
+    search_notes: bool,
+    // with a strict folder, only entries in the given folder (or, without
+    // --folder, entries which aren't in any folder) can match
+    strict_folder: bool,
+    // used instead of the match type stored with each uri when looking up
+    // an entry by url
+    uri_match: Option<rbw::api::UriMatchType>,
+) -> anyhow::Result<Vec<(rbw::db::Entry, DecryptedCipher)>> {
+    if uuid::Uuid::parse_str(name).is_ok() {
+        let entries: Vec<_> = db
+            .entries
+            .iter()
+            .filter(|cipher| name == cipher.id && in_org(cipher, org_id))
+            .collect();
+        let cache = load_decrypt_cache(entries.iter().copied())?;
+        let matches = entries
+            .into_iter()
+            .map(|cipher| {
+                Ok((cipher.clone(), decrypt_cipher(cipher, cache.as_ref())?))
+            })
+            .collect();
+        store_decrypt_cache(cache);
+        matches
+    } else {
+        let entries: Vec<_> = supported_entries(db)
+            .filter(|entry| in_org(entry, org_id))
+            .collect();
+        let cache = load_decrypt_cache(entries.iter().copied())?;
+        let ciphers: Vec<(rbw::db::Entry, DecryptedCipher)> = entries
+            .into_iter()
+            .map(|entry| {
+                decrypt_cipher(entry, cache.as_ref())
+                    .map(|decrypted| (entry.clone(), decrypted))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        store_decrypt_cache(cache);
+        // groups of domains which are considered the same site when
+        // matching uris
+        let equivalent_domains =
+            rbw::config::Config::load()?.equivalent_domains;
+        let mut matches = if folder.is_none()
+            && name.contains('/')
+            && url::Url::parse(name).is_err()
+        {
+            let (needle_folder, needle_name) = split_folder_needle(name);
+            find_entries_raw(
+                &ciphers,
+                &needle_name,
+                username,
+                needle_folder.as_deref(),
+                search_notes,
+                strict_folder,
+                uri_match,
+                &equivalent_domains,
+            )
+        } else {
+            vec![]
+        };
+        // names which weren't meant as folder/name (and urls) are matched
+        // as given
+        if matches.is_empty() {
+            matches = find_entries_raw(
+                &ciphers,
+                name,
+                username,
+                folder,
+                search_notes,
+                strict_folder,
+                uri_match,
+                &equivalent_domains,
+            );
+        }
+        if matches.is_empty() {
+            if let Ok(url) = url::Url::parse(name) {
+                let entries: Vec<_> = ciphers
+                    .iter()
+                    .filter(|(_, decrypted)| {
+                        folder.is_none()
+                            || decrypted.folder.as_deref() == folder
+                    })
+                    .filter(|(_, decrypted)| {
+                        decrypted.never_matches_url(&url, &equivalent_domains)
+                    })
+                    .map(|(_, decrypted)| decrypted.display_name())
+                    .collect();
+                if !entries.is_empty() {
+                    return Err(
+                        FindEntryError::NeverMatched { entries }.into()
+                    );
+                }
+            }
+        }
+        Ok(matches)
+    }
+}
+
+// splits "folder/name" on the first single '/', with "//" standing for a
+// literal '/' in either part
+fn split_folder_needle(needle: &str) -> (Option<String>, String) {
+    let mut folder = None;
+    let mut name = String::new();
+    let mut chars = needle.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '/' {
+            name.push(c);
+        } else if chars.next_if_eq(&'/').is_some() {
+            name.push('/');
+        } else if folder.is_none() {
+            folder = Some(std::mem::take(&mut name));
+        } else {
+            name.push('/');
+        }
+    }
+    (folder, name)
+}
+
+// organizations can be given either by id or by (case insensitive) name.
+// names are only known for databases which were synced since they started
+// being stored.
+fn resolve_org(db: &rbw::db::Db, org: &str) -> anyhow::Result<String> {
+    if db.protected_org_keys.contains_key(org) {
+        return Ok(org.to_string());
+    }
+
+    let mut ids: Vec<_> = db
+        .org_names
+        .iter()
+        .filter(|(_, name)| name.eq_ignore_ascii_case(org))
+        .map(|(id, _)| id.clone())
+        .collect();
+    match ids.len() {
+        1 => Ok(ids.remove(0)),
+        0 if db.org_names.is_empty() && !db.protected_org_keys.is_empty() => {
+            Err(anyhow::anyhow!(
+                "couldn't find organization '{org}' (organization names \
+                aren't known yet, run rbw sync or use the organization id)"
+            ))
+        }
+        0 => Err(anyhow::anyhow!("couldn't find organization '{org}'")),
+        _ => {
+            ids.sort_unstable();
+            Err(anyhow::anyhow!(
+                "multiple organizations named '{org}': {}",
+                ids.join(", ")
+            ))
+        }
+    }
+}
+
+fn in_org(entry: &rbw::db::Entry, org_id: Option<&str>) -> bool {
+    org_id.is_none() || entry.org_id.as_deref() == org_id
+}
+
+fn single_entry(
+    matches: Vec<(rbw::db::Entry, DecryptedCipher)>,
+) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
+    let on_multiple_matches = rbw::config::Config::load()
+        .map(|config| config.on_multiple_matches)
+        .unwrap_or_default();
+    pick_entry(matches, on_multiple_matches)
+}
+
+fn pick_entry(
+    mut matches: Vec<(rbw::db::Entry, DecryptedCipher)>,
+    on_multiple_matches: rbw::config::MultipleMatches,
+) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
+    if matches.len() == 1 {
+        Ok(matches.remove(0))
+    } else if matches.is_empty() {
+        Err(FindEntryError::NotFound.into())
+    } else if on_multiple_matches == rbw::config::MultipleMatches::First {
+        Ok(matches.remove(0))
+    } else if on_multiple_matches == rbw::config::MultipleMatches::Newest {
+        // entries without a (parseable) revision date are treated as the
+        // oldest, and the first of several equally new entries is used
+        let revision = |entry: &rbw::db::Entry| {
+            entry.revision_date.as_deref().and_then(parse_revision_date)
+        };
+        let newest = matches
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, (entry, _))| revision(entry))
+            .map(|(i, _)| i)
+            .unwrap();
+        Ok(matches.remove(newest))
+    } else {
+        Err(FindEntryError::MultipleFound {
+            entries: matches
+                .iter()
+                .map(|(_, decrypted)| decrypted.display_name())
+                .collect(),
+        }
+        .into())
+    }
+}
+
+// revision dates from the server are in utc, with varying precision
+fn parse_revision_date(date: &str) -> Option<std::time::SystemTime> {
+    humantime::parse_rfc3339_weak(date.trim_end_matches('Z')).ok()
+}
+
+// these are reported with their own exit statuses, so that scripts can tell
+// a typo apart from a needle which needs to be more specific
+#[derive(Debug)]
+pub enum FindEntryError {
+    NotFound,
+    // nothing matched, but these entries have the given uri with uri
+    // matching disabled
+    NeverMatched { entries: Vec<String> },
+    MultipleFound { entries: Vec<String> },
+}
+
+impl std::fmt::Display for FindEntryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no entry found"),
+            Self::NeverMatched { entries } => {
+                let names = entries
+                    .iter()
+                    .map(|entry| format!("'{entry}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if entries.len() == 1 {
+                    write!(
+                        f,
+                        "no entry found (entry {names} has this uri, but \
+                        match type is Never)"
+                    )
+                } else {
+                    write!(
+                        f,
+                        "no entry found (entries {names} have this uri, but \
+                        their match type is Never)"
+                    )
+                }
+            }
+            Self::MultipleFound { entries } => {
+                write!(f, "multiple entries found: {}", entries.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FindEntryError {}
+
+// returns the first set of matches which is unambiguous, trying more and more
+// lenient ways of matching, or else the last (ambiguous or empty) set
+fn find_entries_raw(
+    entries: &[(rbw::db::Entry, DecryptedCipher)],
+    name: &str,
+    username: Option<&str>,
+    folder: Option<&str>,
+    search_notes: bool,
+    strict_folder: bool,
+    uri_match: Option<rbw::api::UriMatchType>,
+    equivalent_domains: &[Vec<String>],
+) -> Vec<(rbw::db::Entry, DecryptedCipher)> {
+    // the passes which ignore folders are only used when no folder was
+    // given, and not at all when the folder is strict
+    let any_folder = folder.is_none() && !strict_folder;
+
+    if let Ok(url) = url::Url::parse(name) {
+        if url.has_host() {
+            let in_folder = |(_, decrypted_cipher): &&(
+                rbw::db::Entry,
+                DecryptedCipher,
+            )| {
+                any_folder || decrypted_cipher.folder.as_deref() == folder
+            };
+            let mut matches: Vec<_> = entries
+                .iter()
+                .filter(in_folder)
+                .filter(|(_, decrypted_cipher)| {
+                    decrypted_cipher.matches_url(
+                        &url,
+                        username,
+                        uri_match,
+                        equivalent_domains,
+                    )
+                })
+                .collect();
+            // urls mentioned in the text of an entry are much less precise
+            // than stored uris, so they are only searched when asked for and
+            // when no stored uri matched
+            if matches.is_empty() && search_notes && username.is_none() {
+                matches = entries
+                    .iter()
+                    .filter(in_folder)
+                    .filter(|(_, decrypted_cipher)| {
+                        decrypted_cipher
+                            .text_matches_url(&url, equivalent_domains)
+                    })
+                    .collect();
+            }
+            // entries can also be named after urls, so fall back to matching
+            // on the name
+            if !matches.is_empty() {
+                return matches.into_iter().cloned().collect();
+            }
+        }
+    }
+
+    // each pass is more lenient than the one before it, so the first pass
+    // which matches anything is the most precise answer we can give
+    let mut matches: Vec<(rbw::db::Entry, DecryptedCipher)> = entries
+        .iter()
+        .cloned()
+        .filter(|(_, decrypted_cipher)| {
+            decrypted_cipher.exact_match(name, username, folder, true)
+        })
+        .collect();
+    if !matches.is_empty() {
+        return matches;
+    }
+
+    if any_folder {
+        matches = entries
+            .iter()
+            .cloned()
+            .filter(|(_, decrypted_cipher)| {
+                decrypted_cipher.exact_match(name, username, folder, false)
+            })
+            .collect();
+        if !matches.is_empty() {
+            return matches;
+        }
+    }
+
+    matches = entries
+        .iter()
+        .cloned()
+        .filter(|(_, decrypted_cipher)| {
+            decrypted_cipher
+                .partial_match(name, username, folder, true, false)
+        })
+        .collect();
+    if !matches.is_empty() {
+        return matches;
+    }
+
+    if any_folder {
+        matches = entries
+            .iter()
+            .cloned()
+            .filter(|(_, decrypted_cipher)| {
+                decrypted_cipher
+                    .partial_match(name, username, folder, false, false)
+            })
+            .collect();
+        if !matches.is_empty() {
+            return matches;
+        }
+    }
+
+    // case and accent insensitive matching is only a last resort, so that
+    // this can't make a previously unambiguous search ambiguous
+    entries
+        .iter()
+        .filter(|(_, decrypted_cipher)| {
+            decrypted_cipher.partial_match(
+                name,
+                username,
+                folder,
+                !any_folder,
+                true,
+            )
+        })
+        .cloned()
+        .collect()
+}
+
+// decomposes the string and strips combining characters, so that for
+// instance "Café" and "cafe" compare equal
+fn fold_case_and_accents(s: &str) -> String {
+    s.nfkd()
+        .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn decrypt_field(
+    decrypt: &crate::actions::DecryptFn<'_>,
+    name: &str,
+    field: Option<&str>,
+    org_id: Option<&str>,
+    failed_fields: &mut Vec<String>,
+) -> Option<String> {
+    let field = field
+        .as_ref()
+        .map(|field| decrypt(field, org_id))
+        .transpose();
+    match field {
+        Ok(field) => field,
+        Err(e) => {
+            log::warn!("failed to decrypt {}: {}", name, e);
+            // keep track of this separately so that we can tell the
+            // difference between a field that is empty and a field that we
+            // couldn't decrypt (usually because of a missing org key)
+            failed_fields.push(name.to_string());
+            None
+        }
+    }
+}
+
+// entries of cipher types that rbw doesn't know about yet are skipped (with
+// a warning) rather than making every command that looks through the whole
+// vault fail
+// a note given without --password-file creates an entry without a password
+// on purpose, so only a password which was actually given (or left empty in
+// the editor) needs --allow-empty
+fn check_empty_password(
+    password: Option<&str>,
+    note_only: bool,
+    allow_empty: bool,
+) -> anyhow::Result<()> {
+    if !allow_empty && !note_only && password.unwrap_or("").is_empty() {
+        return Err(anyhow::anyhow!(
+            "refusing to store an empty password; use --allow-empty"
+        ));
+    }
+    Ok(())
+}
+
+fn supported_entries(
+    db: &rbw::db::Db,
+) -> impl Iterator<Item = &rbw::db::Entry> {
+    db.entries.iter().filter(|entry| {
+        if let rbw::db::EntryData::Unsupported { ty } = entry.data {
+            log::warn!(
+                "skipping entry {} with unsupported cipher type {}",
+                entry.id,
+                ty
+            );
+            false
+        } else {
+            true
+        }
+    })
+}
+
+fn decrypt_cipher(
+    entry: &rbw::db::Entry,
+    cache: Option<&crate::actions::DecryptCache>,
+) -> anyhow::Result<DecryptedCipher> {
+    cache.map_or_else(
+        || decrypt_cipher_fields(entry, &crate::actions::decrypt),
+        |cache| {
+            cache.with_entry(entry, |decrypt| {
+                decrypt_cipher_fields(entry, decrypt)
+            })
+        },
+    )
+}
+
+fn decrypt_cipher_fields(
+    entry: &rbw::db::Entry,
+    decrypt: &crate::actions::DecryptFn<'_>,
+) -> anyhow::Result<DecryptedCipher> {
+    let mut failed_fields = vec![];
+
+    // folder name should always be decrypted with the local key because
+    // folders are local to a specific user's vault, not the organization
+    let folder = entry
+        .folder
+        .as_ref()
+        .map(|folder| decrypt(folder, None))
+        .transpose();
+    let folder = match folder {
+        Ok(folder) => folder,
+        Err(e) => {
+            log::warn!("failed to decrypt folder name: {}", e);
+            failed_fields.push("folder".to_string());
+            None
+        }
+    };
+    let fields = entry
+        .fields
+        .iter()
+        .map(|field| {
+            Ok(DecryptedField {
+                ty: field.ty,
+                name: field
+                    .name
+                    .as_ref()
+                    .map(|name| decrypt(name, entry.org_id.as_deref()))
+                    .transpose()?,
+                value: field
+                    .value
+                    .as_ref()
+                    .map(|value| decrypt(value, entry.org_id.as_deref()))
+                    .transpose()?,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let notes = entry
+        .notes
+        .as_ref()
+        .map(|notes| decrypt(notes, entry.org_id.as_deref()))
+        .transpose();
+    let notes = match notes {
+        Ok(notes) => notes,
+        Err(e) => {
+            log::warn!("failed to decrypt notes: {}", e);
+            failed_fields.push("notes".to_string());
+            None
+        }
+    };
+    let history = entry
+        .history
+        .iter()
+        .map(|history_entry| {
+            Ok(DecryptedHistoryEntry {
+                last_used_date: history_entry.last_used_date.clone(),
+                password: decrypt(
+                    &history_entry.password,
+                    entry.org_id.as_deref(),
+                )?,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let data = match &entry.data {
+        rbw::db::EntryData::Login {
+            username,
+            password,
+            totp,
+            uris,
+        } => DecryptedData::Login {
+            username: decrypt_field(
+                decrypt,
+                "username",
+                username.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            password: decrypt_field(
+                decrypt,
+                "password",
+                password.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            totp: decrypt_field(
+                decrypt,
+                "totp",
+                totp.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            uris: uris
+                .iter()
+                .map(|s| {
+                    decrypt_field(
+                        decrypt,
+                        "uri",
+                        Some(&s.uri),
+                        entry.org_id.as_deref(),
+                        &mut failed_fields,
+                    )
+                    .map(|uri| DecryptedUri {
+                        uri,
+                        match_type: s.match_type,
+                    })
+                })
+                .collect(),
+        },
+        rbw::db::EntryData::Card {
+            cardholder_name,
+            number,
+            brand,
+            exp_month,
+            exp_year,
+            code,
+        } => DecryptedData::Card {
+            cardholder_name: decrypt_field(
+                decrypt,
+                "cardholder_name",
+                cardholder_name.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            number: decrypt_field(
+                decrypt,
+                "number",
+                number.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            brand: decrypt_field(
+                decrypt,
+                "brand",
+                brand.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            exp_month: decrypt_field(
+                decrypt,
+                "exp_month",
+                exp_month.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            exp_year: decrypt_field(
+                decrypt,
+                "exp_year",
+                exp_year.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            code: decrypt_field(
+                decrypt,
+                "code",
+                code.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+        },
+        rbw::db::EntryData::Identity {
+            title,
+            first_name,
+            middle_name,
+            last_name,
+            address1,
+            address2,
+            address3,
+            city,
+            state,
+            postal_code,
+            country,
+            phone,
+            email,
+            ssn,
+            license_number,
+            passport_number,
+            username,
+        } => DecryptedData::Identity {
+            title: decrypt_field(
+                decrypt,
+                "title",
+                title.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            first_name: decrypt_field(
+                decrypt,
+                "first_name",
+                first_name.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            middle_name: decrypt_field(
+                decrypt,
+                "middle_name",
+                middle_name.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            last_name: decrypt_field(
+                decrypt,
+                "last_name",
+                last_name.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            address1: decrypt_field(
+                decrypt,
+                "address1",
+                address1.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            address2: decrypt_field(
+                decrypt,
+                "address2",
+                address2.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            address3: decrypt_field(
+                decrypt,
+                "address3",
+                address3.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            city: decrypt_field(
+                decrypt,
+                "city",
+                city.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            state: decrypt_field(
+                decrypt,
+                "state",
+                state.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            postal_code: decrypt_field(
+                decrypt,
+                "postal_code",
+                postal_code.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            country: decrypt_field(
+                decrypt,
+                "country",
+                country.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            phone: decrypt_field(
+                decrypt,
+                "phone",
+                phone.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            email: decrypt_field(
+                decrypt,
+                "email",
+                email.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            ssn: decrypt_field(
+                decrypt,
+                "ssn",
+                ssn.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            license_number: decrypt_field(
+                decrypt,
+                "license_number",
+                license_number.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            passport_number: decrypt_field(
+                decrypt,
+                "passport_number",
+                passport_number.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            username: decrypt_field(
+                decrypt,
+                "username",
+                username.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+        },
+        rbw::db::EntryData::SecureNote {} => DecryptedData::SecureNote {},
+        rbw::db::EntryData::SshKey {
+            private_key,
+            public_key,
+            fingerprint,
+        } => DecryptedData::SshKey {
+            private_key: decrypt_field(
+                decrypt,
+                "private_key",
+                private_key.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            public_key: decrypt_field(
+                decrypt,
+                "public_key",
+                public_key.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+            fingerprint: decrypt_field(
+                decrypt,
+                "fingerprint",
+                fingerprint.as_deref(),
+                entry.org_id.as_deref(),
+                &mut failed_fields,
+            ),
+        },
+        rbw::db::EntryData::Unsupported { ty } => {
+            return Err(rbw::error::Error::UnsupportedCipherType { ty: *ty })
+                .context(format!("failed to decrypt entry {}", entry.id));
+        }
+    };
+
+    let attachments = entry
+        .attachments
+        .iter()
+        .filter_map(|attachment| {
+            match decrypt(&attachment.file_name, entry.org_id.as_deref()) {
+                Ok(file_name) => Some(DecryptedAttachment {
+                    id: attachment.id.clone(),
+                    file_name,
+                    size: attachment.size.clone(),
+                }),
+                Err(e) => {
+                    log::warn!("failed to decrypt attachment name: {}", e);
+                    if !failed_fields.iter().any(|f| f == "attachments") {
+                        failed_fields.push("attachments".to_string());
+                    }
+                    None
+                }
+            }
+        })
+        .collect();
+
+    Ok(DecryptedCipher {
+        id: entry.id.clone(),
+        folder,
+        name: decrypt(&entry.name, entry.org_id.as_deref())?,
+        data,
+        fields,
+        notes,
+        history,
+        creation_date: entry.creation_date.clone(),
+        revision_date: entry.revision_date.clone(),
+        attachments,
+        failed_fields,
+    })
+}
+
+// uris on the command line can have a match type appended after a final
+// ':'. this can't be confused with a port or anything else in a url, since
+// only the names of match types are recognized there.
+pub fn parse_uri_arg(
+    arg: &str,
+) -> anyhow::Result<(String, Option<rbw::api::UriMatchType>)> {
+    let (uri, match_type) = arg
+        .rsplit_once(':')
+        .and_then(|(uri, match_type)| {
+            match_type
+                .parse()
+                .ok()
+                .map(|match_type| (uri, Some(match_type)))
+        })
+        .unwrap_or((arg, None));
+    if uri.is_empty() {
+        return Err(anyhow::anyhow!("uri can't be empty"));
+    }
+    validate_uri(uri, match_type)?;
+    Ok((uri.to_string(), match_type))
+}
+
+// an invalid regex would just never match anything, so this is checked
+// when storing a uri rather than failing silently when looking up the entry
+fn validate_uri(
+    uri: &str,
+    match_type: Option<rbw::api::UriMatchType>,
+) -> anyhow::Result<()> {
+    if match_type == Some(rbw::api::UriMatchType::RegularExpression) {
+        regex::Regex::new(uri)
+            .map_err(|e| anyhow::anyhow!("invalid regex '{uri}': {e}"))?;
+    }
+    Ok(())
+}
+
+// reading from stdin skips the editor entirely, for scripts. the contents
+// are expected in the same format the editor would have produced.
+fn edit_contents(
+    contents: &str,
+    help: &str,
+    stdin: bool,
+) -> anyhow::Result<String> {
+    if !stdin {
+        return Ok(rbw::edit::edit(contents, help)?);
+    }
+    let mut contents = String::new();
+    io::stdin()
+        .read_to_string(&mut contents)
+        .context("failed to read from stdin")?;
+    Ok(contents)
+}
+
+// so that passwords don't need to be passed on the command line, where other
+// users could see them
+fn read_password_file(path: &std::path::Path) -> anyhow::Result<String> {
+    let mut password = std::fs::read_to_string(path).with_context(|| {
+        format!("failed to read password from {}", path.display())
+    })?;
+    if password.ends_with('\n') {
+        password.pop();
+        if password.ends_with('\r') {
+            password.pop();
+        }
+    }
+    Ok(password)
+}
+
+fn parse_editor(contents: &str) -> (Option<String>, Option<String>) {
+    let mut lines = contents.lines();
+
+    let password = lines.next().map(std::string::ToString::to_string);
+
+    let mut notes: String = lines
+        .skip_while(|line| line.is_empty())
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    while notes.ends_with('\n') {
+        notes.pop();
+    }
+    let notes = if notes.is_empty() { None } else { Some(notes) };
+
+    (password, notes)
+}
+
+// the editor format for entry types which are made up of a fixed set of
+// fields: one "key: value" line per field, followed by the notes
+fn field_template(
+    fields: &[(&str, Option<&str>)],
+    notes: Option<&str>,
+) -> String {
+    use std::fmt::Write as _;
+
+    let mut contents = String::new();
+    for (key, value) in fields {
+        writeln!(contents, "{key}: {}", value.unwrap_or("")).unwrap();
+    }
+    writeln!(contents, "notes: {}", notes.unwrap_or("")).unwrap();
+    contents
+}
+
+fn parse_field_editor(
+    contents: &str,
+    fields: &[&str],
+) -> anyhow::Result<(std::collections::HashMap<String, String>, Option<String>)>
+{
+    let mut values = std::collections::HashMap::new();
+    let mut lines = contents.lines().filter(|line| !line.starts_with('#'));
+    let mut notes = None;
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("invalid line '{line}'"))?;
+        let key = key.trim();
+        let value = value.trim();
+        if key == "notes" {
+            let text = std::iter::once(value)
+                .chain(lines.by_ref())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let text = text.trim_matches('\n');
+            if !text.trim().is_empty() {
+                notes = Some(text.to_string());
+            }
+            break;
+        }
+        if !fields.contains(&key) {
+            return Err(anyhow::anyhow!("unknown field '{key}'"));
+        }
+        if !value.is_empty() {
+            values.insert(key.to_string(), value.to_string());
+        }
+    }
+    Ok((values, notes))
+}
+
+fn edit_fields(
+    fields: &[(&str, Option<&str>)],
+    notes: Option<&str>,
+    stdin: bool,
+) -> anyhow::Result<(std::collections::HashMap<String, String>, Option<String>)>
+{
+    let contents =
+        edit_contents(&field_template(fields, notes), FIELDS_HELP, stdin)?;
+    let keys: Vec<_> = fields.iter().map(|(key, _)| *key).collect();
+    parse_field_editor(&contents, &keys)
+}
+
+fn take_encrypted(
+    values: &mut std::collections::HashMap<String, String>,
+    key: &str,
+    org_id: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    values
+        .remove(key)
+        .map(|value| crate::actions::encrypt(&value, org_id))
+        .transpose()
+}
+
+// values which weren't changed in the editor keep their existing encrypted
+// form. this also keeps values which failed to decrypt (and so were shown as
+// empty) from being wiped out.
+fn keep_or_encrypt(
+    value: Option<String>,
+    old: Option<&str>,
+    encrypted: Option<&String>,
+    org_id: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    if value.as_deref() == old {
+        return Ok(encrypted.cloned());
+    }
+    value
+        .map(|value| crate::actions::encrypt(&value, org_id))
+        .transpose()
+}
+
+fn validate_card(
+    values: &std::collections::HashMap<String, String>,
+) -> anyhow::Result<()> {
+    if let Some(month) = values.get("exp_month") {
+        if !matches!(month.parse::<u8>(), Ok(1..=12)) {
+            return Err(anyhow::anyhow!(
+                "invalid expiration month '{month}', expected 1-12"
+            ));
+        }
+    }
+    if let Some(year) = values.get("exp_year") {
+        if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow::anyhow!(
+                "invalid expiration year '{year}', expected four digits"
+            ));
+        }
+    }
+    Ok(())
+}
+
+// dry runs shouldn't contact the server, so this only finds folders which
+// already contain entries in the local database
+fn dry_run_folder_desc(
+    db: &rbw::db::Db,
+    folder: Option<&str>,
+) -> anyhow::Result<String> {
+    let Some(folder_name) = folder else {
+        return Ok(String::new());
+    };
+    for entry in &db.entries {
+        if let (Some(id), Some(name)) = (&entry.folder_id, &entry.folder) {
+            if crate::actions::decrypt(name, None)? == folder_name {
+                return Ok(format!(" to folder '{folder_name}' ({id})"));
+            }
+        }
+    }
+    Ok(format!(
+        " to folder '{folder_name}' (creating it if it doesn't exist)"
+    ))
+}
+
+// after modifying an entry on the server, either sync the full database
+// back down, or (if syncing was disabled or failed) apply the same change to
+// the local copy of the database directly
+fn sync_or_update_db(
+    db: &mut rbw::db::Db,
+    no_sync: bool,
+    update: impl FnOnce(&mut rbw::db::Db),
+) -> anyhow::Result<()> {
+    let config = rbw::config::Config::load()?;
+    match sync_or_apply_update(
+        db,
+        syncs_after_write(no_sync, &config),
+        update,
+        crate::actions::sync,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => save_local_db(db, &config),
+        Err(e) => {
+            save_local_db(db, &config)?;
+            Err(e.context(
+                "the change was saved, but the local copy couldn't be \
+                refreshed",
+            ))
+        }
+    }
+}
+
+// returns whether the local copy was refreshed by syncing. otherwise (and
+// also when syncing fails, since the change was already made on the server)
+// the update is applied to the local copy directly, and it needs to be saved
+fn sync_or_apply_update(
+    db: &mut rbw::db::Db,
+    sync_after_write: bool,
+    update: impl FnOnce(&mut rbw::db::Db),
+    sync: impl FnOnce() -> anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    if sync_after_write {
+        let Err(e) = sync() else {
+            return Ok(true);
+        };
+        apply_local_update(db, update);
+        return Err(e);
+    }
+
+    apply_local_update(db, update);
+    Ok(false)
+}
+
+fn save_local_db(
+    db: &rbw::db::Db,
+    config: &rbw::config::Config,
+) -> anyhow::Result<()> {
+    save_db(db)?;
+    // syncing clears the cache in the agent itself
+    if config.decrypt_cache {
+        crate::actions::clear_decrypt_cache()?;
+    }
+
+    Ok(())
+}
+
+fn syncs_after_write(no_sync: bool, config: &rbw::config::Config) -> bool {
+    !no_sync && config.sync_after_write
+}
+
+// entries changed this way are tracked as pending until the next sync
+// replaces them with the server's copy
+fn apply_local_update(
+    db: &mut rbw::db::Db,
+    update: impl FnOnce(&mut rbw::db::Db),
+) {
+    let before = db.entries.clone();
+    update(db);
+    db.mark_pending(&before);
+}
+
+fn load_db() -> anyhow::Result<rbw::db::Db> {
+    let config = rbw::config::Config::load()?;
+    let db = config.email.as_ref().map_or_else(
+        || Err(anyhow::anyhow!("failed to find email address in config")),
+        |email| {
+            rbw::db::Db::load(&config.server_name(), email)
+                .map_err(anyhow::Error::new)
+        },
+    )?;
+    Ok(db)
+}
+
+// the cache is only an optimization, so everything is decrypted from scratch
+// if it isn't enabled or isn't available
+fn load_decrypt_cache<'a>(
+    entries: impl IntoIterator<Item = &'a rbw::db::Entry>,
+) -> anyhow::Result<Option<crate::actions::DecryptCache>> {
+    if !rbw::config::Config::load()?.decrypt_cache {
+        return Ok(None);
+    }
+    match crate::actions::DecryptCache::load(entries) {
+        Ok(cache) => Ok(Some(cache)),
+        Err(e) => {
+            log::debug!("{e:#}");
+            Ok(None)
+        }
+    }
+}
+
+fn store_decrypt_cache(cache: Option<crate::actions::DecryptCache>) {
+    if let Some(cache) = cache {
+        if let Err(e) = cache.store() {
+            log::warn!("failed to update the decrypt cache: {e:#}");
+        }
+    }
+}
+
+fn save_db(db: &rbw::db::Db) -> anyhow::Result<()> {
+    let config = rbw::config::Config::load()?;
+    config.email.as_ref().map_or_else(
+        || Err(anyhow::anyhow!("failed to find email address in config")),
+        |email| {
+            db.save(&config.server_name(), email)
+                .map_err(anyhow::Error::new)
+        },
+    )
+}
+
+// when track_usage is enabled, the time each entry was last displayed by
+// `rbw get` or `rbw code` is recorded in a local file (and never sent to the
+// server), keyed by entry id
+fn usage_file() -> anyhow::Result<std::path::PathBuf> {
+    let config = rbw::config::Config::load()?;
+    let email = config.email.as_ref().ok_or_else(|| {
+        anyhow::anyhow!("failed to find email address in config")
+    })?;
+    Ok(rbw::dirs::usage_file(&config.server_name(), email))
+}
+
+fn load_usage() -> anyhow::Result<std::collections::HashMap<String, String>> {
+    let file = usage_file()?;
+    match std::fs::read_to_string(&file) {
+        Ok(json) => serde_json::from_str(&json).with_context(|| {
+            format!("failed to parse usage data from {}", file.display())
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(std::collections::HashMap::new())
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!("failed to load usage data from {}", file.display())
+        }),
+    }
+}
+
+// failing to record usage shouldn't prevent displaying the entry
+fn record_usage(id: &str) {
+    if let Err(e) = try_record_usage(id) {
+        log::warn!("{e:#}");
+    }
+}
+
+fn try_record_usage(id: &str) -> anyhow::Result<()> {
+    if !rbw::config::Config::load()?.track_usage {
+        return Ok(());
+    }
+    let mut usage = load_usage()?;
+    usage.insert(
+        id.to_string(),
+        humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+            .to_string(),
+    );
+    let file = usage_file()?;
+    std::fs::write(&file, serde_json::to_string(&usage)?).with_context(|| {
+        format!("failed to save usage data to {}", file.display())
+    })
+}
+
+fn remove_usage() -> anyhow::Result<()> {
+    let file = usage_file()?;
+    match std::fs::remove_file(&file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e)
+            .with_context(|| {
+                format!("failed to remove usage data at {}", file.display())
+            }),
+        _ => Ok(()),
+    }
+}
+
+fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "not running interactively, pass --force to skip confirmation"
+        ));
+    }
+
+    eprint!("{prompt} [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read confirmation")?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn remove_db() -> anyhow::Result<()> {
+    let config = rbw::config::Config::load()?;
+    config.email.as_ref().map_or_else(
+        || Err(anyhow::anyhow!("failed to find email address in config")),
+        |email| {
+            rbw::db::Db::remove(&config.server_name(), email)
+                .map_err(anyhow::Error::new)
+        },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl std::str::FromStr for TotpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "sha1" => Self::Sha1,
+            "sha256" => Self::Sha256,
+            "sha512" => Self::Sha512,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "unknown totp algorithm {s}, expected one of sha1, \
+                    sha256, sha512"
+                ))
+            }
+        })
+    }
+}
+
+// explicitly requested totp parameters, which take precedence over the
+// parameters stored in an otpauth url
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TotpOverrides {
+    pub digits: Option<u32>,
+    pub period: Option<u64>,
+    pub algorithm: Option<TotpAlgorithm>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct TotpParams {
+    key: Vec<u8>,
+    algorithm: TotpAlgorithm,
+    digits: u32,
+    period: u64,
+    issuer: Option<String>,
+    account: Option<String>,
+    // steam guard codes, which are five characters from a custom alphabet
+    // rather than decimal digits
+    steam: bool,
+}
+
+impl TotpParams {
+    fn apply(mut self, overrides: &TotpOverrides) -> anyhow::Result<Self> {
+        if let Some(digits) = overrides.digits {
+            self.digits = digits;
+        }
+        if let Some(period) = overrides.period {
+            self.period = period;
+        }
+        if let Some(algorithm) = overrides.algorithm {
+            self.algorithm = algorithm;
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    fn code_at(&self, time: u64) -> String {
+        if self.steam {
+            return steam_code(&self.key, time / self.period);
+        }
+        match self.algorithm {
+            TotpAlgorithm::Sha1 => totp_lite::totp_custom::<totp_lite::Sha1>(
+                self.period,
+                self.digits,
+                &self.key,
+                time,
+            ),
+            TotpAlgorithm::Sha256 => {
+                totp_lite::totp_custom::<totp_lite::Sha256>(
+                    self.period,
+                    self.digits,
+                    &self.key,
+                    time,
+                )
+            }
+            TotpAlgorithm::Sha512 => {
+                totp_lite::totp_custom::<totp_lite::Sha512>(
+                    self.period,
+                    self.digits,
+                    &self.key,
+                    time,
+                )
+            }
+        }
+    }
+
+    // an otpauth url containing everything needed to generate the same
+    // codes, for moving the secret to another authenticator. the entry name
+    // and username are used for whatever parts of the label weren't stored.
+    fn to_uri(&self, name: &str, username: Option<&str>) -> String {
+        use std::fmt::Write as _;
+
+        let encode = |s: &str| {
+            percent_encoding::utf8_percent_encode(
+                s,
+                percent_encoding::NON_ALPHANUMERIC,
+            )
+            .to_string()
+        };
+        let issuer = self.issuer.as_deref().unwrap_or(name);
+        let account = self.account.as_deref().or(username).unwrap_or(name);
+        let secret = base32::encode(
+            base32::Alphabet::RFC4648 { padding: false },
+            &self.key,
+        );
+        let mut uri = format!(
+            "otpauth://totp/{}:{}?secret={secret}&issuer={}",
+            encode(issuer),
+            encode(account),
+            encode(issuer),
+        );
+        if self.steam {
+            uri.push_str("&digits=5&encoder=steam");
+        } else {
+            let algorithm = match self.algorithm {
+                TotpAlgorithm::Sha1 => "SHA1",
+                TotpAlgorithm::Sha256 => "SHA256",
+                TotpAlgorithm::Sha512 => "SHA512",
+            };
+            write!(uri, "&algorithm={algorithm}&digits={}", self.digits)
+                .unwrap();
+        }
+        write!(uri, "&period={}", self.period).unwrap();
+        uri
+    }
+
+    fn raw_at(&self, time: u64) -> TotpCode {
+        let code = self.code_at(time);
+        TotpCode {
+            digits: code.chars().count(),
+            code,
+            period: self.period,
+            expires_at: (time / self.period + 1) * self.period,
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        // steam guard codes always have five characters, whatever digits
+        // says
+        if !self.steam && !(6..=8).contains(&self.digits) {
+            return Err(anyhow::anyhow!(
+                "totp digits must be between 6 and 8 (got {})",
+                self.digits
+            ));
+        }
+        if self.period == 0 {
+            return Err(anyhow::anyhow!("totp period must be positive"));
+        }
+        Ok(())
+    }
+}
+
+fn parse_totp_secret(secret: &str) -> anyhow::Result<TotpParams> {
+    let mut algorithm = TotpAlgorithm::Sha1;
+    let mut digits = 6;
+    let mut period = totp_lite::DEFAULT_STEP;
+    let mut issuer = None;
+    let mut account = None;
+    let mut steam = false;
+    let secret_str = if let Some(secret) = secret.strip_prefix("steam://") {
+        steam = true;
+        secret.to_string()
+    } else if let Ok(u) = url::Url::parse(secret) {
+        if u.scheme() != "otpauth" {
+            return Err(anyhow::anyhow!(
+                "totp secret url must have otpauth scheme"
+            ));
+        }
+        if u.host_str() != Some("totp") {
+            return Err(anyhow::anyhow!(
+                "totp secret url must have totp host"
+            ));
+        }
+        // the label is either "account" or "issuer:account", and the
+        // account itself may contain further colons
+        let label = percent_encoding::percent_decode_str(
+            u.path().trim_start_matches('/'),
+        )
+        .decode_utf8_lossy()
+        .to_string();
+        if let Some((label_issuer, label_account)) = label.split_once(':') {
+            issuer = Some(label_issuer.trim().to_string());
+            account = Some(label_account.trim().to_string());
+        } else if !label.is_empty() {
+            account = Some(label);
+        }
+        let query: std::collections::HashMap<_, _> =
+            u.query_pairs().collect();
+        // the issuer parameter is preferred over the label prefix
+        if let Some(value) = query.get("issuer") {
+            issuer = Some(value.to_string());
+        }
+        if let Some(value) = query.get("algorithm") {
+            algorithm = value.parse()?;
+        }
+        if let Some(value) = query.get("digits") {
+            digits = value.parse().map_err(|_| {
+                anyhow::anyhow!("totp secret url has invalid digits")
+            })?;
+        }
+        if let Some(value) = query.get("period") {
+            period = value.parse().map_err(|_| {
+                anyhow::anyhow!("totp secret url has invalid period")
+            })?;
+        }
+        // not part of the standard, but used by some authenticator apps
+        // (with digits=5) for steam guard codes
+        match query.get("encoder").map(|value| value.to_lowercase()) {
+            None => {}
+            Some(encoder) if encoder == "steam" => steam = true,
+            Some(encoder) => {
+                return Err(anyhow::anyhow!(
+                    "totp secret url has unsupported encoder {encoder}"
+                ))
+            }
+        }
+        query
+            .get("secret")
+            .ok_or_else(|| {
+                anyhow::anyhow!("totp secret url must have secret")
+            })?
+            .to_string()
+    } else {
+        secret.to_string()
+    };
+    let key = base32::decode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &secret_str.replace(' ', ""),
+    )
+    .ok_or_else(|| anyhow::anyhow!("totp secret was not valid base32"))?;
+    let params = TotpParams {
+        key,
+        algorithm,
+        digits,
+        period,
+        issuer,
+        account,
+        steam,
+    };
+    params.validate()?;
+    Ok(params)
+}
+
+fn steam_code(key: &[u8], counter: u64) -> String {
+    use hmac::Mac as _;
+
+    const ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+
+    // hmac accepts keys of any length
+    let mut mac = hmac::Hmac::<sha1::Sha1>::new_from_slice(key).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // the same dynamic truncation as regular totp codes
+    let offset = usize::from(hash[hash.len() - 1] & 0xf);
+    let mut code =
+        u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap())
+            & 0x7fff_ffff;
+    (0..5)
+        .map(|_| {
+            let c = ALPHABET[usize::try_from(code % 26).unwrap()];
+            code /= 26;
+            char::from(c)
+        })
+        .collect()
+}
+
+// generates the codes for the given range of time steps relative to the
+// current one (so 0..=0 is just the current code, and 1..=1 is the next one),
+// one per line
+fn generate_totp(
+    secret: &str,
+    overrides: &TotpOverrides,
+    windows: &std::ops::RangeInclusive<u64>,
+) -> anyhow::Result<String> {
+    let params = parse_totp_secret(secret)?.apply(overrides)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let codes: Vec<String> = windows
+        .clone()
+        .map(|window| params.code_at(now + window * params.period))
+        .collect();
+    Ok(codes.join("\n"))
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+struct TotpCode {
+    code: String,
+    period: u64,
+    digits: usize,
+    // unix timestamp
+    expires_at: u64,
+}
+
+// the first code in the given windows along with everything needed to
+// display how long it is valid for, as json
+fn totp_raw(
+    secret: &str,
+    overrides: &TotpOverrides,
+    windows: &std::ops::RangeInclusive<u64>,
+) -> anyhow::Result<String> {
+    let params = parse_totp_secret(secret)?.apply(overrides)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let code = params.raw_at(now + windows.start() * params.period);
+    serde_json::to_string(&code).context("failed to serialize totp code")
+}
+
+// rendered with unicode half blocks, with the colors inverted since
+// terminals usually have a dark background
+fn qr_code(data: &str) -> anyhow::Result<String> {
+    let code = qrcode::QrCode::new(data.as_bytes())
+        .context("failed to generate QR code")?;
+    Ok(code
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build()
+        + "\n")
+}
+
+// the number of seconds until the current code expires
+fn totp_remaining(
+    secret: &str,
+    overrides: &TotpOverrides,
+) -> anyhow::Result<u64> {
+    let params = parse_totp_secret(secret)?.apply(overrides)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    Ok(params.period - now % params.period)
+}
+
+// redraws the current code and how many more seconds it is valid for on a
+// single line once a second, until interrupted
+fn watch_totp(secret: &str, overrides: &TotpOverrides) -> anyhow::Result<()> {
+    let params = parse_totp_secret(secret)?.apply(overrides)?;
+    let mut stdout = io::stdout();
+    loop {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)?;
+        let secs = now.as_secs();
+        let remaining = params.period - secs % params.period;
+        // clear to the end of the line, in case the previous line was longer
+        write!(stdout, "\r{} ({remaining}s)\x1b[K", params.code_at(secs))?;
+        stdout.flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(1).saturating_sub(
+            std::time::Duration::from_nanos(u64::from(now.subsec_nanos())),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_entry() {
+        let entries = &[
+            make_entry("github", Some("foo"), None),
+            make_entry("gitlab", Some("foo"), None),
+            make_entry("gitlab", Some("bar"), None),
+            make_entry("gitter", Some("baz"), None),
+            make_entry("git", Some("foo"), None),
+            make_entry("bitwarden", None, None),
+            make_entry("github", Some("foo"), Some("websites")),
+            make_entry("github", Some("foo"), Some("ssh")),
+            make_entry("github", Some("root"), Some("ssh")),
+            make_entry("Café", None, None),
+            make_entry("résumé", Some("Zoë"), None),
+            make_entry("Cafe", Some("foo"), Some("Dépôt")),
+        ];
+
+        assert!(
+            one_match(entries, "github", Some("foo"), None, 0),
+            "foo@github"
+        );
+        assert!(one_match(entries, "github", None, None, 0), "github");
+        assert!(
+            one_match(entries, "gitlab", Some("foo"), None, 1),
+            "foo@gitlab"
+        );
+        assert!(one_match(entries, "git", Some("bar"), None, 2), "bar@git");
+        assert!(
+            one_match(entries, "gitter", Some("ba"), None, 3),
+            "ba@gitter"
+        );
+        assert!(one_match(entries, "git", Some("foo"), None, 4), "foo@git");
+        assert!(one_match(entries, "git", None, None, 4), "git");
+        assert!(one_match(entries, "bitwarden", None, None, 5), "bitwarden");
+        assert!(
+            one_match(entries, "github", Some("foo"), Some("websites"), 6),
+            "websites/foo@github"
+        );
+        assert!(
+            one_match(entries, "github", Some("foo"), Some("ssh"), 7),
+            "ssh/foo@github"
+        );
+        assert!(
+            one_match(entries, "github", Some("root"), None, 8),
+            "ssh/root@github"
+        );
+
+        assert!(
+            no_matches(entries, "gitlab", Some("baz"), None),
+            "baz@gitlab"
+        );
+        assert!(
+            no_matches(entries, "bitbucket", Some("foo"), None),
+            "foo@bitbucket"
+        );
+        assert!(
+            no_matches(entries, "github", Some("foo"), Some("bar")),
+            "bar/foo@github"
+        );
+        assert!(
+            no_matches(entries, "gitlab", Some("foo"), Some("bar")),
+            "bar/foo@gitlab"
+        );
+
+        assert!(one_match(entries, "Café", None, None, 9), "Café");
+        assert!(one_match(entries, "Cafe", None, None, 11), "Cafe");
+        assert!(one_match(entries, "resume", None, None, 10), "resume");
+        assert!(
+            one_match(entries, "RÉSUMÉ", Some("zoe"), None, 10),
+            "zoe@RÉSUMÉ"
+        );
+        assert!(
+            one_match(entries, "caf", None, Some("depot"), 11),
+            "depot/caf"
+        );
+        assert!(
+            no_matches(entries, "resume", Some("foo"), None),
+            "foo@resume"
+        );
+
+        assert!(many_matches(entries, "gitlab", None, None), "gitlab");
+        assert!(many_matches(entries, "caf", None, None), "caf");
+        assert!(many_matches(entries, "gi", Some("foo"), None), "foo@gi");
+        assert!(many_matches(entries, "git", Some("ba"), None), "ba@git");
+        assert!(
+            many_matches(entries, "github", Some("foo"), Some("s")),
+            "s/foo@github"
+        );
+    }
+
+    #[test]
+    fn test_totp_fallback() {
+        let secret = "JBSWY3DPEHPK3PXP";
+        let url = format!("otpauth://totp/foo?secret={secret}");
+
+        let (_, mut decrypted) = make_entry("github", None, None);
+        assert_eq!(decrypted.totp(), None);
+
+        decrypted.fields.push(DecryptedField {
+            ty: None,
+            name: Some("TOTP".to_string()),
+            value: Some("not a secret!".to_string()),
+        });
+        assert_eq!(decrypted.totp(), None);
+
+        decrypted.fields.push(DecryptedField {
+            ty: None,
+            name: Some("something else".to_string()),
+            value: Some(url.clone()),
+        });
+        assert_eq!(decrypted.totp(), Some(url.as_str()));
+
+        decrypted.fields.push(DecryptedField {
+            ty: None,
+            name: Some("totp".to_string()),
+            value: Some(secret.to_string()),
+        });
+        assert_eq!(decrypted.totp(), Some(secret));
+
+        if let DecryptedData::Login { totp, .. } = &mut decrypted.data {
+            *totp = Some("dedicated".to_string());
+        }
+        assert_eq!(decrypted.totp(), Some("dedicated"));
+    }
+
+    #[test]
+    fn test_field_or_failed() {
+        let (_, mut decrypted) = make_entry("github", None, None);
+        decrypted.failed_fields.push("username".to_string());
+
+        assert_eq!(
+            decrypted.field_or_failed("username", None),
+            Some(DECRYPT_FAILED)
+        );
+        assert_eq!(decrypted.field_or_failed("totp", None), None);
+        assert_eq!(
+            decrypted.field_or_failed("username", Some("foo")),
+            Some("foo")
+        );
+    }
+
+    fn find_entry_raw(
+        entries: &[(rbw::db::Entry, DecryptedCipher)],
+        name: &str,
+        username: Option<&str>,
+        folder: Option<&str>,
+        search_notes: bool,
+    ) -> anyhow::Result<(rbw::db::Entry, DecryptedCipher)> {
+        pick_entry(
+            find_entries_raw(
+                entries,
+                name,
+                username,
+                folder,
+                search_notes,
+                false,
+                None,
+                &[],
+            ),
+            rbw::config::MultipleMatches::Error,
+        )
+    }
+
+    fn one_match(
+        entries: &[(rbw::db::Entry, DecryptedCipher)],
+        name: &str,
+        username: Option<&str>,
+        folder: Option<&str>,
+        idx: usize,
+    ) -> bool {
+        entries_eq(
+            &find_entry_raw(entries, name, username, folder, false).unwrap(),
+            &entries[idx],
+        )
+    }
+
+    fn no_matches(
+        entries: &[(rbw::db::Entry, DecryptedCipher)],
+        name: &str,
+        username: Option<&str>,
+        folder: Option<&str>,
+    ) -> bool {
+        let res = find_entry_raw(entries, name, username, folder, false);
+        if let Err(e) = res {
+            format!("{e}").contains("no entry found")
+        } else {
+            false
+        }
+    }
+
+    fn many_matches(
+        entries: &[(rbw::db::Entry, DecryptedCipher)],
+        name: &str,
+        username: Option<&str>,
+        folder: Option<&str>,
+    ) -> bool {
+        let res = find_entry_raw(entries, name, username, folder, false);
+        if let Err(e) = res {
+            format!("{e}").contains("multiple entries found")
+        } else {
+            false
+        }
+    }
+
+    fn entries_eq(
+        a: &(rbw::db::Entry, DecryptedCipher),
+        b: &(rbw::db::Entry, DecryptedCipher),
+    ) -> bool {
+        a.0 == b.0 && a.1 == b.1
+    }
+
+    #[test]
+    fn test_validate_raw_keys() {
+        assert!(validate_raw_keys(&[]).is_ok());
+        assert!(validate_raw_keys(&["name".to_string(), "data".to_string()])
+            .is_ok());
+        let err = validate_raw_keys(&["password".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("valid keys are: id, folder"));
+    }
+
+    #[test]
+    fn test_matches_url() {
+        fn uri(s: &str, ty: rbw::api::UriMatchType) -> DecryptedUri {
+            DecryptedUri {
+                uri: s.to_string(),
+                match_type: Some(ty),
+            }
+        }
+        fn url(s: &str) -> url::Url {
+            url::Url::parse(s).unwrap()
+        }
+        use rbw::api::UriMatchType::{
+            Domain, Exact, Host, Never, RegularExpression, StartsWith,
+        };
+
+        let login = url("https://example.com/login?next=/home#top");
+
+        assert!(uri("https://example.com", Domain).matches_url(&login, &[]));
+        assert!(uri("example.com", Domain).matches_url(&login, &[]));
+        assert!(uri("https://example.com", Domain)
+            .matches_url(&url("https://accounts.example.com/"), &[]));
+        assert!(!uri("https://example.com", Domain)
+            .matches_url(&url("https://notexample.com/"), &[]));
+        assert!(
+            uri("https://www.example.com", Domain).matches_url(&login, &[])
+        );
+        assert!(uri("https://example.com", Domain)
+            .matches_url(&url("https://www.example.com/"), &[]));
+        assert!(uri("www.example.com", Domain)
+            .matches_url(&url("https://accounts.example.com/"), &[]));
+        assert!(uri("https://accounts.example.com", Domain)
+            .matches_url(&url("https://www.example.com/"), &[]));
+        assert!(!uri("https://www.com", Domain).matches_url(&login, &[]));
+        assert!(uri("https://example.co.uk", Domain)
+            .matches_url(&url("https://www.example.co.uk/"), &[]));
+        assert!(!uri("https://example.co.uk", Domain)
+            .matches_url(&url("https://other.co.uk/"), &[]));
+        assert!(!uri("https://foo.github.io", Domain)
+            .matches_url(&url("https://bar.github.io/"), &[]));
+        assert!(uri("https://192.168.1.1", Domain)
+            .matches_url(&url("https://192.168.1.1:8443/"), &[]));
+        assert!(!uri("https://192.168.1.1", Domain)
+            .matches_url(&url("https://10.168.1.1/"), &[]));
+
+        assert!(
+            uri("https://example.com:443/foo", Host).matches_url(&login, &[])
+        );
+        assert!(
+            !uri("https://example.com:8443", Host).matches_url(&login, &[])
+        );
+        assert!(!uri("https://example.com", Host)
+            .matches_url(&url("https://accounts.example.com/"), &[]));
+
+        assert!(uri("https://example.com/log", StartsWith)
+            .matches_url(&login, &[]));
+        assert!(!uri("https://example.com/login?next=/other", StartsWith)
+            .matches_url(&login, &[]));
+
+        assert!(
+            uri("https://example.com/login", Exact).matches_url(&login, &[])
+        );
+        assert!(uri("https://example.com/login?next=/home#top", Exact)
+            .matches_url(&login, &[]));
+        assert!(!uri("https://example.com/login?next=/other", Exact)
+            .matches_url(&login, &[]));
+        assert!(!uri("https://example.com/", Exact).matches_url(&login, &[]));
+        assert!(uri("https://example.com/login/", Exact)
+            .matches_url(&url("https://example.com/login"), &[]));
+        assert!(uri("https://example.com/login", Exact)
+            .matches_url(&url("https://example.com/login/"), &[]));
+
+        assert!(uri(
+            r"^https://example\.com/login\?next=",
+            RegularExpression
+        )
+        .matches_url(&login, &[]));
+        assert!(!uri("[", RegularExpression).matches_url(&login, &[]));
+
+        assert!(!uri("https://example.com", Never).matches_url(&login, &[]));
+
+        let groups =
+            vec![vec!["example.com".to_string(), "example.org".to_string()]];
+        let other = url("https://www.example.org/");
+        assert!(
+            uri("https://example.com", Domain).matches_url(&other, &groups)
+        );
+        assert!(!uri("https://example.com", Domain).matches_url(&other, &[]));
+        assert!(
+            !uri("https://example.com", Host).matches_url(&other, &groups)
+        );
+
+        let default = DecryptedUri {
+            uri: "https://example.com".to_string(),
+            match_type: None,
+        };
+        assert!(default.matches_url(&login, &[]));
+
+        assert!(!uri("https://example.com", Domain).matches_url_as(
+            &login,
+            Some(Exact),
+            &[]
+        ));
+        assert!(uri("https://example.com/login", Host).matches_url_as(
+            &login,
+            Some(Exact),
+            &[]
+        ));
+        assert!(default.matches_url_as(&login, Some(Host), &[]));
+        assert!(!default.matches_url_as(
+            &url("https://accounts.example.com/"),
+            Some(Host),
+            &[]
+        ));
+        assert!(!uri("https://example.com", Never).matches_url_as(
+            &login,
+            Some(Domain),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        let groups = vec![
+            vec!["google.com".to_string(), "youtube.com".to_string()],
+            vec!["amazon.com".to_string(), "amazon.co.uk".to_string()],
+        ];
+
+        assert!(domain_matches("google.com", "www.youtube.com", &groups));
+        assert!(domain_matches(
+            "youtube.com",
+            "accounts.google.com",
+            &groups
+        ));
+        assert!(domain_matches(
+            "accounts.google.com",
+            "youtube.com",
+            &groups
+        ));
+        assert!(domain_matches("amazon.co.uk", "www.amazon.com", &groups));
+        assert!(!domain_matches("google.com", "amazon.com", &groups));
+        assert!(!domain_matches("google.com", "youtube.com", &[]));
+        assert!(!domain_matches("notgoogle.com", "youtube.com", &groups));
+        assert!(domain_matches("www.amazon.co.uk", "amazon.com", &groups));
+        assert!(!domain_matches("amazon.co.uk", "amazon.de", &groups));
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("example.com"), "example.com");
+        assert_eq!(registrable_domain("www.example.com"), "example.com");
+        assert_eq!(
+            registrable_domain("login.accounts.example.co.uk"),
+            "example.co.uk"
+        );
+        assert_eq!(registrable_domain("foo.github.io"), "foo.github.io");
+        assert_eq!(registrable_domain("co.uk"), "co.uk");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+        assert_eq!(registrable_domain("10.0.0.1"), "10.0.0.1");
+        assert_eq!(registrable_domain("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_parse_equivalent_domains() {
+        assert_eq!(
+            parse_equivalent_domains(
+                "google.com, YouTube.com;amazon.com,amazon.co.uk;"
+            )
+            .unwrap(),
+            vec![
+                vec!["google.com".to_string(), "youtube.com".to_string()],
+                vec!["amazon.com".to_string(), "amazon.co.uk".to_string()],
+            ]
+        );
+        assert!(parse_equivalent_domains("").unwrap().is_empty());
+        assert!(parse_equivalent_domains("google.com").is_err());
+    }
+
+    #[test]
+    fn test_find_entry_by_url() {
+        let mut entries = vec![
+            make_entry("example", Some("foo"), None),
+            make_entry("other", None, None),
+        ];
+        if let DecryptedData::Login { uris, .. } = &mut entries[0].1.data {
+            *uris = Some(vec![DecryptedUri {
+                uri: "https://example.com/login".to_string(),
+                match_type: Some(rbw::api::UriMatchType::Exact),
+            }]);
+        }
+        let found = find_entry_raw(
+            &entries,
+            "https://example.com/login?next=/",
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(found.1.name, "example");
+        assert!(find_entry_raw(
+            &entries,
+            "https://example.com/login",
+            Some("bar"),
+            None,
+            false
+        )
+        .is_err());
+        assert!(find_entry_raw(
+            &entries,
+            "https://example.com/other",
+            None,
+            None,
+            false
+        )
+        .is_err());
+
+        let found = |uri_match| {
+            find_entries_raw(
+                &entries,
+                "https://example.com/other",
+                None,
+                None,
+                false,
+                false,
+                uri_match,
+                &[],
+            )
+            .len()
+        };
+        assert_eq!(found(None), 0);
+        assert_eq!(found(Some(rbw::api::UriMatchType::Domain)), 1);
+    }
+
+    #[test]
+    fn test_find_entries_raw() {
+        let entries = &[
+            make_entry("aws", Some("prod"), None),
+            make_entry("aws", Some("staging"), None),
+            make_entry("gcp", Some("prod"), None),
+        ];
+        let names = |name: &str, username: Option<&str>| {
+            find_entries_raw(
+                entries,
+                name,
+                username,
+                None,
+                false,
+                false,
+                None,
+                &[],
+            )
+            .into_iter()
+            .map(|(_, decrypted)| decrypted.display_name())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("aws", None), vec!["prod@aws", "staging@aws"]);
+        assert_eq!(names("aws", Some("staging")), vec!["staging@aws"]);
+        assert_eq!(names("gcp", None), vec!["prod@gcp"]);
+        assert!(names("azure", None).is_empty());
+    }
+
+    #[test]
+    fn test_find_entries_raw_strict_folder() {
+        let entries = &[
+            make_entry("github", None, Some("work")),
+            make_entry("github", None, None),
+            make_entry("gitlab", None, Some("work")),
+        ];
+        let paths = |name: &str, folder: Option<&str>, strict: bool| {
+            find_entries_raw(
+                entries,
+                name,
+                None,
+                folder,
+                false,
+                strict,
+                None,
+                &[],
+            )
+            .into_iter()
+            .map(|(_, decrypted)| decrypted.path())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths("gitlab", None, false), vec!["work/gitlab"]);
+        assert!(paths("gitlab", None, true).is_empty());
+        assert_eq!(paths("git", None, true), vec!["github"]);
+        assert_eq!(paths("github", Some("work"), true), vec!["work/github"]);
+        assert!(paths("gitlab", Some("home"), true).is_empty());
+    }
+
+    #[test]
+    fn test_pick_entry() {
+        use rbw::config::MultipleMatches;
+
+        let entry = |id: &str, revision_date: Option<&str>| {
+            let (mut entry, decrypted) = make_entry(id, None, None);
+            entry.id = id.to_string();
+            entry.revision_date = revision_date.map(str::to_string);
+            (entry, decrypted)
+        };
+        let matches = vec![
+            entry("old", Some("2023-01-01T00:00:00.123Z")),
+            entry("new", Some("2024-06-01T12:00:00Z")),
+            entry("none", None),
+            entry("also new", Some("2024-06-01T12:00:00.000Z")),
+        ];
+        let pick = |on_multiple_matches| {
+            pick_entry(matches.clone(), on_multiple_matches)
+                .map(|(entry, _)| entry.id)
+        };
+        assert!(pick(MultipleMatches::Error).is_err());
+        assert_eq!(pick(MultipleMatches::First).unwrap(), "old");
+        assert_eq!(pick(MultipleMatches::Newest).unwrap(), "new");
+        assert!(pick_entry(vec![], MultipleMatches::First).is_err());
+
+        // only the exact matches should be considered, even though the
+        // substring match is newer than both of them
+        let entries = &[
+            entry("github enterprise", Some("2025-01-01T00:00:00Z")),
+            entry("github", Some("2023-01-01T00:00:00Z")),
+            entry("github", Some("2024-01-01T00:00:00Z")),
+        ];
+        let pick = |on_multiple_matches| {
+            pick_entry(
+                find_entries_raw(
+                    entries,
+                    "github",
+                    None,
+                    None,
+                    false,
+                    false,
+                    None,
+                    &[],
+                ),
+                on_multiple_matches,
+            )
+            .map(|(entry, _)| entry.revision_date.unwrap())
+        };
+        assert!(pick(MultipleMatches::Error).is_err());
+        assert_eq!(
+            pick(MultipleMatches::First).unwrap(),
+            "2023-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            pick(MultipleMatches::Newest).unwrap(),
+            "2024-01-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_short_value_or_failed() {
+        let (_, mut decrypted) = make_entry("github", Some("foo"), None);
+        assert_eq!(decrypted.short_value_or_failed(), "");
+        decrypted.failed_fields = vec!["password".to_string()];
+        assert_eq!(decrypted.short_value_or_failed(), DECRYPT_FAILED);
+        if let DecryptedData::Login { password, .. } = &mut decrypted.data {
+            *password = Some("hunter2".to_string());
+        }
+        assert_eq!(decrypted.short_value_or_failed(), "hunter2");
+    }
+
+    #[test]
+    fn test_find_entries_narrowest_pass() {
+        let entries = &[
+            make_entry("github", Some("foo"), None),
+            make_entry("github", Some("bar"), None),
+            make_entry("github enterprise", Some("foo"), None),
+            make_entry("github", Some("baz"), Some("work")),
+        ];
+        let names = |name: &str, folder: Option<&str>| {
+            find_entries_raw(
+                entries,
+                name,
+                None,
+                folder,
+                false,
+                false,
+                None,
+                &[],
+            )
+            .into_iter()
+            .map(|(_, decrypted)| decrypted.display_name())
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("github", None), vec!["foo@github", "bar@github"]);
+        assert_eq!(names("github", Some("work")), vec!["baz@github"]);
+        assert_eq!(names("enterprise", None), vec!["foo@github enterprise"]);
+        assert_eq!(
+            names("hub", None),
+            vec!["foo@github", "bar@github", "foo@github enterprise",]
+        );
+    }
+
+    #[test]
+    fn test_find_entry_error() {
+        let entries = &[
+            make_entry("aws", Some("prod"), None),
+            make_entry("aws", Some("staging"), None),
+        ];
+        let error = |name: &str| {
+            find_entry_raw(entries, name, None, None, false)
+                .context("couldn't find entry")
+                .unwrap_err()
+        };
+
+        assert!(matches!(
+            error("azure").downcast_ref::<FindEntryError>(),
+            Some(FindEntryError::NotFound)
+        ));
+        let e = error("aws");
+        assert!(matches!(
+            e.downcast_ref::<FindEntryError>(),
+            Some(FindEntryError::MultipleFound { entries })
+                if entries == &["prod@aws", "staging@aws"]
+        ));
+        assert_eq!(
+            format!("{e:#}"),
+            "couldn't find entry: multiple entries found: prod@aws, \
+            staging@aws"
+        );
+
+        assert_eq!(
+            FindEntryError::NeverMatched {
+                entries: vec!["example".to_string()]
+            }
+            .to_string(),
+            "no entry found (entry 'example' has this uri, but match type \
+            is Never)"
+        );
+    }
+
+    #[test]
+    fn test_find_entry_by_url_domain() {
+        let mut entries = vec![
+            make_entry("example", None, None),
+            make_entry("pages", None, None),
+        ];
+        let set_uri = |entry: &mut (rbw::db::Entry, DecryptedCipher), uri| {
+            if let DecryptedData::Login { uris, .. } = &mut entry.1.data {
+                *uris = Some(vec![DecryptedUri {
+                    uri: String::from(uri),
+                    match_type: Some(rbw::api::UriMatchType::Domain),
+                }]);
+            }
+        };
+        set_uri(&mut entries[0], "https://www.example.co.uk/login");
+        set_uri(&mut entries[1], "https://foo.github.io");
+        let found = |url: &str| {
+            find_entry_raw(&entries, url, None, None, false)
+                .ok()
+                .map(|(_, decrypted)| decrypted.name)
+        };
+
+        assert_eq!(found("https://example.co.uk/").unwrap(), "example");
+        assert_eq!(
+            found("https://accounts.example.co.uk/").unwrap(),
+            "example"
+        );
+        assert!(found("https://other.co.uk/").is_none());
+        assert_eq!(found("https://foo.github.io/app").unwrap(), "pages");
+        assert!(found("https://bar.github.io/").is_none());
+    }
+
+    #[test]
+    fn test_never_matches_url() {
+        let (_, mut entry) = make_entry("example", None, None);
+        let url = url::Url::parse("https://www.example.com/login").unwrap();
+        let set_uri = |entry: &mut DecryptedCipher, uri: &str, ty| {
+            if let DecryptedData::Login { uris, .. } = &mut entry.data {
+                *uris = Some(vec![DecryptedUri {
+                    uri: uri.to_string(),
+                    match_type: Some(ty),
+                }]);
+            }
+        };
+
+        set_uri(&mut entry, "example.com", rbw::api::UriMatchType::Never);
+        assert!(!entry.matches_url(&url, None, None, &[]));
+        assert!(entry.never_matches_url(&url, &[]));
+        set_uri(&mut entry, "other.com", rbw::api::UriMatchType::Never);
+        assert!(!entry.never_matches_url(&url, &[]));
+        set_uri(&mut entry, "example.com", rbw::api::UriMatchType::Domain);
+        assert!(!entry.never_matches_url(&url, &[]));
+    }
+
+    #[test]
+    fn test_find_entry_by_url_in_notes() {
+        let mut entries = vec![
+            make_entry("router", None, None),
+            make_entry("other", None, None),
+        ];
+        entries[0].1.data = DecryptedData::SecureNote;
+        entries[0].1.notes =
+            Some("admin page at https://192.168.1.1/admin".to_string());
+        entries[1].1.fields = vec![DecryptedField {
+            ty: None,
+            name: Some("url".to_string()),
+            value: Some("https://nas.example.com".to_string()),
+        }];
+
+        assert!(find_entry_raw(
+            &entries,
+            "https://192.168.1.1/admin",
+            None,
+            None,
+            false
+        )
+        .is_err());
+        let found = find_entry_raw(
+            &entries,
+            "https://192.168.1.1/admin",
+            None,
+            None,
+            true,
+        )
+        .unwrap();
+        assert_eq!(found.1.name, "router");
+        let found = find_entry_raw(
+            &entries,
+            "https://nas.example.com/login",
+            None,
+            None,
+            true,
+        )
+        .unwrap();
+        assert_eq!(found.1.name, "other");
+    }
+
+    #[test]
+    fn test_list_field_presets() {
+        fn parse(fields: &[&str]) -> anyhow::Result<Vec<ListField>> {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(std::string::ToString::to_string)
+                .collect();
+            ListField::parse_list(&fields)
+        }
+        assert_eq!(
+            parse(&["all"]).unwrap(),
+            vec![
+                ListField::Id,
+                ListField::Name,
+                ListField::User,
+                ListField::Folder,
+                ListField::Uri,
+                ListField::Totp,
+                ListField::LastUsed
+            ]
+        );
+        assert_eq!(parse(&["default"]).unwrap(), vec![ListField::Name]);
+        assert_eq!(
+            parse(&["default", "user"]).unwrap(),
+            vec![ListField::Name, ListField::User]
+        );
+        assert!(parse(&["bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_totp_to_uri() {
+        let params = parse_totp_secret("JBSWY3DPEHPK3PXP").unwrap();
+        let uri = params.to_uri("My Site", Some("me@example.com"));
+        assert_eq!(
+            uri,
+            "otpauth://totp/My%20Site:me%40example%2Ecom\
+            ?secret=JBSWY3DPEHPK3PXP&issuer=My%20Site&algorithm=SHA1\
+            &digits=6&period=30"
+        );
+        let reparsed = parse_totp_secret(&uri).unwrap();
+        assert_eq!(reparsed.key, params.key);
+        assert_eq!(reparsed.issuer.as_deref(), Some("My Site"));
+        assert_eq!(reparsed.account.as_deref(), Some("me@example.com"));
+
+        let params = parse_totp_secret(
+            "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP\
+            &algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+        let reparsed =
+            parse_totp_secret(&params.to_uri("github", None)).unwrap();
+        assert_eq!(reparsed.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(reparsed.account.as_deref(), Some("alice"));
+        assert_eq!(reparsed.algorithm, TotpAlgorithm::Sha256);
+        assert_eq!(reparsed.digits, 8);
+        assert_eq!(reparsed.period, 60);
+
+        let params =
+            parse_totp_secret("steam://ON2XAZLSMR2XAZLSONSWG4TFOQ").unwrap();
+        let reparsed =
+            parse_totp_secret(&params.to_uri("Steam", None)).unwrap();
+        assert!(reparsed.steam);
+        assert_eq!(reparsed.code_at(3_000_030), "YRGQJ");
+    }
+
+    #[test]
+    fn test_qr_code() {
+        let code =
+            qr_code("otpauth://totp/x?secret=JBSWY3DPEHPK3PXP").unwrap();
+        let lines: Vec<_> = code.lines().collect();
+        assert!(lines.len() > 10);
+        let width = lines[0].chars().count();
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        assert!(code.contains('\u{2580}') || code.contains('\u{2584}'));
+    }
+
+    #[test]
+    fn test_totp_raw_at() {
+        let params = parse_totp_secret("JBSWY3DPEHPK3PXP").unwrap();
+        let code = params.raw_at(1_000_010);
+        assert_eq!(code.code, params.code_at(1_000_010));
+        assert_eq!(code.digits, 6);
+        assert_eq!(code.period, 30);
+        assert_eq!(code.expires_at, 1_000_020);
+        assert_eq!(params.raw_at(1_000_020).expires_at, 1_000_050);
+
+        let params =
+            parse_totp_secret("steam://ON2XAZLSMR2XAZLSONSWG4TFOQ").unwrap();
+        assert_eq!(params.raw_at(3_000_030).digits, 5);
+    }
+
+    #[test]
+    fn test_steam_totp() {
+        let params =
+            parse_totp_secret("steam://ON2XAZLSMR2XAZLSONSWG4TFOQ").unwrap();
+        assert!(params.steam);
+        assert_eq!(params.key, b"superdupersecret");
+        assert_eq!(params.code_at(3_000_030), "YRGQJ");
+        assert_eq!(params.code_at(3_000_029), "94R9D");
+
+        assert!(parse_totp_secret("steam://not base32!").is_err());
+
+        let params = parse_totp_secret(
+            "otpauth://totp/Steam:me?secret=ON2XAZLSMR2XAZLSONSWG4TFOQ\
+            &digits=5&encoder=steam",
+        )
+        .unwrap();
+        assert!(params.steam);
+        assert_eq!(params.code_at(3_000_030), "YRGQJ");
+        assert!(parse_totp_secret(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&encoder=other"
+        )
+        .is_err());
+        assert!(parse_totp_secret(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=5"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_totp_secret() {
+        let params = parse_totp_secret("JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(params.algorithm, TotpAlgorithm::Sha1);
+        assert_eq!(params.digits, 6);
+        assert_eq!(params.period, 30);
+
+        let params = parse_totp_secret(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256\
+            &digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(params.algorithm, TotpAlgorithm::Sha256);
+        assert_eq!(params.digits, 8);
+        assert_eq!(params.period, 60);
+
+        let overridden = params
+            .apply(&TotpOverrides {
+                digits: Some(7),
+                period: None,
+                algorithm: Some(TotpAlgorithm::Sha512),
+            })
+            .unwrap();
+        assert_eq!(overridden.algorithm, TotpAlgorithm::Sha512);
+        assert_eq!(overridden.digits, 7);
+        assert_eq!(overridden.period, 60);
+
+        assert!(parse_totp_secret(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=12"
+        )
+        .is_err());
+        assert!(parse_totp_secret(
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&algorithm=md5"
+        )
+        .is_err());
+        let params = parse_totp_secret(
+            "otpauth://totp/ACME%20Co:john:doe@example.com\
+            ?secret=JBSWY3DPEHPK3PXP",
+        )
+        .unwrap();
+        assert_eq!(params.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(params.account.as_deref(), Some("john:doe@example.com"));
+        assert_eq!(params.code_at(0).len(), 6);
+        let params = parse_totp_secret(
+            "otpauth://totp/ACME Co: john?secret=JBSWY3DPEHPK3PXP\
+            &issuer=ACME",
+        )
+        .unwrap();
+        assert_eq!(params.issuer.as_deref(), Some("ACME"));
+        assert_eq!(params.account.as_deref(), Some("john"));
+        let params =
+            parse_totp_secret("otpauth://totp/john?secret=JBSWY3DPEHPK3PXP")
+                .unwrap();
+        assert_eq!(params.issuer, None);
+        assert_eq!(params.account.as_deref(), Some("john"));
+
+        // rfc 6238 test vectors
+        let params = TotpParams {
+            key: b"12345678901234567890".to_vec(),
+            algorithm: TotpAlgorithm::Sha1,
+            digits: 8,
+            period: 30,
+            issuer: None,
+            account: None,
+            steam: false,
+        };
+        assert_eq!(params.code_at(59), "94287082");
+        assert_eq!(params.code_at(59 + 30), params.code_at(89));
+        assert_ne!(params.code_at(59), params.code_at(89));
+
+        assert!(parse_totp_secret("JBSWY3DPEHPK3PXP")
+            .unwrap()
+            .apply(&TotpOverrides {
+                period: Some(0),
+                ..TotpOverrides::default()
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_serialize_boolean_field() {
+        let field = |ty, value: &str| DecryptedField {
+            ty,
+            name: Some("flag".to_string()),
+            value: Some(value.to_string()),
+        };
+        let boolean = Some(rbw::api::FieldType::Boolean);
+        assert_eq!(
+            serde_json::to_value(field(boolean, "true")).unwrap(),
+            serde_json::json!({"name": "flag", "value": true})
+        );
+        assert_eq!(
+            serde_json::to_value(field(boolean, "false")).unwrap(),
+            serde_json::json!({"name": "flag", "value": false})
+        );
+        assert_eq!(
+            serde_json::to_value(field(boolean, "maybe")).unwrap(),
+            serde_json::json!({"name": "flag", "value": "maybe"})
+        );
+        assert_eq!(
+            serde_json::to_value(field(
+                Some(rbw::api::FieldType::Text),
+                "true"
+            ))
+            .unwrap(),
+            serde_json::json!({"name": "flag", "value": "true"})
+        );
+    }
+
+    #[test]
+    fn test_header() {
+        let (_, decrypted) = make_entry("github", Some("foo"), Some("dev"));
+        assert_eq!(decrypted.header(), "=== dev/foo@github (irrelevant) ===");
+        let (_, decrypted) = make_entry("github", None, None);
+        assert_eq!(decrypted.header(), "=== github (irrelevant) ===");
+    }
+
+    #[test]
+    fn test_best_search_match() {
+        let entries = |names: &[&str]| -> Vec<DecryptedCipher> {
+            names
+                .iter()
+                .map(|name| make_entry(name, Some("git"), None).1)
+                .collect()
+        };
+        let best = |names: &[&str], term: &str| {
+            best_search_match(entries(names), term).map(|cipher| cipher.name)
+        };
+
+        assert_eq!(
+            best(&["my github", "github.com", "GitHub"], "github"),
+            Some("GitHub".to_string())
+        );
+        assert_eq!(
+            best(&["my github", "github.com"], "github"),
+            Some("github.com".to_string())
+        );
+        assert_eq!(
+            best(&["foo", "my github"], "github"),
+            Some("my github".to_string())
+        );
+        assert_eq!(best(&["foo", "bar"], "git"), Some("bar".to_string()));
+        assert_eq!(best(&[], "github"), None);
+    }
+
+    #[test]
+    fn test_search_match() {
+        let (_, mut decrypted) =
+            make_entry("work", Some("Alice"), Some("dev"));
+        decrypted.data = DecryptedData::Login {
+            username: Some("Alice".to_string()),
+            password: None,
+            totp: None,
+            uris: Some(vec![DecryptedUri {
+                uri: "https://login.Example.com/".to_string(),
+                match_type: None,
+            }]),
+        };
+        decrypted.fields = vec![DecryptedField {
+            ty: None,
+            name: Some("Recovery Code".to_string()),
+            value: Some("1234".to_string()),
+        }];
+
+        assert!(decrypted.search_match("example", None, None));
+        assert!(decrypted.search_match("recovery", None, None));
+        assert!(decrypted.search_match("1234", None, None));
+        assert!(decrypted.search_match("alice", Some("dev"), None));
+        assert!(!decrypted.search_match("alice", Some("other"), None));
+        assert!(!decrypted.search_match("nothing", None, None));
+
+        assert!(decrypted.search_match(
+            "example",
+            None,
+            Some(SearchField::Uri)
+        ));
+        assert!(!decrypted.search_match(
+            "example",
+            None,
+            Some(SearchField::Name)
+        ));
+        assert!(decrypted.search_match(
+            "recovery",
+            None,
+            Some(SearchField::Field)
+        ));
+        assert!(!decrypted.search_match(
+            "work",
+            None,
+            Some(SearchField::Username)
+        ));
+    }
+
+    #[test]
+    fn test_color_enabled() {
+        assert!(color_enabled(None, true));
+        assert!(color_enabled(Some(std::ffi::OsStr::new("")), true));
+        assert!(!color_enabled(Some(std::ffi::OsStr::new("1")), true));
+        assert!(!color_enabled(None, false));
+    }
+
+    #[test]
+    fn test_resolve_field_alias() {
+        let aliases = std::collections::BTreeMap::from([
+            ("otp".to_string(), "one-time-password".to_string()),
+            ("login".to_string(), "username".to_string()),
+        ]);
+        assert_eq!(resolve_field_alias(&aliases, "otp"), "one-time-password");
+        assert_eq!(resolve_field_alias(&aliases, "OTP"), "one-time-password");
+        assert_eq!(resolve_field_alias(&aliases, "login"), "username");
+        assert_eq!(resolve_field_alias(&aliases, "password"), "password");
+        assert_eq!(
+            resolve_field_alias(&std::collections::BTreeMap::new(), "otp"),
+            "otp"
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        let mut out = vec![];
+        write_csv_row(&mut out, &["github", "", "a,b"]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "github,,\"a,b\"\n");
+    }
+
+    #[test]
+    fn test_porcelain_line() {
+        assert_eq!(porcelain_line(&["a", "b", ""]), "a\tb\t");
+        assert_eq!(
+            porcelain_line(&["tab\there", "new\nline", "back\\slash"]),
+            "tab\\there\tnew\\nline\tback\\\\slash"
+        );
+    }
+
+    #[test]
+    fn test_porcelain_lines() {
+        let (_, mut decrypted) =
+            make_entry("github", Some("foo"), Some("dev"));
+        decrypted.data = DecryptedData::Login {
+            username: Some("foo".to_string()),
+            password: Some("hunter2".to_string()),
+            totp: None,
+            uris: Some(vec![
+                DecryptedUri {
+                    uri: "https://github.com".to_string(),
+                    match_type: None,
+                },
+                DecryptedUri {
+                    uri: "https://gist.github.com".to_string(),
+                    match_type: None,
+                },
+            ]),
+        };
+        decrypted.fields = vec![DecryptedField {
+            ty: None,
+            name: Some("pin".to_string()),
+            value: Some("1234".to_string()),
+        }];
+        decrypted.notes = Some("line one\nline two".to_string());
+        assert_eq!(
+            decrypted.porcelain_lines(),
+            vec![
+                "id\tirrelevant",
+                "name\tgithub",
+                "folder\tdev",
+                "type\tlogin",
+                "username\tfoo",
+                "password\thunter2",
+                "uri\thttps://github.com",
+                "uri\thttps://gist.github.com",
+                "notes\tline one\\nline two",
+                "field\tpin\t1234",
+            ]
+        );
+
+        let (_, mut decrypted) = make_entry("note", None, None);
+        decrypted.data = DecryptedData::SecureNote;
+        assert_eq!(
+            decrypted.porcelain_lines(),
+            vec!["id\tirrelevant", "name\tnote", "type\tnote"]
+        );
+    }
+
+    #[test]
+    fn test_is_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("pinentry-test");
+        assert!(!is_executable(program.to_str().unwrap()));
+
+        std::fs::write(&program, "#!/bin/sh\n").unwrap();
+        assert!(!is_executable(program.to_str().unwrap()));
+
+        let mut permissions =
+            std::fs::metadata(&program).unwrap().permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o755);
+        std::fs::set_permissions(&program, permissions).unwrap();
+        assert!(is_executable(program.to_str().unwrap()));
+
+        assert!(!is_executable(dir.path().to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_no_sync_updates_local_db() {
+        let mut config = rbw::config::Config::new();
+        assert!(syncs_after_write(false, &config));
+        assert!(!syncs_after_write(true, &config));
+        config.sync_after_write = false;
+        assert!(!syncs_after_write(false, &config));
+
+        let (mut entry, _) = make_entry("github", None, None);
+        let mut db = rbw::db::Db::new();
+        db.upsert_entry(entry.clone());
+        entry.name = "this is the new encrypted name".to_string();
+        apply_local_update(&mut db, |db| db.upsert_entry(entry.clone()));
+        assert_eq!(db.entries, vec![entry]);
+        assert_eq!(
+            db.pending["irrelevant"].name,
+            "this is the new encrypted name"
+        );
+        assert!(!db.pending["irrelevant"].removed);
+
+        apply_local_update(&mut db, |db| db.remove_entry("irrelevant"));
+        assert!(db.entries.is_empty());
+        assert!(db.pending["irrelevant"].removed);
+    }
+
+    #[test]
+    fn test_sync_or_apply_update() {
+        let (entry, _) = make_entry("github", None, None);
+        let mut renamed = entry.clone();
+        renamed.name = "this is the new encrypted name".to_string();
+        let mut config = rbw::config::Config::new();
+
+        // --no-sync
+        let mut db = rbw::db::Db::new();
+        db.upsert_entry(entry.clone());
+        let synced = std::cell::Cell::new(false);
+        let res = sync_or_apply_update(
+            &mut db,
+            syncs_after_write(true, &config),
+            |db| db.upsert_entry(renamed.clone()),
+            || {
+                synced.set(true);
+                Ok(())
+            },
+        );
+        assert!(!res.unwrap());
+        assert!(!synced.get());
+        assert_eq!(db.entries, vec![renamed.clone()]);
+        assert!(db.pending.contains_key("irrelevant"));
+
+        // sync_after_write = false
+        config.sync_after_write = false;
+        let mut db = rbw::db::Db::new();
+        db.upsert_entry(entry.clone());
+        let res = sync_or_apply_update(
+            &mut db,
+            syncs_after_write(false, &config),
+            |db| db.upsert_entry(renamed.clone()),
+            || {
+                synced.set(true);
+                Ok(())
+            },
+        );
+        assert!(!res.unwrap());
+        assert!(!synced.get());
+        assert_eq!(db.entries, vec![renamed.clone()]);
+
+        // syncing replaces the local copy, so the update isn't applied
+        config.sync_after_write = true;
+        let mut db = rbw::db::Db::new();
+        db.upsert_entry(entry.clone());
+        let res = sync_or_apply_update(
+            &mut db,
+            syncs_after_write(false, &config),
+            |db| db.upsert_entry(renamed.clone()),
+            || {
+                synced.set(true);
+                Ok(())
+            },
+        );
+        assert!(res.unwrap());
+        assert!(synced.get());
+        assert_eq!(db.entries, vec![entry.clone()]);
+        assert!(db.pending.is_empty());
+
+        // a failed sync still updates the local copy
+        let mut db = rbw::db::Db::new();
+        db.upsert_entry(entry);
+        let res = sync_or_apply_update(
+            &mut db,
+            syncs_after_write(false, &config),
+            |db| db.upsert_entry(renamed.clone()),
+            || Err(anyhow::anyhow!("server unreachable")),
+        );
+        assert_eq!(res.unwrap_err().to_string(), "server unreachable");
+        assert_eq!(db.entries, vec![renamed]);
+        assert!(db.pending.contains_key("irrelevant"));
+    }
+
+    #[test]
+    fn test_sort_list_results() {
+        let mut ciphers: Vec<DecryptedCipher> = [
+            ("github", Some("bob"), Some("work")),
+            ("github", Some("alice"), None),
+            ("gitlab", None, Some("personal")),
+            ("aws", Some("carol"), Some("work")),
+        ]
+        .iter()
+        .map(|(name, user, folder)| make_entry(name, *user, *folder).1)
+        .collect();
+        let order = |ciphers: &[DecryptedCipher]| {
+            ciphers
+                .iter()
+                .map(DecryptedCipher::path)
+                .collect::<Vec<_>>()
+        };
+
+        let no_usage = std::collections::HashMap::new();
+        sort_list_results(&mut ciphers, ListSort::Name, false, &no_usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "work/carol@aws",
+                "alice@github",
+                "work/bob@github",
+                "personal/gitlab"
+            ]
+        );
+        sort_list_results(&mut ciphers, ListSort::Folder, false, &no_usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "alice@github",
+                "personal/gitlab",
+                "work/carol@aws",
+                "work/bob@github"
+            ]
+        );
+        sort_list_results(&mut ciphers, ListSort::Folder, true, &no_usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "work/bob@github",
+                "work/carol@aws",
+                "personal/gitlab",
+                "alice@github"
+            ]
+        );
+        sort_list_results(&mut ciphers, ListSort::User, false, &no_usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "personal/gitlab",
+                "alice@github",
+                "work/bob@github",
+                "work/carol@aws"
+            ]
+        );
 
-    let password = lines.next().map(std::string::ToString::to_string);
+        for cipher in &mut ciphers {
+            cipher.revision_date = match cipher.name.as_str() {
+                "aws" => Some("2024-01-01T00:00:00.5Z".to_string()),
+                "gitlab" => Some("2024-03-01T00:00:00Z".to_string()),
+                _ => None,
+            };
+        }
+        sort_list_results(&mut ciphers, ListSort::Modified, false, &no_usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "personal/gitlab",
+                "work/carol@aws",
+                "alice@github",
+                "work/bob@github"
+            ]
+        );
 
-    let mut notes: String = lines
-        .skip_while(|line| line.is_empty())
-        .filter(|line| !line.starts_with('#'))
-        .map(|line| format!("{line}\n"))
+        for cipher in &mut ciphers {
+            cipher.id = cipher.path();
+        }
+        let usage = [
+            ("alice@github", "2024-05-01T00:00:00Z"),
+            ("work/carol@aws", "2024-06-01T00:00:00Z"),
+        ]
+        .iter()
+        .map(|(id, date)| ((*id).to_string(), (*date).to_string()))
         .collect();
-    while notes.ends_with('\n') {
-        notes.pop();
+        sort_list_results(&mut ciphers, ListSort::LastUsed, false, &usage);
+        assert_eq!(
+            order(&ciphers),
+            vec![
+                "work/carol@aws",
+                "alice@github",
+                "work/bob@github",
+                "personal/gitlab"
+            ]
+        );
     }
-    let notes = if notes.is_empty() { None } else { Some(notes) };
 
-    (password, notes)
-}
+    #[test]
+    fn test_sort_search_results() {
+        let mut found: Vec<DecryptedCipher> = [
+            ("my github", Some("work")),
+            ("github", Some("work")),
+            ("github", None),
+            ("gitlab", Some("personal")),
+        ]
+        .iter()
+        .map(|(name, folder)| make_entry(name, None, *folder).1)
+        .collect();
+        let order = |found: &[DecryptedCipher]| {
+            found.iter().map(DecryptedCipher::path).collect::<Vec<_>>()
+        };
+
+        sort_search_results(&mut found, SearchSort::Folder, "github");
+        assert_eq!(
+            order(&found),
+            vec![
+                "github",
+                "personal/gitlab",
+                "work/github",
+                "work/my github"
+            ]
+        );
+        sort_search_results(&mut found, SearchSort::Name, "github");
+        assert_eq!(
+            order(&found),
+            vec![
+                "github",
+                "work/github",
+                "personal/gitlab",
+                "work/my github"
+            ]
+        );
+        sort_search_results(&mut found, SearchSort::Relevance, "github");
+        assert_eq!(
+            order(&found),
+            vec![
+                "github",
+                "work/github",
+                "work/my github",
+                "personal/gitlab"
+            ]
+        );
+    }
 
-fn load_db() -> anyhow::Result<rbw::db::Db> {
-    let config = rbw::config::Config::load()?;
-    config.email.as_ref().map_or_else(
-        || Err(anyhow::anyhow!("failed to find email address in config")),
-        |email| {
-            rbw::db::Db::load(&config.server_name(), email)
-                .map_err(anyhow::Error::new)
-        },
-    )
-}
+    #[test]
+    fn test_display_password_field_of_non_login() {
+        let (_, mut decrypted) = make_entry("note", None, None);
+        decrypted.data = DecryptedData::SecureNote;
+        decrypted.fields = vec![DecryptedField {
+            ty: None,
+            name: Some("wifi password".to_string()),
+            value: Some("hunter2".to_string()),
+        }];
+        let display = |decrypted: &DecryptedCipher| {
+            decrypted.display_field(
+                "note",
+                "password",
+                Clipboard::No,
+                &(0..=0),
+            )
+        };
+        assert_eq!(
+            display(&decrypted).unwrap_err().to_string(),
+            "secure note entries have no password"
+        );
 
-fn save_db(db: &rbw::db::Db) -> anyhow::Result<()> {
-    let config = rbw::config::Config::load()?;
-    config.email.as_ref().map_or_else(
-        || Err(anyhow::anyhow!("failed to find email address in config")),
-        |email| {
-            db.save(&config.server_name(), email)
-                .map_err(anyhow::Error::new)
-        },
-    )
-}
+        decrypted.fields[0].name = Some("Password".to_string());
+        assert!(display(&decrypted).is_ok());
+    }
 
-fn remove_db() -> anyhow::Result<()> {
-    let config = rbw::config::Config::load()?;
-    config.email.as_ref().map_or_else(
-        || Err(anyhow::anyhow!("failed to find email address in config")),
-        |email| {
-            rbw::db::Db::remove(&config.server_name(), email)
-                .map_err(anyhow::Error::new)
-        },
-    )
-}
+    #[test]
+    fn test_display_missing_field() {
+        let (_, decrypted) = make_entry("login", None, None);
+        assert_eq!(
+            decrypted
+                .display_field("login", "cvv", Clipboard::No, &(0..=0))
+                .unwrap_err()
+                .to_string(),
+            "entry 'login' has no field 'cvv'"
+        );
+        assert_eq!(
+            decrypted.short_value("login").unwrap_err().to_string(),
+            "entry for 'login' had no password"
+        );
+    }
 
-fn parse_totp_secret(secret: &str) -> anyhow::Result<Vec<u8>> {
-    let secret_str = if let Ok(u) = url::Url::parse(secret) {
-        if u.scheme() != "otpauth" {
-            return Err(anyhow::anyhow!(
-                "totp secret url must have otpauth scheme"
-            ));
-        }
-        if u.host_str() != Some("totp") {
-            return Err(anyhow::anyhow!(
-                "totp secret url must have totp host"
-            ));
+    #[test]
+    fn test_notes_field() {
+        let (_, mut decrypted) = make_entry("entry", None, None);
+        let notes = |decrypted: &DecryptedCipher, field: &str| {
+            decrypted.field_value("entry", field, &(0..=0)).unwrap()
+        };
+        for data in [
+            decrypted.data.clone(),
+            DecryptedData::SecureNote,
+            DecryptedData::Card {
+                cardholder_name: None,
+                number: None,
+                brand: None,
+                exp_month: None,
+                exp_year: None,
+                code: None,
+            },
+        ] {
+            decrypted.data = data;
+            decrypted.notes = None;
+            assert_eq!(notes(&decrypted, "notes"), None);
+            assert!(decrypted
+                .display_field("entry", "notes", Clipboard::No, &(0..=0))
+                .is_err());
+            decrypted.notes = Some("some notes".to_string());
+            assert_eq!(
+                notes(&decrypted, "notes").as_deref(),
+                Some("some notes")
+            );
+            assert_eq!(
+                notes(&decrypted, "Note").as_deref(),
+                Some("some notes")
+            );
         }
-        let query: std::collections::HashMap<_, _> =
-            u.query_pairs().collect();
-        query
-            .get("secret")
-            .ok_or_else(|| {
-                anyhow::anyhow!("totp secret url must have secret")
-            })?
-            .to_string()
-    } else {
-        secret.to_string()
-    };
-    base32::decode(
-        base32::Alphabet::RFC4648 { padding: false },
-        &secret_str.replace(' ', ""),
-    )
-    .ok_or_else(|| anyhow::anyhow!("totp secret was not valid base32"))
-}
-
-fn generate_totp(secret: &str) -> anyhow::Result<String> {
-    let key = parse_totp_secret(secret)?;
-    Ok(totp_lite::totp_custom::<totp_lite::Sha1>(
-        totp_lite::DEFAULT_STEP,
-        6,
-        &key,
-        std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)?
-            .as_secs(),
-    ))
-}
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn test_fields_text() {
+        let (_, mut decrypted) = make_entry("site", Some("alice"), None);
+        decrypted.notes = Some("some notes".to_string());
+        let fields = ["username".to_string(), "notes".to_string()];
+        assert_eq!(
+            decrypted
+                .fields_text("site", &fields, true, &(0..=0))
+                .unwrap(),
+            "username: alice\nnotes: some notes"
+        );
+        assert_eq!(
+            decrypted
+                .fields_text("site", &fields, false, &(0..=0))
+                .unwrap(),
+            "alice\nsome notes"
+        );
+
+        decrypted.notes = None;
+        assert_eq!(
+            decrypted
+                .fields_text("site", &fields, false, &(0..=0))
+                .unwrap(),
+            "alice\n"
+        );
+
+        // a missing password is blank rather than an error, but one which
+        // couldn't be decrypted still fails
+        let fields = ["password".to_string(), "username".to_string()];
+        assert_eq!(
+            decrypted
+                .fields_text("site", &fields, true, &(0..=0))
+                .unwrap(),
+            "password: \nusername: alice"
+        );
+        assert_eq!(
+            decrypted
+                .optional_field_value("site", "password", &(0..=0))
+                .unwrap(),
+            None
+        );
+        decrypted.failed_fields = vec!["password".to_string()];
+        assert!(decrypted
+            .fields_text("site", &fields, true, &(0..=0))
+            .is_err());
+    }
 
     #[test]
-    fn test_find_entry() {
-        let entries = &[
-            make_entry("github", Some("foo"), None),
-            make_entry("gitlab", Some("foo"), None),
-            make_entry("gitlab", Some("bar"), None),
-            make_entry("gitter", Some("baz"), None),
-            make_entry("git", Some("foo"), None),
-            make_entry("bitwarden", None, None),
-            make_entry("github", Some("foo"), Some("websites")),
-            make_entry("github", Some("foo"), Some("ssh")),
-            make_entry("github", Some("root"), Some("ssh")),
+    fn test_exact_custom_field_value() {
+        let (_, mut decrypted) = make_entry("api", None, None);
+        let field = |name: &str, value: &str| DecryptedField {
+            ty: None,
+            name: Some(name.to_string()),
+            value: Some(value.to_string()),
+        };
+        decrypted.fields = vec![
+            field("refresh_token", "refresh"),
+            field("Token", "access"),
+            field("key", "first"),
+            field("KEY", "second"),
+        ];
+        assert_eq!(
+            decrypted.exact_custom_field_value("api", "token").unwrap(),
+            "access"
+        );
+        assert!(decrypted.exact_custom_field_value("api", "tok").is_err());
+        assert!(decrypted.exact_custom_field_value("api", "key").is_err());
+    }
+
+    #[test]
+    fn test_field_list_lines() {
+        let (_, mut decrypted) = make_entry("api", None, None);
+        decrypted.fields = vec![
+            DecryptedField {
+                ty: Some(rbw::api::FieldType::Hidden),
+                name: Some("token".to_string()),
+                value: Some("secret".to_string()),
+            },
+            DecryptedField {
+                ty: None,
+                name: Some("region".to_string()),
+                value: Some("eu".to_string()),
+            },
+            DecryptedField {
+                ty: Some(rbw::api::FieldType::Text),
+                name: None,
+                value: Some("unnamed".to_string()),
+            },
         ];
+        assert_eq!(
+            decrypted.field_list_lines(false),
+            vec!["token (hidden)", "region"]
+        );
+        assert_eq!(
+            decrypted.field_list_lines(true),
+            vec!["token\thidden", "region\t"]
+        );
+    }
 
-        assert!(
-            one_match(entries, "github", Some("foo"), None, 0),
-            "foo@github"
+    #[test]
+    fn test_ssh_key_fields() {
+        let (_, mut decrypted) = make_entry("server", None, None);
+        decrypted.data = DecryptedData::SshKey {
+            private_key: Some("private".to_string()),
+            public_key: Some("ssh-ed25519 public".to_string()),
+            fingerprint: Some("SHA256:fingerprint".to_string()),
+        };
+        let field =
+            |field| decrypted.field_value("server", field, &(0..=0)).unwrap();
+        assert_eq!(field("private_key").as_deref(), Some("private"));
+        assert_eq!(
+            field("public_key").as_deref(),
+            Some("ssh-ed25519 public")
         );
-        assert!(one_match(entries, "github", None, None, 0), "github");
-        assert!(
-            one_match(entries, "gitlab", Some("foo"), None, 1),
-            "foo@gitlab"
+        assert_eq!(
+            field("fingerprint").as_deref(),
+            Some("SHA256:fingerprint")
         );
-        assert!(one_match(entries, "git", Some("bar"), None, 2), "bar@git");
-        assert!(
-            one_match(entries, "gitter", Some("ba"), None, 3),
-            "ba@gitter"
+        assert!(decrypted
+            .field_value("server", "password", &(0..=0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_uri_arg() {
+        use rbw::api::UriMatchType::{Host, RegularExpression, StartsWith};
+
+        assert_eq!(
+            parse_uri_arg("https://example.com").unwrap(),
+            ("https://example.com".to_string(), None)
         );
-        assert!(one_match(entries, "git", Some("foo"), None, 4), "foo@git");
-        assert!(one_match(entries, "git", None, None, 4), "git");
-        assert!(one_match(entries, "bitwarden", None, None, 5), "bitwarden");
-        assert!(
-            one_match(entries, "github", Some("foo"), Some("websites"), 6),
-            "websites/foo@github"
+        assert_eq!(
+            parse_uri_arg("https://example.com:8443").unwrap(),
+            ("https://example.com:8443".to_string(), None)
         );
-        assert!(
-            one_match(entries, "github", Some("foo"), Some("ssh"), 7),
-            "ssh/foo@github"
+        assert_eq!(
+            parse_uri_arg("https://example.com:host").unwrap(),
+            ("https://example.com".to_string(), Some(Host))
         );
-        assert!(
-            one_match(entries, "github", Some("root"), None, 8),
-            "ssh/root@github"
+        assert_eq!(
+            parse_uri_arg("https://example.com:8443/login:starts_with")
+                .unwrap(),
+            (
+                "https://example.com:8443/login".to_string(),
+                Some(StartsWith)
+            )
         );
+        assert_eq!(
+            parse_uri_arg(r"^https://(www\.)?example\.com/:regex").unwrap(),
+            (
+                r"^https://(www\.)?example\.com/".to_string(),
+                Some(RegularExpression)
+            )
+        );
+        assert!(parse_uri_arg("^https://(example.com:regex").is_err());
+        assert!(parse_uri_arg(":host").is_err());
 
-        assert!(
-            no_matches(entries, "gitlab", Some("baz"), None),
-            "baz@gitlab"
+        assert!(validate_uri("(", None).is_ok());
+        assert!(validate_uri("(", Some(RegularExpression)).is_err());
+    }
+
+    #[test]
+    fn test_parse_field_editor() {
+        let template = field_template(
+            &[("number", Some("4111")), ("brand", None), ("code", None)],
+            None,
         );
-        assert!(
-            no_matches(entries, "bitbucket", Some("foo"), None),
-            "foo@bitbucket"
+        assert_eq!(template, "number: 4111\nbrand: \ncode: \nnotes: \n");
+
+        let contents =
+            format!("{template}first line\n\nsecond line\n\n{FIELDS_HELP}");
+        let (values, notes) =
+            parse_field_editor(&contents, CARD_FIELDS).unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values["number"], "4111");
+        assert_eq!(notes.as_deref(), Some("first line\n\nsecond line"));
+
+        let (values, notes) =
+            parse_field_editor("\ncode: 123\n", CARD_FIELDS).unwrap();
+        assert_eq!(values["code"], "123");
+        assert_eq!(notes, None);
+
+        assert!(parse_field_editor("pin: 1234\n", CARD_FIELDS).is_err());
+        assert!(parse_field_editor("4111\n", CARD_FIELDS).is_err());
+    }
+
+    #[test]
+    fn test_keep_or_encrypt() {
+        let encrypted = "2.encrypted".to_string();
+        // unchanged values (including ones which failed to decrypt) don't
+        // need to be encrypted again
+        assert_eq!(
+            keep_or_encrypt(
+                Some("value".to_string()),
+                Some("value"),
+                Some(&encrypted),
+                None,
+            )
+            .unwrap(),
+            Some(encrypted.clone())
         );
-        assert!(
-            no_matches(entries, "github", Some("foo"), Some("bar")),
-            "bar/foo@github"
+        assert_eq!(
+            keep_or_encrypt(None, None, Some(&encrypted), None).unwrap(),
+            Some(encrypted.clone())
         );
-        assert!(
-            no_matches(entries, "gitlab", Some("foo"), Some("bar")),
-            "bar/foo@gitlab"
+        assert_eq!(
+            keep_or_encrypt(None, Some("value"), Some(&encrypted), None)
+                .unwrap(),
+            None
         );
+    }
 
-        assert!(many_matches(entries, "gitlab", None, None), "gitlab");
-        assert!(many_matches(entries, "gi", Some("foo"), None), "foo@gi");
-        assert!(many_matches(entries, "git", Some("ba"), None), "ba@git");
-        assert!(
-            many_matches(entries, "github", Some("foo"), Some("s")),
-            "s/foo@github"
-        );
+    #[test]
+    fn test_validate_card() {
+        let card = |month: &str, year: &str| {
+            validate_card(
+                &[
+                    ("exp_month".to_string(), month.to_string()),
+                    ("exp_year".to_string(), year.to_string()),
+                ]
+                .into_iter()
+                .collect(),
+            )
+        };
+        assert!(card("1", "2030").is_ok());
+        assert!(card("09", "2030").is_ok());
+        assert!(card("12", "2030").is_ok());
+        assert!(card("0", "2030").is_err());
+        assert!(card("13", "2030").is_err());
+        assert!(card("12", "30").is_err());
+        assert!(card("12", "203a").is_err());
+        assert!(validate_card(&std::collections::HashMap::new()).is_ok());
     }
 
-    fn one_match(
-        entries: &[(rbw::db::Entry, DecryptedCipher)],
-        name: &str,
-        username: Option<&str>,
-        folder: Option<&str>,
-        idx: usize,
-    ) -> bool {
-        entries_eq(
-            &find_entry_raw(entries, name, username, folder).unwrap(),
-            &entries[idx],
-        )
+    #[test]
+    fn test_list_match() {
+        let (_, login) = make_entry("login", None, Some("work"));
+        assert!(login.list_match(None, None));
+        assert!(login.list_match(Some("work"), Some("login")));
+        assert!(!login.list_match(Some("home"), None));
+        assert!(!login.list_match(None, Some("card")));
+
+        let (_, mut note) = make_entry("note", None, None);
+        note.data = DecryptedData::SecureNote;
+        assert!(note.list_match(None, Some("note")));
+        assert!(!note.list_match(Some("work"), Some("note")));
     }
 
-    fn no_matches(
-        entries: &[(rbw::db::Entry, DecryptedCipher)],
-        name: &str,
-        username: Option<&str>,
-        folder: Option<&str>,
-    ) -> bool {
-        let res = find_entry_raw(entries, name, username, folder);
-        if let Err(e) = res {
-            format!("{e}").contains("no entry found")
-        } else {
-            false
-        }
+    #[test]
+    fn test_split_folder_needle() {
+        assert_eq!(split_folder_needle("github"), (None, "github".into()));
+        assert_eq!(
+            split_folder_needle("Work/github"),
+            (Some("Work".into()), "github".into())
+        );
+        assert_eq!(split_folder_needle("a//b"), (None, "a/b".into()));
+        assert_eq!(
+            split_folder_needle("Work/a//b"),
+            (Some("Work".into()), "a/b".into())
+        );
+        assert_eq!(
+            split_folder_needle("a/b/c"),
+            (Some("a".into()), "b/c".into())
+        );
     }
 
-    fn many_matches(
-        entries: &[(rbw::db::Entry, DecryptedCipher)],
-        name: &str,
-        username: Option<&str>,
-        folder: Option<&str>,
-    ) -> bool {
-        let res = find_entry_raw(entries, name, username, folder);
-        if let Err(e) = res {
-            format!("{e}").contains("multiple entries found")
-        } else {
-            false
+    #[test]
+    fn test_resolve_org() {
+        let mut db = rbw::db::Db::default();
+        for id in ["org-1", "org-2", "org-3"] {
+            db.protected_org_keys
+                .insert(id.to_string(), "protected key".to_string());
         }
+        assert_eq!(resolve_org(&db, "org-2").unwrap(), "org-2");
+        assert!(resolve_org(&db, "Work")
+            .unwrap_err()
+            .to_string()
+            .contains("run rbw sync"));
+
+        db.org_names.insert("org-1".to_string(), "Work".to_string());
+        db.org_names
+            .insert("org-2".to_string(), "Family".to_string());
+        db.org_names
+            .insert("org-3".to_string(), "family".to_string());
+        assert_eq!(resolve_org(&db, "work").unwrap(), "org-1");
+        assert_eq!(
+            resolve_org(&db, "FAMILY").unwrap_err().to_string(),
+            "multiple organizations named 'FAMILY': org-2, org-3"
+        );
+        assert_eq!(
+            resolve_org(&db, "home").unwrap_err().to_string(),
+            "couldn't find organization 'home'"
+        );
+
+        let (mut entry, _) = make_entry("shared", None, None);
+        assert!(in_org(&entry, None));
+        assert!(!in_org(&entry, Some("org-1")));
+        entry.org_id = Some("org-1".to_string());
+        assert!(in_org(&entry, Some("org-1")));
+        assert!(!in_org(&entry, Some("org-2")));
     }
 
-    fn entries_eq(
-        a: &(rbw::db::Entry, DecryptedCipher),
-        b: &(rbw::db::Entry, DecryptedCipher),
-    ) -> bool {
-        a.0 == b.0 && a.1 == b.1
+    #[test]
+    fn test_split_field_list() {
+        assert_eq!(split_field_list("password"), vec!["password"]);
+        assert_eq!(
+            split_field_list("username,totp"),
+            vec!["username", "totp"]
+        );
+        assert_eq!(
+            split_field_list("security question\\, first pet"),
+            vec!["security question, first pet"]
+        );
+        assert_eq!(split_field_list("a\\,b,c"), vec!["a,b", "c"]);
+        assert_eq!(split_field_list("a\\\\,b"), vec!["a\\", "b"]);
+        assert_eq!(split_field_list("C:\\path"), vec!["C:\\path"]);
+    }
+
+    #[test]
+    fn test_check_empty_password() {
+        assert!(check_empty_password(Some("hunter2"), false, false).is_ok());
+        assert!(check_empty_password(None, false, false).is_err());
+        assert!(check_empty_password(Some(""), false, false).is_err());
+        assert!(check_empty_password(None, false, true).is_ok());
+        // rbw add foo --note text
+        assert!(check_empty_password(None, true, false).is_ok());
+    }
+
+    #[test]
+    fn test_supported_entries() {
+        let (entry, _) = make_entry("login", None, None);
+        let mut unsupported = entry.clone();
+        unsupported.id = "unsupported".to_string();
+        unsupported.data = rbw::db::EntryData::Unsupported { ty: 5 };
+        let db = rbw::db::Db {
+            entries: vec![entry, unsupported],
+            ..Default::default()
+        };
+        let ids: Vec<_> = supported_entries(&db)
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["irrelevant"]);
     }
 
     fn make_entry(
@@ -1967,6 +7785,9 @@ mod test {
                 fields: vec![],
                 notes: None,
                 history: vec![],
+                creation_date: None,
+                revision_date: None,
+                attachments: vec![],
             },
             DecryptedCipher {
                 id: "irrelevant".to_string(),
@@ -1981,14 +7802,28 @@ mod test {
                 fields: vec![],
                 notes: None,
                 history: vec![],
+                creation_date: None,
+                revision_date: None,
+                attachments: vec![],
+                failed_fields: vec![],
             },
         )
     }
 }
 
-fn display_field(name: &str, field: Option<&str>, clipboard: bool) -> bool {
+fn display_field(
+    name: &str,
+    field: Option<&str>,
+    clipboard: Clipboard,
+) -> bool {
     field.map_or_else(
         || false,
-        |field| val_display_or_store(clipboard, &format!("{name}: {field}")),
+        |field| {
+            styled_display_or_store(
+                clipboard,
+                &format!("{name}: {field}"),
+                &format!("{}: {field}", style_label(name)),
+            )
+        },
     )
 }
@@ -6,6 +6,7 @@
 #![allow(clippy::cognitive_complexity)]
 #![allow(clippy::missing_const_for_fn)]
 #![allow(clippy::similar_names)]
+#![allow(clippy::fn_params_excessive_bools)]
 #![allow(clippy::struct_excessive_bools)]
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::too_many_lines)]
@@ -23,6 +24,76 @@ mod sock;
 
 #[derive(Debug, clap::Parser)]
 #[command(version, about = "Unofficial Bitwarden CLI")]
+struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Don't sync the local database from the server after \
+            modifying an entry (only the modified entry will be updated \
+            locally until the next `rbw sync`)"
+    )]
+    no_sync: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Display what `add`, `edit`, `move`, `rename`, `remove`, \
+            `generate`, and `gen-username` would change, without modifying \
+            the entry on the server or in the local database"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Use a stable, tab separated output format for `get`, \
+            `list`, `search`, and `history`, which is suitable for scripts"
+    )]
+    porcelain: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Directory to store the local copy of the database in \
+            (overrides $RBW_DATA_DIR)"
+    )]
+    data_dir: Option<std::path::PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Give up on requests to the Bitwarden server after this many \
+            seconds (overrides the network_timeout configuration option)"
+    )]
+    timeout: Option<u64>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "When looking up an entry, only match entries in the folder \
+            given with --folder (or entries which aren't in a folder, if no \
+            folder is given), rather than falling back to other folders"
+    )]
+    strict_folder: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Don't display informational messages (like entries not \
+            having a password) on stderr, only errors (can also be enabled \
+            by setting $RBW_QUIET)"
+    )]
+    quiet: bool,
+
+    #[command(subcommand)]
+    opt: Opt,
+}
+
+#[derive(Debug, clap::Subcommand)]
 enum Opt {
     #[command(about = "Get or set configuration options")]
     Config {
@@ -50,9 +121,19 @@ enum Opt {
     #[command(about = "Check if the local Bitwarden database is unlocked")]
     Unlocked,
 
+    #[command(
+        about = "Display the configured account and whether it is logged \
+            in and unlocked"
+    )]
+    Whoami,
+
     #[command(about = "Update the local copy of the Bitwarden database")]
     Sync,
 
+    #[command(about = "List modified entries whose local copy hasn't been \
+            refreshed by a sync")]
+    Pending,
+
     #[command(
         about = "List all entries in the local Bitwarden database",
         visible_alias = "ls"
@@ -61,40 +142,362 @@ enum Opt {
         #[arg(
             long,
             help = "Fields to display. \
-                Available options are id, name, user, folder. \
-                Multiple fields will be separated by tabs.",
+                Available options are id, name, user, folder, uri (the \
+                first uri of the entry), totp (whether the entry has a totp \
+                secret), last_used (see the track_usage configuration \
+                option). \
+                Multiple fields will be separated by tabs. \
+                Use 'all' to display every field (in the order id, name, \
+                user, folder, uri, totp, last_used) and 'default' for just \
+                the name.",
             default_value = "name",
             use_value_delimiter = true
         )]
         fields: Vec<String>,
+        #[arg(long, help = "Only list entries in the given folder")]
+        folder: Option<String>,
+        #[arg(
+            long = "type",
+            value_name = "TYPE",
+            help = "Only list entries of the given type (login, card, \
+                identity, note, or ssh_key)"
+        )]
+        ty: Option<String>,
+        #[arg(
+            long,
+            value_name = "ORG",
+            help = "Only match entries in the given organization (name or id)"
+        )]
+        org: Option<String>,
+        #[arg(
+            long,
+            default_value = "name",
+            help = "Order to display the entries in (name, folder, user, \
+                id, modified, which lists the most recently modified \
+                entries first, or last_used, which lists the most recently \
+                used entries first)"
+        )]
+        sort: commands::ListSort,
+        #[arg(long, help = "Reverse the sort order")]
+        reverse: bool,
+    },
+
+    #[command(about = "Search for entries")]
+    Search {
+        #[arg(help = "Search term to locate entries")]
+        term: String,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+        #[arg(
+            long,
+            value_name = "ORG",
+            help = "Only match entries in the given organization (name or id)"
+        )]
+        org: Option<String>,
+        #[arg(
+            long,
+            visible_alias = "one",
+            help = "Display only the best matching entry \
+                (exact name matches first, then name prefixes, then other \
+                matches), failing if nothing matches"
+        )]
+        first: bool,
+        #[arg(
+            long,
+            default_value = "folder",
+            help = "Order to display the results in (folder, name, or \
+                relevance)"
+        )]
+        sort: commands::SearchSort,
+        #[arg(
+            long,
+            value_name = "AREA",
+            help = "Only search in the given part of the entries (name, \
+                username, notes, uri, or field, which matches custom field \
+                names and values)"
+        )]
+        field: Option<commands::SearchField>,
+        #[arg(
+            long,
+            help = "Display the results as a JSON array of objects with \
+                id, name, folder, and username keys"
+        )]
+        raw: bool,
+    },
+
+    #[command(about = "Export the login entries in the database")]
+    Export {
+        #[arg(
+            long,
+            default_value = "csv",
+            help = "Format to export in. Currently only csv is supported, \
+                with the columns name, folder, username, password, totp, \
+                and uris (comma separated)."
+        )]
+        format: commands::ExportFormat,
     },
 
     #[command(about = "Display the password for a given entry")]
     Get {
-        #[arg(help = "Name or UUID of the entry to display")]
+        #[arg(help = "Name, URL, or UUID of the entry to display")]
         name: String,
         #[arg(help = "Username of the entry to display")]
         user: Option<String>,
         #[arg(long, help = "Folder name to search in")]
         folder: Option<String>,
-        #[arg(short, long, help = "Field to get")]
-        field: Option<String>,
+        #[arg(
+            long,
+            value_name = "ORG",
+            help = "Only match entries in the given organization (name or id)"
+        )]
+        org: Option<String>,
+        #[arg(
+            long,
+            help = "When looking up an entry by URL and no login entry \
+                matches, also look for the URL in notes and custom fields"
+        )]
+        search_notes: bool,
+        #[arg(
+            long,
+            value_name = "TYPE",
+            help = "When looking up an entry by URL, match every stored URI \
+                with the given match type (domain, host, starts_with, exact, \
+                or regex) instead of its own"
+        )]
+        uri_match: Option<rbw::api::UriMatchType>,
+        #[arg(
+            short,
+            long,
+            value_name = "FIELD",
+            help = "Field to get. May be given multiple times or as a \
+                comma separated list to get several fields at once (commas \
+                in field names can be escaped as \\,)."
+        )]
+        field: Vec<String>,
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = [
+                "field",
+                "user_only",
+                "pass_only",
+                "otp_only",
+                "full",
+                "raw"
+            ],
+            help = "Custom field to get, matching its name exactly (ignoring \
+                case) rather than any field whose name contains it. Fails if \
+                several fields have that name."
+        )]
+        field_exact: Option<String>,
+        #[arg(
+            long = "user",
+            conflicts_with_all =
+                ["field", "full", "raw", "pass_only", "otp_only"],
+            help = "Display only the username (the same as --field username)"
+        )]
+        user_only: bool,
+        #[arg(
+            long = "pass",
+            conflicts_with_all = ["field", "full", "raw", "otp_only"],
+            help = "Display only the password (the same as --field password)"
+        )]
+        pass_only: bool,
+        #[arg(
+            long = "otp",
+            conflicts_with_all = ["field", "full", "raw"],
+            help = "Display only the current totp code (the same as --field \
+                totp)"
+        )]
+        otp_only: bool,
+        #[arg(
+            long,
+            help = "When getting multiple fields, display only the values \
+                (one per line, in the order requested) without the field \
+                names"
+        )]
+        no_labels: bool,
         #[arg(long, help = "Display the notes in addition to the password")]
         full: bool,
-        #[structopt(long, help = "Display output as JSON")]
+        #[arg(
+            long,
+            requires = "full",
+            help = "Also display the password history (not when copying to \
+                the clipboard)"
+        )]
+        print_history: bool,
+        #[arg(
+            long,
+            conflicts_with_all = ["field_exact", "full", "raw", "clipboard"],
+            help = "Display the totp secret as a QR code, for adding it to \
+                another authenticator app. Can only be combined with \
+                --field totp or --field totp_secret."
+        )]
+        qr: bool,
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "field",
+                "field_exact",
+                "user_only",
+                "pass_only",
+                "otp_only",
+                "raw",
+                "clipboard",
+                "qr"
+            ],
+            help = "Display the password of every matching entry instead of \
+                failing when there are several, as name<TAB>password lines \
+                (or, with --full, every matching entry in full, each after a \
+                header line)"
+        )]
+        all: bool,
+        #[arg(long, help = "Display output as JSON")]
         raw: bool,
-        #[structopt(long, help = "Copy result to clipboard")]
+        #[arg(
+            long,
+            requires = "raw",
+            value_name = "KEYS",
+            help = "Only include the given top level keys in the JSON \
+                output. Available keys are id, folder, name, data, fields, \
+                notes, history, creation_date, revision_date, attachments, \
+                failed_fields.",
+            use_value_delimiter = true
+        )]
+        only: Vec<String>,
+        #[arg(long, help = "Copy result to clipboard")]
         clipboard: bool,
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            requires = "clipboard",
+            help = "Clear the clipboard again after the given number of \
+                seconds, unless something else was copied in the meantime"
+        )]
+        clipboard_timeout: Option<u64>,
+        #[arg(
+            long,
+            requires = "clipboard",
+            help = "Also display the result when copying it to the clipboard"
+        )]
+        print: bool,
+        #[arg(
+            long,
+            requires = "full",
+            help = "Display a header line identifying the entry before \
+                its contents (the default with --all)"
+        )]
+        header: bool,
+        #[arg(
+            long,
+            requires = "full",
+            conflicts_with = "header",
+            help = "Don't display a header line before the entry contents"
+        )]
+        no_header: bool,
+        #[arg(
+            long,
+            conflicts_with = "window",
+            requires = "field",
+            help = "With --field totp, display the code for the next time \
+                period instead of the current one"
+        )]
+        next: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "field",
+            help = "With --field totp, display the current code followed by \
+                the codes for the next N time periods, one per line"
+        )]
+        window: Option<u64>,
     },
 
     #[command(about = "Display the authenticator code for a given entry")]
     Code {
-        #[arg(help = "Name or UUID of the entry to display")]
-        name: String,
+        #[arg(
+            help = "Name, URL, or UUID of the entry to display",
+            required_unless_present = "secret"
+        )]
+        name: Option<String>,
         #[arg(help = "Username of the entry to display")]
         user: Option<String>,
         #[arg(long, help = "Folder name to search in")]
         folder: Option<String>,
+        #[arg(
+            long,
+            help = "When looking up an entry by URL and no login entry \
+                matches, also look for the URL in notes and custom fields"
+        )]
+        search_notes: bool,
+        #[arg(
+            long,
+            value_name = "TYPE",
+            conflicts_with = "secret",
+            help = "When looking up an entry by URL, match every stored URI \
+                with the given match type (domain, host, starts_with, exact, \
+                or regex) instead of its own"
+        )]
+        uri_match: Option<rbw::api::UriMatchType>,
+        #[arg(
+            long,
+            conflicts_with_all = &["name", "user", "folder", "search_notes"],
+            help = "Generate a code from the given TOTP secret or otpauth \
+                url instead of a stored entry"
+        )]
+        secret: Option<String>,
+        #[arg(long, help = "Number of digits in the code (6 to 8)")]
+        digits: Option<u32>,
+        #[arg(long, help = "Number of seconds each code is valid for")]
+        period: Option<u64>,
+        #[arg(
+            long,
+            help = "Hash algorithm to use (sha1, sha256, or sha512)"
+        )]
+        algorithm: Option<commands::TotpAlgorithm>,
+        #[arg(
+            long,
+            conflicts_with = "window",
+            help = "Display the code for the next time period instead of \
+                the current one"
+        )]
+        next: bool,
+        #[arg(
+            long,
+            value_name = "N",
+            help = "Display the current code followed by the codes for the \
+                next N time periods, one per line"
+        )]
+        window: Option<u64>,
+        #[arg(
+            long,
+            conflicts_with_all = &["next", "window"],
+            help = "Keep displaying the current code and the number of \
+                seconds until it expires, updating it until interrupted"
+        )]
+        watch: bool,
+        #[arg(
+            long,
+            conflicts_with = "watch",
+            help = "Also display the number of seconds until the current \
+                code expires, on its own line after the code"
+        )]
+        remaining: bool,
+        #[arg(
+            long,
+            conflicts_with_all = &["secret", "window", "watch", "remaining"],
+            help = "Display the codes for every matching entry instead of \
+                failing when there are several, as name<TAB>code lines \
+                (skipping entries without a TOTP secret)"
+        )]
+        all: bool,
+        #[arg(
+            long,
+            conflicts_with_all = &["window", "watch", "remaining", "all"],
+            help = "Display the code as JSON, along with its period, number \
+                of digits, and the unix timestamp it expires at"
+        )]
+        raw: bool,
     },
 
     #[command(
@@ -113,12 +516,58 @@ enum Opt {
         user: Option<String>,
         #[arg(
             long,
-            help = "URI for the password entry",
+            help = "URI for the password entry. A match type can be given \
+                after a final ':', for example https://example.com:host \
+                (one of domain, host, starts_with, exact, regex, or never).",
+            value_name = "URI[:MATCH]",
+            value_parser = commands::parse_uri_arg,
             number_of_values = 1
         )]
-        uri: Vec<String>,
+        uri: Vec<(String, Option<rbw::api::UriMatchType>)>,
         #[arg(long, help = "Folder for the password entry")]
         folder: Option<String>,
+        #[arg(
+            long = "type",
+            value_name = "TYPE",
+            default_value = "login",
+            help = "Type of entry to create (login, card, or identity). \
+                Entries other than logins are filled in using a template in \
+                the editor."
+        )]
+        ty: commands::EntryType,
+        #[arg(
+            long,
+            value_name = "SECRET",
+            help = "TOTP secret for the password entry, either as base32 or \
+                as an otpauth:// url"
+        )]
+        totp: Option<String>,
+        #[arg(long, help = "Allow saving the entry without a password")]
+        allow_empty: bool,
+        #[arg(
+            long,
+            help = "Read the password and notes (or the template, for \
+                entries other than logins) from stdin instead of opening an \
+                editor"
+        )]
+        stdin: bool,
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with = "stdin",
+            help = "Read the password from the given file instead of opening \
+                an editor (a single trailing newline is ignored)"
+        )]
+        password_file: Option<std::path::PathBuf>,
+        #[arg(
+            long,
+            value_name = "TEXT",
+            conflicts_with = "stdin",
+            help = "Notes for the password entry. Given either this or \
+                --password-file, no editor is opened, and without \
+                --password-file the entry is created without a password."
+        )]
+        note: Option<String>,
     },
 
     #[command(
@@ -128,8 +577,8 @@ enum Opt {
             password to the database.",
         visible_alias = "gen",
         group = clap::ArgGroup::new("password-type").args(&[
-            "no-symbols",
-            "only-numbers",
+            "no_symbols",
+            "only_numbers",
             "nonconfusables",
             "diceware",
         ])
@@ -143,10 +592,14 @@ enum Opt {
         user: Option<String>,
         #[arg(
             long,
-            help = "URI for the password entry",
+            help = "URI for the password entry. A match type can be given \
+                after a final ':', for example https://example.com:host \
+                (one of domain, host, starts_with, exact, regex, or never).",
+            value_name = "URI[:MATCH]",
+            value_parser = commands::parse_uri_arg,
             number_of_values = 1
         )]
-        uri: Vec<String>,
+        uri: Vec<(String, Option<rbw::api::UriMatchType>)>,
         #[arg(long, help = "Folder for the password entry")]
         folder: Option<String>,
         #[arg(
@@ -168,12 +621,111 @@ enum Opt {
         nonconfusables: bool,
         #[arg(
             long,
+            visible_alias = "words",
             help = "Generate a password of multiple dictionary \
                 words chosen from the EFF word list. The len \
                 parameter for this option will set the number \
                 of words to generate, rather than characters."
         )]
         diceware: bool,
+        #[arg(
+            long,
+            requires = "diceware",
+            value_name = "SEPARATOR",
+            help = "String to put between the words of a diceware password \
+                (defaults to a space)"
+        )]
+        separator: Option<String>,
+        #[arg(
+            long,
+            requires = "diceware",
+            help = "Capitalize each word of a diceware password"
+        )]
+        capitalize: bool,
+        #[arg(
+            long,
+            requires = "diceware",
+            help = "Append a random digit to one of the words of a diceware \
+                password"
+        )]
+        include_number: bool,
+        #[arg(
+            long,
+            help = "Characters to exclude from the generated password \
+                (useful for sites which disallow specific symbols)"
+        )]
+        exclude: Option<String>,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            help = "Generate a password with at least this many digits"
+        )]
+        min_digits: usize,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            help = "Generate a password with at least this many special \
+                characters"
+        )]
+        min_symbols: usize,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            help = "Generate a password with at least this many uppercase \
+                letters"
+        )]
+        min_uppercase: usize,
+        #[arg(
+            long,
+            help = "Copy the generated password to the clipboard instead of \
+                displaying it"
+        )]
+        copy: bool,
+    },
+
+    #[command(
+        name = "gen-username",
+        about = "Generate a new username",
+        long_about = "Generate a new username\n\n\
+            By default, this generates a username from random dictionary \
+            words. If given an existing entry name with --into, also save \
+            the generated username to that entry.",
+        group = clap::ArgGroup::new("username-type").args(&[
+            "random_words",
+            "catchall",
+            "plus",
+        ])
+    )]
+    GenUsername {
+        #[arg(
+            long = "random-words",
+            help = "Generate a username from random dictionary words \
+                (the default)"
+        )]
+        random_words: bool,
+        #[arg(
+            long,
+            value_name = "DOMAIN",
+            help = "Generate a random email address at the given \
+                catch-all domain"
+        )]
+        catchall: Option<String>,
+        #[arg(
+            long,
+            value_name = "EMAIL",
+            help = "Generate a random plus-addressed alias of the given \
+                email address"
+        )]
+        plus: Option<String>,
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Name or UUID of an existing entry to save the username to"
+        )]
+        into: Option<String>,
     },
 
     #[command(
@@ -184,20 +736,44 @@ enum Opt {
             The editor to use is determined  by the value of the \
             $VISUAL or $EDITOR environment variables. The first line \
             will be saved as the password and the remainder will be saved \
-            as a note."
+            as a note. Card and identity entries are instead edited as one \
+            \"key: value\" line per field, followed by the notes. If \
+            --username is given, the username is changed instead, without \
+            opening an editor."
     )]
     Edit {
-        #[arg(help = "Name or UUID of the password entry")]
+        #[arg(help = "Name, URL, or UUID of the password entry")]
         name: String,
         #[arg(help = "Username for the password entry")]
         user: Option<String>,
         #[arg(long, help = "Folder name to search in")]
         folder: Option<String>,
+        #[arg(
+            long,
+            value_name = "USERNAME",
+            help = "Change the username of the entry to this value \
+                (an empty value removes the username)"
+        )]
+        username: Option<String>,
+        #[arg(
+            long,
+            value_name = "SECRET",
+            help = "Change the TOTP secret of the entry to this value \
+                (an empty value removes the secret)"
+        )]
+        totp: Option<String>,
+        #[arg(
+            long,
+            conflicts_with_all = ["username", "totp"],
+            help = "Read the new contents from stdin, in the same format the \
+                editor would use, instead of opening an editor"
+        )]
+        stdin: bool,
     },
 
     #[command(about = "Remove a given entry", visible_alias = "rm")]
     Remove {
-        #[arg(help = "Name or UUID of the password entry")]
+        #[arg(help = "Name, URL, or UUID of the password entry")]
         name: String,
         #[arg(help = "Username for the password entry")]
         user: Option<String>,
@@ -205,21 +781,89 @@ enum Opt {
         folder: Option<String>,
     },
 
+    #[command(
+        name = "move",
+        about = "Move an entry to a different folder (creating the folder \
+            if it doesn't exist)",
+        visible_alias = "mv"
+    )]
+    Move {
+        #[arg(help = "Name, URL, or UUID of the entry to move")]
+        name: String,
+        #[arg(
+            help = "Folder to move the entry to (an empty name moves the \
+                entry out of its folder)"
+        )]
+        folder: String,
+        #[arg(long, help = "Username of the entry to move")]
+        user: Option<String>,
+    },
+
+    #[command(about = "Rename an entry without opening an editor")]
+    Rename {
+        #[arg(help = "Name, URL, or UUID of the entry to rename")]
+        name: String,
+        #[arg(help = "New name for the entry")]
+        new_name: String,
+        #[arg(long, help = "Username of the entry to rename")]
+        user: Option<String>,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+    },
+
+    #[command(
+        about = "Create a copy of an entry under a new name",
+        long_about = "Create a copy of an entry under a new name\n\n\
+            The copy keeps the folder, notes, and custom fields of the \
+            original entry, but not its password history."
+    )]
+    Clone {
+        #[arg(help = "Name, URL, or UUID of the entry to copy")]
+        name: String,
+        #[arg(help = "Name for the new entry")]
+        new_name: String,
+        #[arg(long, help = "Username of the entry to copy")]
+        user: Option<String>,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+    },
+
+    #[command(about = "List the names of the custom fields of an entry")]
+    Fields {
+        #[arg(help = "Name, URL, or UUID of the entry")]
+        name: String,
+        #[arg(help = "Username of the entry")]
+        user: Option<String>,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+    },
+
     #[command(about = "View the password history for a given entry")]
     History {
-        #[arg(help = "Name or UUID of the password entry")]
+        #[arg(help = "Name, URL, or UUID of the password entry")]
         name: String,
         #[arg(help = "Username for the password entry")]
         user: Option<String>,
         #[arg(long, help = "Folder name to search in")]
         folder: Option<String>,
+        #[arg(long, help = "Display output as JSON")]
+        raw: bool,
+    },
+
+    #[command(about = "List or download the file attachments of an entry")]
+    Attachments {
+        #[command(subcommand)]
+        attachments: Attachments,
     },
 
     #[command(about = "Lock the password database")]
     Lock,
 
     #[command(about = "Remove the local copy of the password database")]
-    Purge,
+    Purge {
+        #[arg(short, long, help = "Don't ask for confirmation")]
+        force: bool,
+    },
 
     #[command(name = "stop-agent", about = "Terminate the background agent")]
     StopAgent,
@@ -241,17 +885,29 @@ impl Opt {
             Self::Login => "login".to_string(),
             Self::Unlock => "unlock".to_string(),
             Self::Unlocked => "unlocked".to_string(),
+            Self::Whoami => "whoami".to_string(),
             Self::Sync => "sync".to_string(),
+            Self::Pending => "pending".to_string(),
             Self::List { .. } => "list".to_string(),
+            Self::Search { .. } => "search".to_string(),
+            Self::Export { .. } => "export".to_string(),
             Self::Get { .. } => "get".to_string(),
             Self::Code { .. } => "code".to_string(),
             Self::Add { .. } => "add".to_string(),
             Self::Generate { .. } => "generate".to_string(),
+            Self::GenUsername { .. } => "gen-username".to_string(),
             Self::Edit { .. } => "edit".to_string(),
             Self::Remove { .. } => "remove".to_string(),
+            Self::Move { .. } => "move".to_string(),
+            Self::Rename { .. } => "rename".to_string(),
+            Self::Clone { .. } => "clone".to_string(),
+            Self::Fields { .. } => "fields".to_string(),
             Self::History { .. } => "history".to_string(),
+            Self::Attachments { attachments } => {
+                format!("attachments {}", attachments.subcommand_name())
+            }
             Self::Lock => "lock".to_string(),
-            Self::Purge => "purge".to_string(),
+            Self::Purge { .. } => "purge".to_string(),
             Self::StopAgent => "stop-agent".to_string(),
             Self::GenCompletions { .. } => "gen-completions".to_string(),
         }
@@ -287,11 +943,73 @@ impl Config {
     }
 }
 
+#[derive(Debug, clap::Parser)]
+enum Attachments {
+    #[command(about = "List the names and sizes of an entry's attachments")]
+    List {
+        #[arg(help = "Name, URL, or UUID of the entry")]
+        name: String,
+        #[arg(long, help = "Username of the entry")]
+        user: Option<String>,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+    },
+    #[command(about = "Download and decrypt an attachment")]
+    Get {
+        #[arg(help = "Name, URL, or UUID of the entry")]
+        name: String,
+        #[arg(help = "File name of the attachment")]
+        file: String,
+        #[arg(long, help = "Username of the entry")]
+        user: Option<String>,
+        #[arg(long, help = "Folder name to search in")]
+        folder: Option<String>,
+        #[arg(
+            short,
+            long,
+            value_name = "PATH",
+            help = "Where to write the attachment (- for stdout). Defaults \
+                to the attachment's file name in the current directory."
+        )]
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+impl Attachments {
+    fn subcommand_name(&self) -> String {
+        match self {
+            Self::List { .. } => "list",
+            Self::Get { .. } => "get",
+        }
+        .to_string()
+    }
+}
+
 fn main() {
-    let opt = Opt::parse();
+    let cli = Cli::parse();
+    let opt = &cli.opt;
+    let no_sync = cli.no_sync;
+    let dry_run = cli.dry_run;
+    let porcelain = cli.porcelain;
+    let strict_folder = cli.strict_folder;
+    // the agent is spawned from this process, so this also makes it use the
+    // same directory
+    if let Some(data_dir) = &cli.data_dir {
+        std::env::set_var("RBW_DATA_DIR", data_dir);
+    }
+    if cli.timeout.is_some() {
+        rbw::actions::set_network_timeout(cli.timeout);
+    }
 
+    let quiet = cli.quiet
+        || std::env::var_os("RBW_QUIET")
+            .is_some_and(|quiet| !quiet.is_empty());
     env_logger::Builder::from_env(
-        env_logger::Env::default().default_filter_or("info"),
+        env_logger::Env::default().default_filter_or(if quiet {
+            "error"
+        } else {
+            "info"
+        }),
     )
     .format(|buf, record| {
         if let Some((terminal_size::Width(w), _)) =
@@ -305,7 +1023,7 @@ fn main() {
     })
     .init();
 
-    let res = match &opt {
+    let res = match opt {
         Opt::Config { config } => match config {
             Config::Show => commands::config_show(),
             Config::Set { key, value } => commands::config_set(key, value),
@@ -315,42 +1033,173 @@ fn main() {
         Opt::Login => commands::login(),
         Opt::Unlock => commands::unlock(),
         Opt::Unlocked => commands::unlocked(),
+        Opt::Whoami => commands::whoami(),
         Opt::Sync => commands::sync(),
-        Opt::List { fields } => commands::list(fields),
+        Opt::Pending => commands::pending(porcelain),
+        Opt::List {
+            fields,
+            folder,
+            ty,
+            org,
+            sort,
+            reverse,
+        } => commands::list(
+            fields,
+            folder.as_deref(),
+            ty.as_deref(),
+            org.as_deref(),
+            *sort,
+            *reverse,
+            porcelain,
+        ),
+        Opt::Search {
+            term,
+            folder,
+            first,
+            sort,
+            field,
+            org,
+            raw,
+        } => commands::search(
+            term,
+            folder.as_deref(),
+            *first,
+            *sort,
+            *field,
+            org.as_deref(),
+            *raw,
+            porcelain,
+        ),
+        Opt::Export { format } => commands::export(*format),
         Opt::Get {
             name,
             user,
             folder,
+            org,
+            search_notes,
+            uri_match,
             field,
+            field_exact,
+            user_only,
+            pass_only,
+            otp_only,
+            no_labels,
             full,
+            print_history,
+            qr,
+            all,
             raw,
+            only,
             clipboard,
+            clipboard_timeout,
+            print,
+            header,
+            no_header,
+            next,
+            window,
         } => commands::get(
             name,
             user.as_deref(),
             folder.as_deref(),
-            field.as_deref(),
+            strict_folder,
+            org.as_deref(),
+            &if *user_only {
+                vec!["username".to_string()]
+            } else if *pass_only {
+                vec!["password".to_string()]
+            } else if *otp_only {
+                vec!["totp".to_string()]
+            } else {
+                field.clone()
+            },
+            field_exact.as_deref(),
+            !*no_labels,
             *full,
             *raw,
-            *clipboard,
+            match (*clipboard, *print) {
+                (false, _) => commands::Clipboard::No,
+                (true, false) => commands::Clipboard::Copy {
+                    clear_after: *clipboard_timeout,
+                },
+                (true, true) => commands::Clipboard::CopyAndPrint {
+                    clear_after: *clipboard_timeout,
+                },
+            },
+            if *header {
+                Some(true)
+            } else if *no_header {
+                Some(false)
+            } else {
+                None
+            },
+            only,
+            &totp_windows(*next, *window),
+            *search_notes,
+            *uri_match,
+            *print_history,
+            *qr,
+            *all,
+            porcelain,
+        ),
+        Opt::Code {
+            name,
+            user,
+            folder,
+            search_notes,
+            uri_match,
+            secret,
+            digits,
+            period,
+            algorithm,
+            next,
+            window,
+            watch,
+            remaining,
+            all,
+            raw,
+        } => commands::code(
+            name.as_deref(),
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            secret.as_deref(),
+            &commands::TotpOverrides {
+                digits: *digits,
+                period: *period,
+                algorithm: *algorithm,
+            },
+            &totp_windows(*next, *window),
+            *search_notes,
+            *uri_match,
+            *watch,
+            *remaining,
+            *all,
+            *raw,
         ),
-        Opt::Code { name, user, folder } => {
-            commands::code(name, user.as_deref(), folder.as_deref())
-        }
         Opt::Add {
             name,
             user,
             uri,
             folder,
+            ty,
+            totp,
+            allow_empty,
+            stdin,
+            password_file,
+            note,
         } => commands::add(
             name,
             user.as_deref(),
-            &uri.iter()
-                // XXX not sure what the ui for specifying the match type
-                // should be
-                .map(|uri| (uri.clone(), None))
-                .collect::<Vec<_>>(),
+            uri,
             folder.as_deref(),
+            *ty,
+            totp.as_deref(),
+            *allow_empty,
+            *stdin,
+            password_file.as_deref(),
+            note.as_deref(),
+            no_sync,
+            dry_run,
         ),
         Opt::Generate {
             len,
@@ -362,6 +1211,14 @@ fn main() {
             only_numbers,
             nonconfusables,
             diceware,
+            separator,
+            capitalize,
+            include_number,
+            exclude,
+            min_digits,
+            min_symbols,
+            min_uppercase,
+            copy,
         } => {
             let ty = if *no_symbols {
                 rbw::pwgen::Type::NoSymbols
@@ -370,39 +1227,159 @@ fn main() {
             } else if *nonconfusables {
                 rbw::pwgen::Type::NonConfusables
             } else if *diceware {
-                rbw::pwgen::Type::Diceware
+                rbw::pwgen::Type::Diceware(rbw::pwgen::Passphrase {
+                    separator: separator.as_deref().unwrap_or(" "),
+                    capitalize: *capitalize,
+                    include_number: *include_number,
+                })
             } else {
                 rbw::pwgen::Type::AllChars
             };
             commands::generate(
                 name.as_deref(),
                 user.as_deref(),
-                &uri.iter()
-                    // XXX not sure what the ui for specifying the match type
-                    // should be
-                    .map(|uri| (uri.clone(), None))
-                    .collect::<Vec<_>>(),
+                uri,
                 folder.as_deref(),
                 *len,
                 ty,
+                exclude.as_deref().unwrap_or(""),
+                rbw::pwgen::Minimums {
+                    digits: *min_digits,
+                    symbols: *min_symbols,
+                    uppercase: *min_uppercase,
+                },
+                *copy,
+                no_sync,
+                dry_run,
             )
         }
-        Opt::Edit { name, user, folder } => {
-            commands::edit(name, user.as_deref(), folder.as_deref())
-        }
-        Opt::Remove { name, user, folder } => {
-            commands::remove(name, user.as_deref(), folder.as_deref())
-        }
-        Opt::History { name, user, folder } => {
-            commands::history(name, user.as_deref(), folder.as_deref())
-        }
+        Opt::GenUsername {
+            random_words: _,
+            catchall,
+            plus,
+            into,
+        } => commands::gen_username(
+            catchall.as_deref(),
+            plus.as_deref(),
+            into.as_deref(),
+            strict_folder,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Edit {
+            name,
+            user,
+            folder,
+            username,
+            totp,
+            stdin,
+        } => commands::edit(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            username.as_deref(),
+            totp.as_deref(),
+            *stdin,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Remove { name, user, folder } => commands::remove(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Move { name, folder, user } => commands::move_entry(
+            name,
+            user.as_deref(),
+            strict_folder,
+            folder,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Rename {
+            name,
+            new_name,
+            user,
+            folder,
+        } => commands::rename(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            new_name,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Clone {
+            name,
+            new_name,
+            user,
+            folder,
+        } => commands::clone_entry(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            new_name,
+            no_sync,
+            dry_run,
+        ),
+        Opt::Fields { name, user, folder } => commands::fields(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            porcelain,
+        ),
+        Opt::History {
+            name,
+            user,
+            folder,
+            raw,
+        } => commands::history(
+            name,
+            user.as_deref(),
+            folder.as_deref(),
+            strict_folder,
+            *raw,
+            porcelain,
+        ),
+        Opt::Attachments { attachments } => match attachments {
+            Attachments::List { name, user, folder } => {
+                commands::attachments_list(
+                    name,
+                    user.as_deref(),
+                    folder.as_deref(),
+                    strict_folder,
+                    porcelain,
+                )
+            }
+            Attachments::Get {
+                name,
+                file,
+                user,
+                folder,
+                output,
+            } => commands::attachments_get(
+                name,
+                user.as_deref(),
+                folder.as_deref(),
+                strict_folder,
+                file,
+                output.as_deref(),
+            ),
+        },
         Opt::Lock => commands::lock(),
-        Opt::Purge => commands::purge(),
+        Opt::Purge { force } => commands::purge(*force),
         Opt::StopAgent => commands::stop_agent(),
         Opt::GenCompletions { shell } => {
             clap_complete::generate(
                 *shell,
-                &mut Opt::command(),
+                &mut Cli::command(),
                 "rbw",
                 &mut std::io::stdout(),
             );
@@ -413,6 +1390,30 @@ fn main() {
 
     if let Err(e) = res {
         eprintln!("{e:#}");
-        std::process::exit(1);
+        // the conventional exit status for a command which wasn't found
+        if e.downcast_ref::<commands::AgentNotFound>().is_some() {
+            std::process::exit(127);
+        }
+        match e.downcast_ref::<commands::FindEntryError>() {
+            Some(
+                commands::FindEntryError::NotFound
+                | commands::FindEntryError::NeverMatched { .. },
+            ) => std::process::exit(4),
+            Some(commands::FindEntryError::MultipleFound { .. }) => {
+                std::process::exit(5)
+            }
+            None => std::process::exit(1),
+        }
+    }
+}
+
+fn totp_windows(
+    next: bool,
+    window: Option<u64>,
+) -> std::ops::RangeInclusive<u64> {
+    if next {
+        1..=1
+    } else {
+        0..=window.unwrap_or(0)
     }
 }
@@ -93,6 +93,39 @@ impl CipherString {
         }
     }
 
+    // attachments are stored as raw bytes rather than as a string: a type
+    // byte (only 2 is supported), followed by the iv, the mac, and then the
+    // ciphertext
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(2) => {}
+            Some(ty) => {
+                return Err(Error::UnimplementedCipherStringType {
+                    ty: ty.to_string(),
+                })
+            }
+            None => {
+                return Err(Error::InvalidCipherString {
+                    reason: "empty encrypted data".to_string(),
+                })
+            }
+        }
+        if bytes.len() <= 1 + 16 + 32 {
+            return Err(Error::InvalidCipherString {
+                reason: format!(
+                    "encrypted data too short ({} bytes)",
+                    bytes.len()
+                ),
+            });
+        }
+
+        Ok(Self::Symmetric {
+            iv: bytes[1..17].to_vec(),
+            mac: Some(bytes[17..49].to_vec()),
+            ciphertext: bytes[49..].to_vec(),
+        })
+    }
+
     pub fn encrypt_symmetric(
         keys: &crate::locked::Keys,
         plaintext: &[u8],
@@ -291,6 +324,46 @@ fn pkcs7_unpad(b: &[u8]) -> Option<&[u8]> {
     Some(&b[..b.len() - padding_len])
 }
 
+#[test]
+fn test_from_bytes() {
+    let mut key = crate::locked::Vec::new();
+    key.extend((0..64).map(|i: u8| i.wrapping_mul(7)));
+    let keys = crate::locked::Keys::new(key);
+
+    let CipherString::Symmetric {
+        iv,
+        ciphertext,
+        mac: Some(mac),
+    } = CipherString::encrypt_symmetric(&keys, b"attachment contents")
+        .unwrap()
+    else {
+        unreachable!();
+    };
+    let mut bytes = vec![2];
+    bytes.extend(&iv);
+    bytes.extend(&mac);
+    bytes.extend(&ciphertext);
+    assert_eq!(
+        CipherString::from_bytes(&bytes)
+            .unwrap()
+            .decrypt_symmetric(&keys)
+            .unwrap(),
+        b"attachment contents"
+    );
+
+    // flipping a bit of the ciphertext should fail the mac check
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert!(CipherString::from_bytes(&bytes)
+        .unwrap()
+        .decrypt_symmetric(&keys)
+        .is_err());
+
+    assert!(CipherString::from_bytes(&[]).is_err());
+    assert!(CipherString::from_bytes(&[2; 49]).is_err());
+    assert!(CipherString::from_bytes(&[0; 64]).is_err());
+}
+
 #[test]
 fn test_pkcs7_unpad() {
     let tests = [
@@ -15,12 +15,73 @@ pub struct Config {
     pub sync_interval: u64,
     #[serde(default = "default_pinentry")]
     pub pinentry: String,
+    pub pinentry_title: Option<String>,
+    pub pinentry_prompt: Option<String>,
+    #[serde(default = "default_sync_after_write")]
+    pub sync_after_write: bool,
+    pub network_timeout: Option<u64>,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    pub generate_policy: Option<std::path::PathBuf>,
+    #[serde(default)]
+    pub track_usage: bool,
+    #[serde(default)]
+    pub clipboard_selection: ClipboardSelection,
+    #[serde(default)]
+    pub field_aliases: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub decrypt_cache: bool,
+    #[serde(default)]
+    pub prewarm: bool,
+    #[serde(default)]
+    pub equivalent_domains: Vec<Vec<String>>,
+    #[serde(default)]
+    pub on_multiple_matches: MultipleMatches,
     pub client_cert_path: Option<std::path::PathBuf>,
     // backcompat, no longer generated in new configs
     #[serde(skip_serializing)]
     pub device_id: Option<String>,
 }
 
+// which selections `rbw get --clipboard` copies to
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardSelection {
+    #[default]
+    Clipboard,
+    Primary,
+    Both,
+}
+
+// what to do when looking up a single entry matches several
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MultipleMatches {
+    #[default]
+    Error,
+    First,
+    // by revision date
+    Newest,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -31,6 +92,19 @@ impl Default for Config {
             lock_timeout: default_lock_timeout(),
             sync_interval: default_sync_interval(),
             pinentry: default_pinentry(),
+            pinentry_title: None,
+            pinentry_prompt: None,
+            sync_after_write: default_sync_after_write(),
+            network_timeout: None,
+            max_retries: default_max_retries(),
+            generate_policy: None,
+            track_usage: false,
+            clipboard_selection: ClipboardSelection::default(),
+            field_aliases: std::collections::BTreeMap::new(),
+            decrypt_cache: false,
+            prewarm: false,
+            equivalent_domains: vec![],
+            on_multiple_matches: MultipleMatches::default(),
             client_cert_path: None,
             device_id: None,
         }
@@ -52,6 +126,26 @@ pub fn default_pinentry() -> String {
     "pinentry".to_string()
 }
 
+#[must_use]
+pub fn default_pinentry_title() -> String {
+    "rbw ({email})".to_string()
+}
+
+#[must_use]
+pub fn default_pinentry_prompt() -> String {
+    "Master Password".to_string()
+}
+
+#[must_use]
+pub fn default_sync_after_write() -> bool {
+    true
+}
+
+#[must_use]
+pub fn default_max_retries() -> u32 {
+    3
+}
+
 impl Config {
     #[must_use]
     pub fn new() -> Self {
@@ -170,6 +264,30 @@ impl Config {
         })
     }
 
+    #[must_use]
+    pub fn pinentry_title(&self) -> String {
+        self.expand_pinentry_placeholders(
+            self.pinentry_title
+                .as_deref()
+                .unwrap_or(&default_pinentry_title()),
+        )
+    }
+
+    #[must_use]
+    pub fn pinentry_prompt(&self) -> String {
+        self.expand_pinentry_placeholders(
+            self.pinentry_prompt
+                .as_deref()
+                .unwrap_or(&default_pinentry_prompt()),
+        )
+    }
+
+    fn expand_pinentry_placeholders(&self, s: &str) -> String {
+        s.replace("{email}", self.email.as_deref().unwrap_or(""))
+            .replace("{profile}", &crate::dirs::profile())
+            .replace("{server}", &self.server_name())
+    }
+
     #[must_use]
     pub fn client_cert_path(&self) -> Option<&std::path::Path> {
         self.client_cert_path.as_deref()
@@ -16,6 +16,111 @@ pub struct Entry {
     pub fields: Vec<Field>,
     pub notes: Option<String>,
     pub history: Vec<HistoryEntry>,
+    // not present in databases synced by older versions
+    #[serde(default)]
+    pub creation_date: Option<String>,
+    #[serde(default)]
+    pub revision_date: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Entry {
+    // every encrypted value in the entry which is encrypted with the entry's
+    // (or its organization's) key. the folder name is left out, since it is
+    // always encrypted with the user's own key.
+    #[must_use]
+    pub fn cipherstrings(&self) -> Vec<&str> {
+        let data: Vec<&Option<String>> = match &self.data {
+            EntryData::Login {
+                username,
+                password,
+                totp,
+                ..
+            } => vec![username, password, totp],
+            EntryData::Card {
+                cardholder_name,
+                number,
+                brand,
+                exp_month,
+                exp_year,
+                code,
+            } => vec![
+                cardholder_name,
+                number,
+                brand,
+                exp_month,
+                exp_year,
+                code,
+            ],
+            EntryData::Identity {
+                title,
+                first_name,
+                middle_name,
+                last_name,
+                address1,
+                address2,
+                address3,
+                city,
+                state,
+                postal_code,
+                country,
+                phone,
+                email,
+                ssn,
+                license_number,
+                passport_number,
+                username,
+            } => vec![
+                title,
+                first_name,
+                middle_name,
+                last_name,
+                address1,
+                address2,
+                address3,
+                city,
+                state,
+                postal_code,
+                country,
+                phone,
+                email,
+                ssn,
+                license_number,
+                passport_number,
+                username,
+            ],
+            EntryData::SshKey {
+                private_key,
+                public_key,
+                fingerprint,
+            } => vec![private_key, public_key, fingerprint],
+            EntryData::SecureNote | EntryData::Unsupported { .. } => vec![],
+        };
+        let uris = match &self.data {
+            EntryData::Login { uris, .. } => uris.as_slice(),
+            _ => &[],
+        };
+
+        std::iter::once(self.name.as_str())
+            .chain(data.into_iter().flatten().map(String::as_str))
+            .chain(uris.iter().map(|uri| uri.uri.as_str()))
+            .chain(self.fields.iter().flat_map(|field| {
+                field
+                    .name
+                    .iter()
+                    .chain(field.value.iter())
+                    .map(String::as_str)
+            }))
+            .chain(self.notes.as_deref())
+            .chain(self.history.iter().map(|entry| entry.password.as_str()))
+            .chain(
+                self.attachments
+                    .iter()
+                    .map(|attachment| attachment.file_name.as_str()),
+            )
+            .collect()
+    }
 }
 
 #[derive(serde::Serialize, Debug, Clone, Eq, PartialEq)]
@@ -141,16 +246,44 @@ pub enum EntryData {
         username: Option<String>,
     },
     SecureNote,
+    SshKey {
+        private_key: Option<String>,
+        public_key: Option<String>,
+        fingerprint: Option<String>,
+    },
+    // cipher types added to bitwarden after this version of rbw was
+    // written, which are kept so that they can be reported rather than
+    // silently dropped
+    Unsupported {
+        ty: u32,
+    },
 }
 
 #[derive(
     serde::Serialize, serde::Deserialize, Debug, Clone, Eq, PartialEq,
 )]
 pub struct Field {
+    // not present in databases synced by older versions
+    #[serde(default)]
+    pub ty: Option<crate::api::FieldType>,
     pub name: Option<String>,
     pub value: Option<String>,
 }
 
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Eq, PartialEq,
+)]
+pub struct Attachment {
+    pub id: String,
+    pub file_name: String,
+    // the key the attachment contents are encrypted with, itself encrypted
+    // with the entry's key. attachments uploaded by very old clients don't
+    // have one, and are encrypted with the entry's key directly.
+    pub key: Option<String>,
+    // human readable, as given by the server
+    pub size: Option<String>,
+}
+
 #[derive(
     serde::Serialize, serde::Deserialize, Debug, Clone, Eq, PartialEq,
 )]
@@ -159,6 +292,17 @@ pub struct HistoryEntry {
     pub password: String,
 }
 
+// an entry which has been changed locally since the last sync
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Eq, PartialEq,
+)]
+pub struct PendingEntry {
+    // encrypted, like the name stored in the entry itself
+    pub name: String,
+    pub org_id: Option<String>,
+    pub removed: bool,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Default, Debug)]
 pub struct Db {
     pub access_token: Option<String>,
@@ -171,8 +315,14 @@ pub struct Db {
     pub protected_key: Option<String>,
     pub protected_private_key: Option<String>,
     pub protected_org_keys: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub org_names: std::collections::HashMap<String, String>,
 
     pub entries: Vec<Entry>,
+
+    // keyed by entry id, cleared by a full sync
+    #[serde(default)]
+    pub pending: std::collections::BTreeMap<String, PendingEntry>,
 }
 
 impl Db {
@@ -291,6 +441,56 @@ impl Db {
         Ok(())
     }
 
+    // used to keep the local copy of the database up to date after making a
+    // change on the server without needing to do a full sync
+    pub fn upsert_entry(&mut self, entry: Entry) {
+        if let Some(existing) = self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.id == entry.id)
+        {
+            *existing = entry;
+        } else {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn remove_entry(&mut self, id: &str) {
+        self.entries.retain(|entry| entry.id != id);
+    }
+
+    // compares the current entries against a copy taken before a local
+    // modification, and records every entry which differs as pending until
+    // the next sync
+    pub fn mark_pending(&mut self, before: &[Entry]) {
+        for entry in &self.entries {
+            if before.iter().any(|old| old == entry) {
+                continue;
+            }
+            self.pending.insert(
+                entry.id.clone(),
+                PendingEntry {
+                    name: entry.name.clone(),
+                    org_id: entry.org_id.clone(),
+                    removed: false,
+                },
+            );
+        }
+        for old in before {
+            if self.entries.iter().any(|entry| entry.id == old.id) {
+                continue;
+            }
+            self.pending.insert(
+                old.id.clone(),
+                PendingEntry {
+                    name: old.name.clone(),
+                    org_id: old.org_id.clone(),
+                    removed: true,
+                },
+            );
+        }
+    }
+
     #[must_use]
     pub fn needs_login(&self) -> bool {
         self.access_token.is_none()
@@ -300,3 +500,91 @@ impl Db {
             || self.protected_key.is_none()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_upsert_remove_entry() {
+        let mut db = Db::new();
+        db.upsert_entry(make_entry("a", "first"));
+        db.upsert_entry(make_entry("b", "second"));
+        assert_eq!(db.entries.len(), 2);
+
+        db.upsert_entry(make_entry("a", "renamed"));
+        assert_eq!(db.entries.len(), 2);
+        assert_eq!(db.entries[0].name, "renamed");
+
+        db.remove_entry("a");
+        assert_eq!(db.entries.len(), 1);
+        assert_eq!(db.entries[0].id, "b");
+
+        db.remove_entry("nonexistent");
+        assert_eq!(db.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_mark_pending() {
+        let mut db = Db::new();
+        db.upsert_entry(make_entry("a", "first"));
+        db.upsert_entry(make_entry("b", "second"));
+        db.upsert_entry(make_entry("c", "third"));
+        let before = db.entries.clone();
+
+        db.upsert_entry(make_entry("a", "renamed"));
+        db.remove_entry("b");
+        db.upsert_entry(make_entry("d", "fourth"));
+        db.mark_pending(&before);
+
+        assert_eq!(db.pending.len(), 3);
+        assert_eq!(db.pending["a"].name, "renamed");
+        assert!(!db.pending["a"].removed);
+        assert_eq!(db.pending["b"].name, "second");
+        assert!(db.pending["b"].removed);
+        assert!(!db.pending["d"].removed);
+        assert!(!db.pending.contains_key("c"));
+    }
+
+    #[test]
+    fn test_cipherstrings() {
+        let mut entry = make_entry("a", "name");
+        entry.folder = Some("folder".to_string());
+        entry.notes = Some("notes".to_string());
+        entry.fields = vec![Field {
+            ty: None,
+            name: Some("field name".to_string()),
+            value: None,
+        }];
+        entry.data = EntryData::Login {
+            username: None,
+            password: Some("password".to_string()),
+            totp: None,
+            uris: vec![Uri {
+                uri: "uri".to_string(),
+                match_type: None,
+            }],
+        };
+        assert_eq!(
+            entry.cipherstrings(),
+            vec!["name", "password", "uri", "field name", "notes"]
+        );
+    }
+
+    fn make_entry(id: &str, name: &str) -> Entry {
+        Entry {
+            id: id.to_string(),
+            org_id: None,
+            folder: None,
+            folder_id: None,
+            name: name.to_string(),
+            data: EntryData::SecureNote,
+            fields: vec![],
+            notes: None,
+            history: vec![],
+            creation_date: None,
+            revision_date: None,
+            attachments: vec![],
+        }
+    }
+}
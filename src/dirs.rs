@@ -26,6 +26,14 @@ pub fn make_all() -> Result<()> {
         file: runtime_dir,
     })?;
 
+    let db_dir = db_dir();
+    std::fs::create_dir_all(&db_dir).map_err(|source| {
+        Error::CreateDirectory {
+            source,
+            file: db_dir,
+        }
+    })?;
+
     let data_dir = data_dir();
     std::fs::create_dir_all(&data_dir).map_err(|source| {
         Error::CreateDirectory {
@@ -49,7 +57,15 @@ pub fn db_file(server: &str, email: &str) -> std::path::PathBuf {
     let server =
         percent_encoding::percent_encode(server.as_bytes(), INVALID_PATH)
             .to_string();
-    cache_dir().join(format!("{server}:{email}.json"))
+    db_dir().join(format!("{server}:{email}.json"))
+}
+
+#[must_use]
+pub fn usage_file(server: &str, email: &str) -> std::path::PathBuf {
+    let server =
+        percent_encoding::percent_encode(server.as_bytes(), INVALID_PATH)
+            .to_string();
+    db_dir().join(format!("{server}:{email}.usage.json"))
 }
 
 #[must_use]
@@ -91,6 +107,16 @@ fn cache_dir() -> std::path::PathBuf {
     project_dirs.cache_dir().to_path_buf()
 }
 
+// the local copy of the database is just a cache of the server's data, but
+// some users prefer to keep it somewhere specific (like an encrypted volume)
+#[must_use]
+pub fn db_dir() -> std::path::PathBuf {
+    match std::env::var_os("RBW_DATA_DIR") {
+        Some(dir) if !dir.is_empty() => dir.into(),
+        _ => cache_dir(),
+    }
+}
+
 #[must_use]
 fn data_dir() -> std::path::PathBuf {
     let project_dirs =
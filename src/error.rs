@@ -1,5 +1,20 @@
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("failed to connect to rbw-agent")]
+    AgentConnect { source: std::io::Error },
+
+    #[error("{error}")]
+    AgentError { error: String },
+
+    #[error("failed to communicate with rbw-agent")]
+    AgentIo { source: std::io::Error },
+
+    #[error("failed to parse message from rbw-agent")]
+    AgentJson { source: serde_json::Error },
+
+    #[error("unexpected message from rbw-agent: {res}")]
+    AgentUnexpectedResponse { res: String },
+
     #[error("email address not set")]
     ConfigMissingEmail,
 
@@ -118,6 +133,18 @@ pub enum Error {
         file: std::path::PathBuf,
     },
 
+    #[error("failed to load password policy from {}", .file.display())]
+    LoadPolicy {
+        source: std::io::Error,
+        file: std::path::PathBuf,
+    },
+
+    #[error("failed to load password policy from {}", .file.display())]
+    LoadPolicyJson {
+        source: serde_json::Error,
+        file: std::path::PathBuf,
+    },
+
     #[error("failed to load client cert from {}", .file.display())]
     LoadClientCert {
         source: tokio::io::Error,
@@ -130,6 +157,18 @@ pub enum Error {
         file: std::path::PathBuf,
     },
 
+    #[error("multiple entries found for '{needle}': {}", .names.join(", "))]
+    MultipleEntries { needle: String, names: Vec<String> },
+
+    #[error("no entry found for '{needle}'")]
+    NoEntry { needle: String },
+
+    #[error("not logged in")]
+    NotLoggedIn,
+
+    #[error("entry {id} is not a login entry")]
+    NotLoginEntry { id: String },
+
     #[error("invalid padding")]
     Padding,
 
@@ -157,6 +196,34 @@ pub enum Error {
     #[error("error waiting for pinentry to exit")]
     PinentryWait { source: tokio::io::Error },
 
+    #[error("no characters left to generate a password from")]
+    PwgenEmptyAlphabet,
+
+    #[error(
+        "password length {len} is shorter than the {total} characters \
+        required by the minimum character counts"
+    )]
+    PwgenMinimumsLength { len: usize, total: usize },
+
+    #[error(
+        "the requested kind of password can't contain the required \
+        minimum numbers of digits, symbols, or uppercase letters"
+    )]
+    PwgenMinimumsUnsatisfiable,
+
+    #[error(
+        "password length {len} is shorter than the minimum length of \
+        {min_length} required by the password policy"
+    )]
+    PwgenPolicyLength { len: usize, min_length: usize },
+
+    #[error(
+        "failed to generate a password which satisfies the password policy \
+        (the password may be too short, or the requested kind of password \
+        may not be able to contain the required characters)"
+    )]
+    PwgenPolicyUnsatisfiable,
+
     #[error("This device has not yet been registered with the Bitwarden server. Run `rbw register` first, and then try again.")]
     RegistrationRequired,
 
@@ -169,6 +236,12 @@ pub enum Error {
     #[error("api request returned error: {status}")]
     RequestFailed { status: u16 },
 
+    #[error("api request was rate limited")]
+    RequestRateLimited { retry_after: Option<u64> },
+
+    #[error("api request timed out")]
+    RequestTimeout,
+
     #[error("api request unauthorized")]
     RequestUnauthorized,
 
@@ -225,11 +298,32 @@ pub enum Error {
     #[error("unimplemented cipherstring type: {ty}")]
     UnimplementedCipherStringType { ty: String },
 
+    #[error("unsupported cipher type: {ty}")]
+    UnsupportedCipherType { ty: u32 },
+
     #[error("error writing to pinentry stdin")]
     WriteStdin { source: tokio::io::Error },
 
     #[error("invalid kdf type: {ty}")]
     InvalidKdfType { ty: String },
+
+    #[error(
+        "invalid uri match type: {ty} (expected one of domain, host, \
+        starts_with, exact, regular_expression, never)"
+    )]
+    InvalidUriMatchType { ty: String },
+}
+
+impl Error {
+    // whether the request might succeed if it is tried again
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RequestRateLimited { .. } | Self::RequestTimeout => true,
+            Self::RequestFailed { status } => *status >= 500,
+            _ => false,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
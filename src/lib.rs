@@ -32,4 +32,5 @@ pub mod pinentry;
 mod prelude;
 pub mod protocol;
 pub mod pwgen;
+pub mod vault;
 pub mod wordlist;
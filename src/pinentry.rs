@@ -5,6 +5,7 @@ use tokio::io::AsyncWriteExt as _;
 
 pub async fn getpin(
     pinentry: &str,
+    title: &str,
     prompt: &str,
     desc: &str,
     err: Option<&str>,
@@ -29,7 +30,7 @@ pub async fn getpin(
 
     let mut ncommands = 1;
     stdin
-        .write_all(b"SETTITLE rbw\n")
+        .write_all(format!("SETTITLE {title}\n").as_bytes())
         .await
         .map_err(|source| Error::WriteStdin { source })?;
     ncommands += 1;
@@ -1,3 +1,5 @@
+use zeroize::Zeroize as _;
+
 // eventually it would be nice to make this a const function so that we could
 // just get the version from a variable directly, but this is fine for now
 #[must_use]
@@ -15,6 +17,10 @@ pub fn version() -> u32 {
 pub struct Request {
     pub tty: Option<String>,
     pub action: Action,
+    // overrides the network_timeout configuration option while handling
+    // this request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_timeout: Option<u64>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -34,11 +40,30 @@ pub enum Action {
         plaintext: String,
         org_id: Option<String>,
     },
+    // attachment contents are binary, so they are sent base64 encoded
+    DecryptAttachment {
+        data: String,
+        key: Option<String>,
+        org_id: Option<String>,
+    },
+    // entries are only returned if they were cached at the given revision
+    DecryptCacheLoad {
+        revisions: std::collections::HashMap<String, Option<String>>,
+    },
+    DecryptCacheStore {
+        entries: Vec<DecryptCacheEntry>,
+    },
+    DecryptCacheClear,
     ClipboardStore {
         text: String,
+        // number of seconds after which to clear the clipboard again, if it
+        // still contains this text
+        #[serde(default)]
+        clear_after: Option<u64>,
     },
     Quit,
     Version,
+    DataDir,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -48,5 +73,27 @@ pub enum Response {
     Error { error: String },
     Decrypt { plaintext: String },
     Encrypt { cipherstring: String },
+    DecryptAttachment { data: String },
+    DecryptCache { entries: Vec<DecryptCacheEntry> },
     Version { version: u32 },
+    // the directory the agent stores the local copy of the database in
+    DataDir { dir: String },
+}
+
+// the decrypted values of a single entry's fields, keyed by their encrypted
+// cipherstrings. the agent can hold on to these for as long as it stays
+// unlocked, so they are zeroed when dropped.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DecryptCacheEntry {
+    pub id: String,
+    pub revision_date: Option<String>,
+    pub plaintexts: std::collections::HashMap<String, String>,
+}
+
+impl Drop for DecryptCacheEntry {
+    fn drop(&mut self) {
+        for plaintext in self.plaintexts.values_mut() {
+            plaintext.zeroize();
+        }
+    }
 }
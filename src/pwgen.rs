@@ -1,3 +1,5 @@
+use crate::prelude::*;
+
 use rand::seq::SliceRandom as _;
 
 const SYMBOLS: &[u8] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
@@ -7,16 +9,69 @@ const LETTERS: &[u8] =
 const NONCONFUSABLES: &[u8] = b"34678abcdefhjkmnpqrtuwxy";
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum Type {
+pub enum Type<'a> {
     AllChars,
     NoSymbols,
     Numbers,
     NonConfusables,
-    Diceware,
+    Diceware(Passphrase<'a>),
 }
 
-#[must_use]
-pub fn pwgen(ty: Type, len: usize) -> String {
+/// How the words of a diceware password are put together.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Passphrase<'a> {
+    pub separator: &'a str,
+    /// Capitalize the first letter of each word.
+    pub capitalize: bool,
+    /// Append a random digit to one of the words.
+    pub include_number: bool,
+}
+
+impl Default for Passphrase<'_> {
+    fn default() -> Self {
+        Self {
+            separator: " ",
+            capitalize: false,
+            include_number: false,
+        }
+    }
+}
+
+/// Minimum numbers of characters of specific classes that generated
+/// passwords must contain. Not supported for diceware passwords.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct Minimums {
+    pub digits: usize,
+    pub symbols: usize,
+    pub uppercase: usize,
+}
+
+impl Minimums {
+    fn classes(self) -> [(usize, CharClass); 3] {
+        [
+            (self.digits, CharClass::Numbers),
+            (self.symbols, CharClass::Symbols),
+            (self.uppercase, CharClass::Uppercase),
+        ]
+    }
+
+    fn total(self) -> usize {
+        self.digits + self.symbols + self.uppercase
+    }
+}
+
+pub fn pwgen(ty: Type, len: usize, exclude: &str) -> Result<String> {
+    pwgen_with_minimums(ty, len, exclude, Minimums::default())
+}
+
+/// Like [`pwgen`], but guarantees that the password contains at least the
+/// given numbers of characters of each class.
+pub fn pwgen_with_minimums(
+    ty: Type,
+    len: usize,
+    exclude: &str,
+    minimums: Minimums,
+) -> Result<String> {
     let mut rng = rand::thread_rng();
 
     let alphabet = match ty {
@@ -43,28 +98,237 @@ pub fn pwgen(ty: Type, len: usize) -> String {
             v.extend(NONCONFUSABLES.iter().copied());
             v
         }
-        Type::Diceware => {
-            return diceware(&mut rng, len);
+        Type::Diceware(passphrase) => {
+            if minimums.total() > 0 {
+                return Err(Error::PwgenMinimumsUnsatisfiable);
+            }
+            return diceware(&mut rng, len, exclude, passphrase);
         }
     };
+    let alphabet: Vec<u8> = alphabet
+        .into_iter()
+        .filter(|c| !exclude.contains(char::from(*c)))
+        .collect();
+    if alphabet.is_empty() {
+        return Err(Error::PwgenEmptyAlphabet);
+    }
+    if minimums.total() > len {
+        return Err(Error::PwgenMinimumsLength {
+            len,
+            total: minimums.total(),
+        });
+    }
 
+    // the required characters are chosen first, and then shuffled in among
+    // the rest of the password
     let mut pass = vec![];
+    for (count, class) in minimums.classes() {
+        if count == 0 {
+            continue;
+        }
+        let class_alphabet: Vec<u8> = alphabet
+            .iter()
+            .copied()
+            .filter(|c| class.contains(char::from(*c)))
+            .collect();
+        if class_alphabet.is_empty() {
+            return Err(Error::PwgenMinimumsUnsatisfiable);
+        }
+        pass.extend(
+            std::iter::repeat_with(|| {
+                class_alphabet.choose(&mut rng).unwrap()
+            })
+            .take(count),
+        );
+    }
     pass.extend(
         std::iter::repeat_with(|| alphabet.choose(&mut rng).unwrap())
-            .take(len),
+            .take(len - pass.len()),
     );
+    pass.shuffle(&mut rng);
     // unwrap is safe because the method of generating passwords guarantees
     // valid utf8
-    String::from_utf8(pass).unwrap()
+    Ok(String::from_utf8(pass).unwrap())
 }
 
-fn diceware(rng: &mut impl rand::RngCore, len: usize) -> String {
+fn diceware(
+    rng: &mut impl rand::RngCore,
+    len: usize,
+    exclude: &str,
+    passphrase: Passphrase,
+) -> Result<String> {
+    let wordlist: Vec<String> = crate::wordlist::EFF_LONG
+        .iter()
+        .map(|word| {
+            if passphrase.capitalize {
+                capitalize(word)
+            } else {
+                (*word).to_string()
+            }
+        })
+        .filter(|word| !word.chars().any(|c| exclude.contains(c)))
+        .collect();
+    if wordlist.is_empty() {
+        return Err(Error::PwgenEmptyAlphabet);
+    }
+
     let mut words = vec![];
     for _ in 0..len {
-        // unwrap is safe because choose only returns None for an empty slice
-        words.push(*crate::wordlist::EFF_LONG.choose(rng).unwrap());
+        // unwrap is safe because we checked that the wordlist is nonempty
+        words.push(wordlist.choose(rng).unwrap().clone());
+    }
+    if passphrase.include_number {
+        let numbers: Vec<u8> = NUMBERS
+            .iter()
+            .copied()
+            .filter(|c| !exclude.contains(char::from(*c)))
+            .collect();
+        let Some(number) = numbers.choose(rng) else {
+            return Err(Error::PwgenEmptyAlphabet);
+        };
+        if let Some(word) = words.choose_mut(rng) {
+            word.push(char::from(*number));
+        }
     }
-    words.join(" ")
+    Ok(words.join(passphrase.separator))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+// passwords are generated randomly and then checked against the policy, so
+// this bounds how long to keep trying when the policy can't be satisfied
+const MAX_POLICY_ATTEMPTS: usize = 1000;
+
+/// Constraints that generated passwords must satisfy, loaded from the file
+/// given by the `generate_policy` configuration option.
+#[derive(serde::Deserialize, Debug, Default, Clone, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub min_length: usize,
+    #[serde(default)]
+    pub required: Vec<CharClass>,
+    #[serde(default)]
+    pub exclude: String,
+}
+
+#[derive(serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Lowercase,
+    Uppercase,
+    Numbers,
+    Symbols,
+}
+
+impl CharClass {
+    fn contains(self, c: char) -> bool {
+        match self {
+            Self::Lowercase => c.is_ascii_lowercase(),
+            Self::Uppercase => c.is_ascii_uppercase(),
+            Self::Numbers => c.is_ascii_digit(),
+            Self::Symbols => SYMBOLS.iter().any(|s| char::from(*s) == c),
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(file: &std::path::Path) -> Result<Self> {
+        let json = std::fs::read_to_string(file).map_err(|source| {
+            Error::LoadPolicy {
+                source,
+                file: file.to_path_buf(),
+            }
+        })?;
+        serde_json::from_str(&json).map_err(|source| Error::LoadPolicyJson {
+            source,
+            file: file.to_path_buf(),
+        })
+    }
+
+    fn allows(&self, password: &str) -> bool {
+        password.chars().count() >= self.min_length
+            && self
+                .required
+                .iter()
+                .all(|class| password.chars().any(|c| class.contains(c)))
+    }
+}
+
+/// Like [`pwgen`], but only returns passwords which satisfy `policy`.
+///
+/// The characters excluded by `policy` are added to `exclude`. For diceware
+/// passwords, `len` is the number of words, so the minimum length is only
+/// checked against the generated password.
+pub fn pwgen_with_policy(
+    ty: Type,
+    len: usize,
+    exclude: &str,
+    minimums: Minimums,
+    policy: &Policy,
+) -> Result<String> {
+    if !matches!(ty, Type::Diceware(_)) && len < policy.min_length {
+        return Err(Error::PwgenPolicyLength {
+            len,
+            min_length: policy.min_length,
+        });
+    }
+
+    let exclude = format!("{exclude}{}", policy.exclude);
+    for _ in 0..MAX_POLICY_ATTEMPTS {
+        let password = pwgen_with_minimums(ty, len, &exclude, minimums)?;
+        if policy.allows(&password) {
+            return Ok(password);
+        }
+    }
+    Err(Error::PwgenPolicyUnsatisfiable)
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum UsernameType<'a> {
+    RandomWords,
+    Catchall { domain: &'a str },
+    Plus { local: &'a str, domain: &'a str },
+}
+
+#[must_use]
+pub fn username(ty: UsernameType) -> String {
+    let mut rng = rand::thread_rng();
+
+    match ty {
+        UsernameType::RandomWords => {
+            // unwraps are safe because choose only returns None for an
+            // empty slice
+            let first = crate::wordlist::EFF_LONG.choose(&mut rng).unwrap();
+            let second = crate::wordlist::EFF_LONG.choose(&mut rng).unwrap();
+            let number = rand::Rng::gen_range(&mut rng, 0..100);
+            format!("{first}-{second}-{number}")
+        }
+        UsernameType::Catchall { domain } => {
+            format!("{}@{domain}", random_lowercase(&mut rng, 8))
+        }
+        UsernameType::Plus { local, domain } => {
+            format!("{local}+{}@{domain}", random_lowercase(&mut rng, 8))
+        }
+    }
+}
+
+fn random_lowercase(rng: &mut impl rand::RngCore, len: usize) -> String {
+    let mut alphabet = vec![];
+    alphabet.extend(NUMBERS.iter().copied());
+    alphabet.extend(LETTERS.iter().copied().filter(u8::is_ascii_lowercase));
+
+    let s: Vec<u8> =
+        std::iter::repeat_with(|| *alphabet.choose(rng).unwrap())
+            .take(len)
+            .collect();
+    // unwrap is safe because the alphabet is ascii
+    String::from_utf8(s).unwrap()
 }
 
 #[cfg(test)]
@@ -73,29 +337,189 @@ mod test {
 
     #[test]
     fn test_pwgen() {
-        let pw = pwgen(Type::AllChars, 50);
+        let pw = pwgen(Type::AllChars, 50, "").unwrap();
         assert_eq!(pw.len(), 50);
         // technically this could fail, but the chances are incredibly low
         // (around 0.000009%)
         assert_duplicates(&pw);
 
-        let pw = pwgen(Type::AllChars, 100);
+        let pw = pwgen(Type::AllChars, 100, "").unwrap();
         assert_eq!(pw.len(), 100);
         assert_duplicates(&pw);
 
-        let pw = pwgen(Type::NoSymbols, 100);
+        let pw = pwgen(Type::NoSymbols, 100, "").unwrap();
         assert_eq!(pw.len(), 100);
         assert_duplicates(&pw);
 
-        let pw = pwgen(Type::Numbers, 100);
+        let pw = pwgen(Type::Numbers, 100, "").unwrap();
         assert_eq!(pw.len(), 100);
         assert_duplicates(&pw);
 
-        let pw = pwgen(Type::NonConfusables, 100);
+        let pw = pwgen(Type::NonConfusables, 100, "").unwrap();
         assert_eq!(pw.len(), 100);
         assert_duplicates(&pw);
     }
 
+    #[test]
+    fn test_pwgen_exclude() {
+        let pw = pwgen(Type::AllChars, 100, "!@#$%^&*()aeiou").unwrap();
+        assert_eq!(pw.len(), 100);
+        assert!(!pw.contains(&['!', '@', '#', '$', '%', '^', '&', '*'][..]));
+        assert!(!pw.contains(&['(', ')', 'a', 'e', 'i', 'o', 'u'][..]));
+
+        let pw = pwgen(Type::Numbers, 100, "012345678").unwrap();
+        assert_eq!(pw, "9".repeat(100));
+
+        assert!(pwgen(Type::Numbers, 10, "0123456789").is_err());
+    }
+
+    #[test]
+    fn test_pwgen_with_minimums() {
+        let minimums = Minimums {
+            digits: 3,
+            symbols: 2,
+            uppercase: 4,
+        };
+        for _ in 0..100 {
+            let pw = pwgen_with_minimums(Type::AllChars, 10, "", minimums)
+                .unwrap();
+            assert_eq!(pw.len(), 10);
+            assert!(pw.chars().filter(char::is_ascii_digit).count() >= 3);
+            assert!(
+                pw.chars()
+                    .filter(|c| CharClass::Symbols.contains(*c))
+                    .count()
+                    >= 2
+            );
+            assert!(pw.chars().filter(char::is_ascii_uppercase).count() >= 4);
+        }
+
+        let pw =
+            pwgen_with_minimums(Type::AllChars, 9, "", minimums).unwrap();
+        assert_eq!(pw.len(), 9);
+        assert!(matches!(
+            pwgen_with_minimums(Type::AllChars, 8, "", minimums),
+            Err(Error::PwgenMinimumsLength { len: 8, total: 9 })
+        ));
+        assert!(matches!(
+            pwgen_with_minimums(Type::NoSymbols, 20, "", minimums),
+            Err(Error::PwgenMinimumsUnsatisfiable)
+        ));
+        assert!(matches!(
+            pwgen_with_minimums(Type::AllChars, 20, "0123456789", minimums),
+            Err(Error::PwgenMinimumsUnsatisfiable)
+        ));
+    }
+
+    #[test]
+    fn test_diceware() {
+        let pw = pwgen(Type::Diceware(Passphrase::default()), 5, "").unwrap();
+        assert_eq!(pw.split(' ').count(), 5);
+
+        let pw = pwgen(
+            Type::Diceware(Passphrase {
+                separator: "-",
+                capitalize: true,
+                include_number: true,
+            }),
+            4,
+            "",
+        )
+        .unwrap();
+        // some words in the list contain hyphens themselves
+        assert!(pw.split('-').count() >= 4);
+        assert!(pw.starts_with(|c: char| c.is_ascii_uppercase()));
+        assert_eq!(pw.chars().filter(char::is_ascii_digit).count(), 1);
+
+        let pw = pwgen(
+            Type::Diceware(Passphrase {
+                separator: "",
+                capitalize: false,
+                include_number: true,
+            }),
+            3,
+            "012345678",
+        )
+        .unwrap();
+        assert!(pw.contains('9'));
+        assert!(!pw.contains(' '));
+    }
+
+    #[test]
+    fn test_pwgen_with_policy() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+                "min_length": 20,
+                "required": ["lowercase", "uppercase", "numbers"],
+                "exclude": "aeiou"
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            pwgen_with_policy(
+                Type::AllChars,
+                12,
+                "",
+                Minimums::default(),
+                &policy
+            ),
+            Err(Error::PwgenPolicyLength {
+                len: 12,
+                min_length: 20
+            })
+        ));
+
+        let pw = pwgen_with_policy(
+            Type::NoSymbols,
+            20,
+            "xyz",
+            Minimums::default(),
+            &policy,
+        )
+        .unwrap();
+        assert_eq!(pw.len(), 20);
+        assert!(pw.chars().any(|c| c.is_ascii_lowercase()));
+        assert!(pw.chars().any(|c| c.is_ascii_uppercase()));
+        assert!(pw.chars().any(|c| c.is_ascii_digit()));
+        assert!(!pw.contains(&['a', 'e', 'i', 'o', 'u', 'x', 'y', 'z'][..]));
+
+        assert!(matches!(
+            pwgen_with_policy(
+                Type::Numbers,
+                20,
+                "",
+                Minimums::default(),
+                &policy
+            ),
+            Err(Error::PwgenPolicyUnsatisfiable)
+        ));
+
+        assert!(serde_json::from_str::<Policy>(r#"{"min_len": 20}"#).is_err());
+    }
+
+    #[test]
+    fn test_username() {
+        let words = username(UsernameType::RandomWords);
+        let (_, number) = words.rsplit_once('-').unwrap();
+        assert!(number.parse::<u8>().unwrap() < 100);
+
+        let catchall = username(UsernameType::Catchall {
+            domain: "example.com",
+        });
+        let (local, domain) = catchall.split_once('@').unwrap();
+        assert_eq!(local.len(), 8);
+        assert_eq!(domain, "example.com");
+
+        let plus = username(UsernameType::Plus {
+            local: "me",
+            domain: "example.com",
+        });
+        assert!(plus.starts_with("me+"));
+        assert!(plus.ends_with("@example.com"));
+        assert_eq!(plus.len(), "me+@example.com".len() + 8);
+    }
+
     #[track_caller]
     fn assert_duplicates(s: &str) {
         let mut set = std::collections::HashSet::new();
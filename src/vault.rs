@@ -0,0 +1,353 @@
+//! A higher level interface to the local copy of the password database.
+//!
+//! [`Vault`] wraps loading the configuration and the local database, and
+//! talks to a running `rbw-agent` for unlocking, decrypting, and encrypting,
+//! so that other tools can read and modify entries without reimplementing the
+//! plumbing that the `rbw` command line client uses (which decrypts and
+//! encrypts through the same [`decrypt`] and [`encrypt`] functions). The
+//! agent is not started automatically; running any `rbw` command (such as
+//! `rbw unlock`) will start it.
+
+use crate::prelude::*;
+
+use std::io::{BufRead as _, Write as _};
+
+/// A decrypted view of a single entry in the database.
+///
+/// Fields which only exist for login entries (`username`, `password`,
+/// `totp`, and `uris`) are empty for other entry types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptedEntry {
+    pub id: String,
+    pub folder: Option<String>,
+    pub name: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub totp: Option<String>,
+    pub uris: Vec<String>,
+    pub notes: Option<String>,
+}
+
+/// The local password database of the configured account.
+pub struct Vault {
+    server: String,
+    email: String,
+    db: crate::db::Db,
+}
+
+impl Vault {
+    /// Loads the configuration and the local database. This does not require
+    /// the database to be unlocked.
+    pub fn open() -> Result<Self> {
+        let config = crate::config::Config::load()?;
+        let email = config.email.clone().ok_or(Error::ConfigMissingEmail)?;
+        let server = config.server_name();
+        let db = crate::db::Db::load(&server, &email)?;
+        Ok(Self { server, email, db })
+    }
+
+    /// The underlying (encrypted) database.
+    #[must_use]
+    pub fn db(&self) -> &crate::db::Db {
+        &self.db
+    }
+
+    /// Asks the agent to unlock the database, prompting for the master
+    /// password via pinentry if it isn't already unlocked.
+    pub fn unlock(&self) -> Result<()> {
+        agent_action(crate::protocol::Action::Unlock)
+    }
+
+    /// Asks the agent to sync the database from the server, and reloads the
+    /// local copy.
+    pub fn sync(&mut self) -> Result<()> {
+        agent_action(crate::protocol::Action::Sync)?;
+        self.db = crate::db::Db::load(&self.server, &self.email)?;
+        Ok(())
+    }
+
+    /// Decrypts every entry in the database.
+    pub fn entries(&self) -> Result<Vec<DecryptedEntry>> {
+        self.db.entries.iter().map(decrypt_entry).collect()
+    }
+
+    /// Finds a single entry by UUID or by name. Exact name matches are
+    /// preferred over entries whose name contains `needle`.
+    pub fn find(&self, needle: &str) -> Result<DecryptedEntry> {
+        find_entry(self.entries()?, needle)
+    }
+
+    /// Changes the password of the login entry with the given id, moving the
+    /// previous password to the password history.
+    pub fn set_password(&mut self, id: &str, password: &str) -> Result<()> {
+        let mut entry = self.entry(id)?.clone();
+        let crate::db::EntryData::Login {
+            password: entry_password,
+            ..
+        } = &mut entry.data
+        else {
+            return Err(Error::NotLoginEntry { id: id.to_string() });
+        };
+        if let Some(prev_password) = entry_password.take() {
+            entry.history.insert(
+                0,
+                crate::db::HistoryEntry {
+                    last_used_date: humantime::format_rfc3339(
+                        std::time::SystemTime::now(),
+                    )
+                    .to_string(),
+                    password: prev_password,
+                },
+            );
+        }
+        *entry_password = Some(encrypt(password, entry.org_id.as_deref())?);
+
+        let (access_token, refresh_token) = self.tokens()?;
+        let (new_access_token, ()) = crate::actions::edit(
+            &access_token,
+            &refresh_token,
+            &entry.id,
+            entry.org_id.as_deref(),
+            &entry.name,
+            &entry.data,
+            entry.notes.as_deref(),
+            entry.folder_id.as_deref(),
+            &entry.history,
+        )?;
+        self.update(new_access_token, |db| db.upsert_entry(entry))
+    }
+
+    /// Removes the entry with the given id.
+    pub fn remove(&mut self, id: &str) -> Result<()> {
+        let entry = self.entry(id)?;
+        let (access_token, refresh_token) = self.tokens()?;
+        let (new_access_token, ()) =
+            crate::actions::remove(&access_token, &refresh_token, &entry.id)?;
+        self.update(new_access_token, |db| db.remove_entry(id))
+    }
+
+    fn entry(&self, id: &str) -> Result<&crate::db::Entry> {
+        self.db
+            .entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| Error::NoEntry {
+                needle: id.to_string(),
+            })
+    }
+
+    fn tokens(&self) -> Result<(String, String)> {
+        match (&self.db.access_token, &self.db.refresh_token) {
+            (Some(access_token), Some(refresh_token)) => {
+                Ok((access_token.clone(), refresh_token.clone()))
+            }
+            _ => Err(Error::NotLoggedIn),
+        }
+    }
+
+    // the change was already made on the server, so the local copy is
+    // updated to match (and the entry tracked as pending, like the command
+    // line client does) until the next sync replaces it
+    fn update(
+        &mut self,
+        new_access_token: Option<String>,
+        f: impl FnOnce(&mut crate::db::Db),
+    ) -> Result<()> {
+        if new_access_token.is_some() {
+            self.db.access_token = new_access_token;
+        }
+        let before = self.db.entries.clone();
+        f(&mut self.db);
+        self.db.mark_pending(&before);
+        self.db.save(&self.server, &self.email)
+    }
+}
+
+fn find_entry(
+    entries: Vec<DecryptedEntry>,
+    needle: &str,
+) -> Result<DecryptedEntry> {
+    if uuid::Uuid::parse_str(needle).is_ok() {
+        return entries
+            .into_iter()
+            .find(|entry| entry.id == needle)
+            .ok_or_else(|| Error::NoEntry {
+                needle: needle.to_string(),
+            });
+    }
+
+    let (exact, partial): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .filter(|entry| entry.name.contains(needle))
+        .partition(|entry| entry.name == needle);
+    let mut matches = if exact.is_empty() { partial } else { exact };
+    match matches.len() {
+        0 => Err(Error::NoEntry {
+            needle: needle.to_string(),
+        }),
+        1 => Ok(matches.remove(0)),
+        _ => Err(Error::MultipleEntries {
+            needle: needle.to_string(),
+            names: matches.into_iter().map(|entry| entry.name).collect(),
+        }),
+    }
+}
+
+fn decrypt_entry(entry: &crate::db::Entry) -> Result<DecryptedEntry> {
+    let org_id = entry.org_id.as_deref();
+    let decrypt_opt = |field: Option<&str>, org_id: Option<&str>| {
+        field.map(|field| decrypt(field, org_id)).transpose()
+    };
+
+    let (username, password, totp, uris) = match &entry.data {
+        crate::db::EntryData::Login {
+            username,
+            password,
+            totp,
+            uris,
+        } => (
+            decrypt_opt(username.as_deref(), org_id)?,
+            decrypt_opt(password.as_deref(), org_id)?,
+            decrypt_opt(totp.as_deref(), org_id)?,
+            uris.iter()
+                .map(|uri| decrypt(&uri.uri, org_id))
+                .collect::<Result<_>>()?,
+        ),
+        _ => (None, None, None, vec![]),
+    };
+
+    Ok(DecryptedEntry {
+        id: entry.id.clone(),
+        // folder names are always encrypted with the user's key
+        folder: decrypt_opt(entry.folder.as_deref(), None)?,
+        name: decrypt(&entry.name, org_id)?,
+        username,
+        password,
+        totp,
+        uris,
+        notes: decrypt_opt(entry.notes.as_deref(), org_id)?,
+    })
+}
+
+/// Asks the agent to decrypt a cipherstring with the given organization's
+/// key (or the user's key, if `org_id` is `None`).
+pub fn decrypt(cipherstring: &str, org_id: Option<&str>) -> Result<String> {
+    match agent_request(crate::protocol::Action::Decrypt {
+        cipherstring: cipherstring.to_string(),
+        org_id: org_id.map(std::string::ToString::to_string),
+    })? {
+        crate::protocol::Response::Decrypt { plaintext } => Ok(plaintext),
+        res => Err(unexpected_response(&res)),
+    }
+}
+
+/// Asks the agent to encrypt a value with the given organization's key (or
+/// the user's key, if `org_id` is `None`).
+pub fn encrypt(plaintext: &str, org_id: Option<&str>) -> Result<String> {
+    match agent_request(crate::protocol::Action::Encrypt {
+        plaintext: plaintext.to_string(),
+        org_id: org_id.map(std::string::ToString::to_string),
+    })? {
+        crate::protocol::Response::Encrypt { cipherstring } => {
+            Ok(cipherstring)
+        }
+        res => Err(unexpected_response(&res)),
+    }
+}
+
+fn agent_action(action: crate::protocol::Action) -> Result<()> {
+    match agent_request(action)? {
+        crate::protocol::Response::Ack => Ok(()),
+        res => Err(unexpected_response(&res)),
+    }
+}
+
+// the agent handles a single request per connection
+fn agent_request(
+    action: crate::protocol::Action,
+) -> Result<crate::protocol::Response> {
+    let mut sock =
+        std::os::unix::net::UnixStream::connect(crate::dirs::socket_file())
+            .map_err(|source| Error::AgentConnect { source })?;
+
+    let req = crate::protocol::Request {
+        tty: nix::unistd::ttyname(0)
+            .ok()
+            .and_then(|p| p.to_str().map(std::string::ToString::to_string)),
+        action,
+        network_timeout: crate::actions::network_timeout_override(),
+    };
+    let mut msg = serde_json::to_string(&req)
+        .map_err(|source| Error::AgentJson { source })?;
+    msg.push('\n');
+    sock.write_all(msg.as_bytes())
+        .map_err(|source| Error::AgentIo { source })?;
+
+    let mut line = String::new();
+    std::io::BufReader::new(sock)
+        .read_line(&mut line)
+        .map_err(|source| Error::AgentIo { source })?;
+    match serde_json::from_str(&line)
+        .map_err(|source| Error::AgentJson { source })?
+    {
+        crate::protocol::Response::Error { error } => {
+            Err(Error::AgentError { error })
+        }
+        res => Ok(res),
+    }
+}
+
+fn unexpected_response(res: &crate::protocol::Response) -> Error {
+    Error::AgentUnexpectedResponse {
+        res: format!("{res:?}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_entry() {
+        let entries = vec![
+            make_entry("2e9f3f6a-46b1-4d3c-8b7f-f4a1c0f5a0b1", "github"),
+            make_entry("f0b8d1a4-7f55-4a3e-9c1b-8c3f3f8f0e21", "gitlab"),
+            make_entry("c8f4a8e6-0d1b-45e1-8c1b-1f0f4b2a7c33", "git"),
+        ];
+
+        let found = find_entry(entries.clone(), "github").unwrap();
+        assert_eq!(found.name, "github");
+        let found = find_entry(entries.clone(), "git").unwrap();
+        assert_eq!(found.name, "git");
+        let found = find_entry(entries.clone(), "lab").unwrap();
+        assert_eq!(found.name, "gitlab");
+        let found = find_entry(
+            entries.clone(),
+            "f0b8d1a4-7f55-4a3e-9c1b-8c3f3f8f0e21",
+        )
+        .unwrap();
+        assert_eq!(found.name, "gitlab");
+
+        assert!(matches!(
+            find_entry(entries.clone(), "it"),
+            Err(Error::MultipleEntries { .. })
+        ));
+        assert!(matches!(
+            find_entry(entries, "bitwarden"),
+            Err(Error::NoEntry { .. })
+        ));
+    }
+
+    fn make_entry(id: &str, name: &str) -> DecryptedEntry {
+        DecryptedEntry {
+            id: id.to_string(),
+            folder: None,
+            name: name.to_string(),
+            username: None,
+            password: None,
+            totp: None,
+            uris: vec![],
+            notes: None,
+        }
+    }
+}